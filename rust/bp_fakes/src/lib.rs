@@ -21,6 +21,7 @@ use serde_json::{self, Value};
 
 use futures::{future::BoxFuture, FutureExt, StreamExt};
 use std::ops::{DerefMut, RangeInclusive};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 use std::time::Duration;
 use std::vec;
@@ -33,6 +34,8 @@ use std::time::Instant;
 #[derive(Clone)]
 pub struct FakeConnectorCallRegistry {
     pub actions: Arc<Mutex<HashMap<u32, Vec<FakeMessage>>>>,
+    server_sender: Arc<Mutex<Option<Sender<ButtplugCurrentSpecServerMessage>>>>,
+    stop_all_calls: Arc<AtomicUsize>,
 }
 
 #[derive(Clone, Debug)]
@@ -153,6 +156,16 @@ impl FakeMessage {
         self
     }
 
+    /// Strength of the first scalar in a ScalarCmd
+    pub fn get_strength(&self) -> f64 {
+        match self.message.clone() {
+            message::ButtplugSpecV3ClientMessage::ScalarCmd(cmd) => {
+                cmd.scalars().iter().next().unwrap().scalar()
+            }
+            _ => panic!("Message is not scalar cmd"),
+        }
+    }
+
     pub fn vibration_stopped(&self) -> bool {
         match self.message.clone() {
             message::ButtplugSpecV3ClientMessage::ScalarCmd(cmd) => {
@@ -168,6 +181,8 @@ impl FakeConnectorCallRegistry {
     fn default() -> Self {
         Self {
             actions: Arc::new(Mutex::new(HashMap::new())),
+            server_sender: Arc::new(Mutex::new(None)),
+            stop_all_calls: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -195,11 +210,58 @@ impl FakeConnectorCallRegistry {
     pub fn assert_unused(&self, device_id: u32) {
         assert_eq!(self.get_device(device_id).len(), 0);
     }
+
+    /// Number of StopAllDevices messages received
+    pub fn get_stop_all_calls(&self) -> usize {
+        self.stop_all_calls.load(Ordering::SeqCst)
+    }
+
+    /// Simulates the server reporting that device_id got disconnected
+    pub fn remove_device(&self, device_id: u32) {
+        let sender = self.server_sender.lock().unwrap().clone();
+        sender
+            .expect("connector not connected")
+            .try_send(ButtplugSpecV3ServerMessage::DeviceRemoved(
+                DeviceRemoved::new(device_id),
+            ))
+            .unwrap();
+    }
+
+    /// Simulates the server reporting a newly connected device
+    pub fn add_device(&self, device: DeviceAdded) {
+        let sender = self.server_sender.lock().unwrap().clone();
+        sender
+            .expect("connector not connected")
+            .try_send(ButtplugSpecV3ServerMessage::DeviceAdded(device))
+            .unwrap();
+    }
+
+    /// Simulates a subscribed sensor of device_id reporting a new value
+    pub fn send_sensor_reading(&self, device_id: u32, sensor_type: SensorType, value: i32) {
+        let sender = self.server_sender.lock().unwrap().clone();
+        sender
+            .expect("connector not connected")
+            .try_send(ButtplugSpecV3ServerMessage::SensorReading(SensorReading::new(
+                device_id,
+                0,
+                sensor_type,
+                vec![value],
+            )))
+            .unwrap();
+    }
+
+    /// Simulates the server going away, i.e. Intiface being closed
+    pub fn disconnect_server(&self) {
+        self.server_sender.lock().unwrap().take();
+    }
 }
 
 pub struct FakeDeviceConnector {
     pub devices: Vec<DeviceAdded>,
-    server_outbound_sender: Sender<ButtplugCurrentSpecServerMessage>,
+    pub battery_levels: HashMap<u32, i32>,
+    pub rssi_levels: HashMap<u32, i32>,
+    pub failing_devices: Vec<u32>,
+    pub silent_sensor_devices: Vec<u32>,
     call_registry: FakeConnectorCallRegistry,
 }
 
@@ -207,10 +269,12 @@ pub struct FakeDeviceConnector {
 #[allow(dead_code)]
 impl FakeDeviceConnector {
     pub fn new(devices: Vec<DeviceAdded>) -> (Self, FakeConnectorCallRegistry) {
-        let (server_outbound_sender, _) = channel(256);
         let connector = FakeDeviceConnector {
             devices,
-            server_outbound_sender,
+            battery_levels: HashMap::new(),
+            rssi_levels: HashMap::new(),
+            failing_devices: vec![],
+            silent_sensor_devices: vec![],
             call_registry: FakeConnectorCallRegistry::default(),
         };
         let calls = connector.get_call_registry();
@@ -233,8 +297,51 @@ impl FakeDeviceConnector {
         self.call_registry.clone()
     }
 
+    /// Another connector with the same devices that records into the same registry,
+    /// i.e. to connect again after a disconnect
+    pub fn reconnector(&self) -> Self {
+        FakeDeviceConnector {
+            devices: self.devices.clone(),
+            battery_levels: self.battery_levels.clone(),
+            rssi_levels: self.rssi_levels.clone(),
+            failing_devices: self.failing_devices.clone(),
+            silent_sensor_devices: self.silent_sensor_devices.clone(),
+            call_registry: self.call_registry.clone(),
+        }
+    }
+
+    /// Battery level (0-100) reported when device_id is read
+    pub fn with_battery_level(mut self, device_id: u32, level: i32) -> Self {
+        self.battery_levels.insert(device_id, level);
+        self
+    }
+
+    /// RSSI level (dBm) reported when device_id is read
+    pub fn with_rssi_level(mut self, device_id: u32, level: i32) -> Self {
+        self.rssi_levels.insert(device_id, level);
+        self
+    }
+
+    /// Rejects all scalar, linear and rotate commands to device_id with a device error
+    pub fn with_failing_device(mut self, device_id: u32) -> Self {
+        self.failing_devices.push(device_id);
+        self
+    }
+
+    /// Never answers sensor reads of device_id, like a device that went out of range
+    pub fn with_silent_sensors(mut self, device_id: u32) -> Self {
+        self.silent_sensor_devices.push(device_id);
+        self
+    }
+
+    fn server_outbound_sender(&self) -> Sender<ButtplugCurrentSpecServerMessage> {
+        // a closed channel after the server has been disconnected
+        let sender = self.call_registry.server_sender.lock().unwrap().clone();
+        sender.unwrap_or_else(|| channel(1).0)
+    }
+
     fn ok_response(&self, msg_id: u32) -> buttplug::core::connector::ButtplugConnectorResultFuture {
-        let sender = self.server_outbound_sender.clone();
+        let sender = self.server_outbound_sender();
         async move {
             let mut response = ButtplugSpecV3ServerMessage::Ok(message::Ok::default());
             response.set_id(msg_id);
@@ -245,6 +352,23 @@ impl FakeDeviceConnector {
         }
         .boxed()
     }
+
+    fn error_response(&self, msg_id: u32) -> buttplug::core::connector::ButtplugConnectorResultFuture {
+        let sender = self.server_outbound_sender();
+        async move {
+            let mut response = ButtplugSpecV3ServerMessage::Error(message::Error::new(
+                ErrorCode::ErrorDevice,
+                "fake device failure",
+                None,
+            ));
+            response.set_id(msg_id);
+            sender
+                .send(response)
+                .await
+                .map_err(|_| ButtplugConnectorError::ConnectorNotConnected)
+        }
+        .boxed()
+    }
 }
 
 impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage>
@@ -254,7 +378,7 @@ impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServ
         &mut self,
         message_sender: tokio::sync::mpsc::Sender<ButtplugCurrentSpecServerMessage>,
     ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
-        self.server_outbound_sender = message_sender;
+        *self.call_registry.server_sender.lock().unwrap() = Some(message_sender);
         async move {
             async_manager::spawn(async move {
                 // assure that other thread has registered listener when the test devices
@@ -276,7 +400,7 @@ impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServ
     ) -> buttplug::core::connector::ButtplugConnectorResultFuture {
         let msg_id = msg.id();
         let msg_clone = msg.clone();
-        let sender = self.server_outbound_sender.clone();
+        let sender = self.server_outbound_sender();
 
         let devices_added = self.devices.clone();
         match msg {
@@ -313,6 +437,21 @@ impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServ
                     .map_err(|_| ButtplugConnectorError::ConnectorNotConnected)
             }
             .boxed(),
+            ButtplugCurrentSpecClientMessage::ScalarCmd(cmd)
+                if self.failing_devices.contains(&cmd.device_index()) =>
+            {
+                self.error_response(msg_id)
+            }
+            ButtplugCurrentSpecClientMessage::LinearCmd(cmd)
+                if self.failing_devices.contains(&cmd.device_index()) =>
+            {
+                self.error_response(msg_id)
+            }
+            ButtplugCurrentSpecClientMessage::RotateCmd(cmd)
+                if self.failing_devices.contains(&cmd.device_index()) =>
+            {
+                self.error_response(msg_id)
+            }
             ButtplugCurrentSpecClientMessage::ScalarCmd(cmd) => {
                 self.call_registry
                     .store_record(&cmd, FakeMessage::new(msg_clone));
@@ -328,20 +467,54 @@ impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServ
                     .store_record(&cmd, FakeMessage::new(msg_clone));
                 self.ok_response(msg_id)
             }
-            ButtplugCurrentSpecClientMessage::StopAllDevices(_) => {
-                // cannot store cause no id
-                self.ok_response(msg_id)
+            ButtplugCurrentSpecClientMessage::SensorReadCmd(cmd)
+                if self.silent_sensor_devices.contains(&cmd.device_index()) =>
+            {
+                async move { ButtplugConnectorResult::Ok(()) }.boxed()
             }
-            ButtplugCurrentSpecClientMessage::StartScanning(cmd) => {
+            ButtplugCurrentSpecClientMessage::SensorReadCmd(cmd) => {
+                let levels = match cmd.sensor_type() {
+                    SensorType::RSSI => &self.rssi_levels,
+                    _ => &self.battery_levels,
+                };
+                let level = *levels.get(&cmd.device_index()).unwrap_or(&0);
+                async move {
+                    let mut response = ButtplugSpecV3ServerMessage::SensorReading(
+                        SensorReading::new(
+                            cmd.device_index(),
+                            *cmd.sensor_index(),
+                            *cmd.sensor_type(),
+                            vec![level],
+                        ),
+                    );
+                    response.set_id(msg_id);
+                    sender
+                        .send(response)
+                        .await
+                        .map_err(|_| ButtplugConnectorError::ConnectorNotConnected)
+                }
+                .boxed()
+            }
+            ButtplugCurrentSpecClientMessage::SensorSubscribeCmd(cmd) => {
                 self.call_registry
                     .store_record(&cmd, FakeMessage::new(msg_clone));
                 self.ok_response(msg_id)
             }
-            ButtplugCurrentSpecClientMessage::StopScanning(cmd) => {
+            ButtplugCurrentSpecClientMessage::SensorUnsubscribeCmd(cmd) => {
                 self.call_registry
                     .store_record(&cmd, FakeMessage::new(msg_clone));
                 self.ok_response(msg_id)
             }
+            ButtplugCurrentSpecClientMessage::StopAllDevices(_) => {
+                // cannot store cause no id
+                self.call_registry.stop_all_calls.fetch_add(1, Ordering::SeqCst);
+                self.ok_response(msg_id)
+            }
+            ButtplugCurrentSpecClientMessage::StartScanning(_)
+            | ButtplugCurrentSpecClientMessage::StopScanning(_) => {
+                // cannot store cause no id
+                self.ok_response(msg_id)
+            }
             _ => {
                 error!("Unimplemented message type.");
                 async move { ButtplugConnectorResult::Ok(()) }.boxed()
@@ -439,6 +612,65 @@ pub fn rotate(id: u32, name: &str) -> DeviceAdded {
     )
 }
 
+/// Vibrator with a single sensor, 'subscribable' sensors are streamed
+/// instead of being read
+#[allow(dead_code)]
+pub fn sensor_vibrator(
+    id: u32,
+    name: &str,
+    sensor_type: SensorType,
+    range: RangeInclusive<i32>,
+    subscribable: bool,
+) -> DeviceAdded {
+    let sensor: SensorDeviceMessageAttributes = serde_json::from_value(serde_json::json!({
+        "FeatureDescriptor": format!("{:?}", sensor_type),
+        "SensorType": sensor_type,
+        "SensorRange": [[range.start(), range.end()]]
+    }))
+    .unwrap();
+    let mut builder = ServerDeviceMessageAttributesBuilder::default();
+    builder.scalar_cmd(&[ServerGenericDeviceMessageAttributes::new(
+        &format!("Vibrator {}", id),
+        &RangeInclusive::new(0, 10),
+        ActuatorType::Vibrate,
+    )]);
+    match subscribable {
+        true => builder.sensor_subscribe_cmd(&[sensor]),
+        false => builder.sensor_read_cmd(&[sensor]),
+    };
+    let attributes = builder.finish();
+    DeviceAdded::new(
+        id,
+        name,
+        &None,
+        &None,
+        &ClientDeviceMessageAttributes::from(attributes),
+    )
+}
+
+#[allow(dead_code)]
+pub fn vibrator_rotator(id: u32, name: &str) -> DeviceAdded {
+    let mut builder = ServerDeviceMessageAttributesBuilder::default();
+    builder.scalar_cmd(&[ServerGenericDeviceMessageAttributes::new(
+        &format!("Vibrator {}", id),
+        &RangeInclusive::new(0, 10),
+        ActuatorType::Vibrate,
+    )]);
+    builder.rotate_cmd(&[ServerGenericDeviceMessageAttributes::new(
+        &format!("Rotator {}", id),
+        &RangeInclusive::new(0, 10),
+        ActuatorType::Rotate,
+    )]);
+    let attributes = builder.finish();
+    DeviceAdded::new(
+        id,
+        name,
+        &None,
+        &None,
+        &ClientDeviceMessageAttributes::from(attributes),
+    )
+}
+
 pub struct ButtplugTestClient {
     pub client: ButtplugClient,
     pub call_registry: FakeConnectorCallRegistry,
@@ -518,7 +750,7 @@ impl ButtplugTestClient {
 #[cfg(test)]
 pub mod tests {
     use buttplug::{
-        client::{LinearCommand, RotateCommand, ScalarCommand},
+        client::{ButtplugClientDeviceEvent, LinearCommand, RotateCommand, ScalarCommand},
         core::message::ActuatorType,
     };
     use tracing::Level;
@@ -616,4 +848,110 @@ pub mod tests {
             .assert_rotation(0.42)
             .assert_direction(false);
     }
+
+    #[tokio::test]
+    async fn remove_device_emits_device_removed() {
+        // arrange
+        let client = get_test_client(vec![vibrator(1, "vibrator")]).await;
+        let mut events = client.client.event_stream();
+
+        // act
+        client.call_registry.remove_device(1);
+
+        // assert
+        match events.next().await.unwrap() {
+            buttplug::client::ButtplugClientEvent::DeviceRemoved(device) => {
+                assert_eq!(device.index(), 1)
+            }
+            _ => panic!(),
+        };
+    }
+
+    #[tokio::test]
+    async fn disconnect_server_emits_server_disconnect() {
+        // arrange
+        let client = get_test_client(vec![vibrator(1, "vibrator")]).await;
+        let mut events = client.client.event_stream();
+
+        // act
+        client.call_registry.disconnect_server();
+
+        // assert
+        loop {
+            match events.next().await.unwrap() {
+                buttplug::client::ButtplugClientEvent::ServerDisconnect => break,
+                buttplug::client::ButtplugClientEvent::DeviceRemoved(_) => {}
+                _ => panic!(),
+            }
+        }
+        assert!(!client.client.connected());
+    }
+
+    #[tokio::test]
+    async fn sensor_read_returns_battery_level() {
+        // arrange
+        let (connector, _) = FakeDeviceConnector::new(vec![sensor_vibrator(1, "vibrator", SensorType::Battery, 0..=100, false)]);
+        let connector = connector.with_battery_level(1, 42);
+        let client = ButtplugClient::new("FakeClient");
+        client.connect(connector).await.unwrap();
+        let device = match client.event_stream().next().await.unwrap() {
+            buttplug::client::ButtplugClientEvent::DeviceAdded(device) => device,
+            _ => panic!(),
+        };
+
+        // act
+        let level = device.battery_level().await.unwrap();
+
+        // assert
+        assert!(device.has_battery_level());
+        assert_eq!(level, 0.42);
+    }
+
+    #[tokio::test]
+    async fn subscribed_sensor_reports_readings() {
+        // arrange
+        let (connector, call_registry) = FakeDeviceConnector::new(vec![sensor_vibrator(1, "vibrator", SensorType::Pressure, 0..=1000, true)]);
+        let client = ButtplugClient::new("FakeClient");
+        client.connect(connector).await.unwrap();
+        let device = match client.event_stream().next().await.unwrap() {
+            buttplug::client::ButtplugClientEvent::DeviceAdded(device) => device,
+            _ => panic!(),
+        };
+        let mut device_events = device.event_stream();
+
+        // act
+        device.subscribe_sensor(0, SensorType::Pressure).await.unwrap();
+        call_registry.send_sensor_reading(1, SensorType::Pressure, 300);
+
+        // assert
+        match device_events.next().await.unwrap() {
+            ButtplugClientDeviceEvent::Message(ButtplugCurrentSpecServerMessage::SensorReading(reading)) => {
+                assert_eq!(reading.data(), &vec![300])
+            }
+            _ => panic!(),
+        }
+        device.unsubscribe_sensor(0, SensorType::Pressure).await.unwrap();
+        assert_eq!(call_registry.get_device(1).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sensor_read_returns_rssi_level() {
+        // arrange
+        let (connector, _) = FakeDeviceConnector::new(vec![sensor_vibrator(1, "vibrator", SensorType::RSSI, 0..=128, false)]);
+        let connector = connector.with_rssi_level(1, -60);
+        let client = ButtplugClient::new("FakeClient");
+        client.connect(connector).await.unwrap();
+        let device = match client.event_stream().next().await.unwrap() {
+            buttplug::client::ButtplugClientEvent::DeviceAdded(device) => device,
+            _ => panic!(),
+        };
+
+        // act
+        let level = device.rssi_level().await.unwrap();
+
+        // assert
+        assert!(device.has_rssi_level());
+        assert!(!device.has_battery_level());
+        assert_eq!(level, -60);
+    }
 }
@@ -1,10 +1,16 @@
-use buttplug::client::{ButtplugClientError, ScalarCommand};
+use buttplug::client::{ButtplugClientError, RotateCommand, ScalarCommand};
+use buttplug::core::errors::{ButtplugDeviceError, ButtplugError};
+use buttplug::core::message::ButtplugDeviceMessageType;
 use std::collections::HashMap;
 
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, trace, instrument};
 
-use crate::{actuator::Actuator, speed::Speed};
+use crate::{actuator::Actuator, speed::Speed, worker::ActuatorError};
 
 /// Stores information about concurrent accesses to a buttplug actuator
 /// to calculate the actual vibration speed or linear movement
@@ -14,20 +20,66 @@ pub struct DeviceEntry {
     /// Priority calculation work like a stack with the top of the stack
     /// task being the used vibration speed
     pub linear_tasks: Vec<(i32, Speed)>,
+    /// The speed that was sent last
+    pub current_speed: Speed,
+    pub actuator: Arc<Actuator>,
 }
 
 pub struct DeviceAccess {
     device_actions: HashMap<String, DeviceEntry>,
+    /// Takeovers by a new task fade from the current speed over this duration
+    crossfade: Duration,
+    fades: HashMap<String, CancellationToken>,
+    /// Speed changes that are sent on the next flush
+    pending: Vec<(Arc<Actuator>, Speed)>,
+    /// Minimum time between two commands to the same device
+    command_interval: Duration,
+    last_sent: HashMap<u32, Instant>,
+    /// Stopped actuators fade to zero over this duration
+    fade_out: Duration,
+    /// Rotation direction of RotateCmd actuators, clockwise if not set
+    clockwise: HashMap<String, bool>,
+    /// Speed changes only update the current speed and are sent on resume
+    paused: bool,
+    /// Receives failures of commands that are sent outside of 'flush', i.e. fade steps
+    error_sender: Option<UnboundedSender<ActuatorError>>,
 }
 
+/// Time between two speed changes of a crossfade
+const CROSSFADE_STEP: Duration = Duration::from_millis(25);
+
 impl DeviceAccess {
-    pub fn default() -> Self {
+    pub fn with_crossfade(crossfade: Duration) -> Self {
         DeviceAccess {
             device_actions: HashMap::new(),
+            crossfade,
+            fades: HashMap::new(),
+            pending: vec![],
+            command_interval: Duration::ZERO,
+            last_sent: HashMap::new(),
+            fade_out: Duration::ZERO,
+            clockwise: HashMap::new(),
+            paused: false,
+            error_sender: None,
         }
     }
 
-    pub async fn start_scalar(
+    pub fn with_error_sender(mut self, error_sender: Option<UnboundedSender<ActuatorError>>) -> Self {
+        self.error_sender = error_sender;
+        self
+    }
+
+    pub fn with_fade_out(mut self, fade_out: Duration) -> Self {
+        self.fade_out = fade_out;
+        self
+    }
+
+    pub fn with_command_interval(mut self, command_interval: Duration) -> Self {
+        self.command_interval = command_interval;
+        self
+    }
+
+    pub fn start_scalar(
         &mut self,
         actuator: &Arc<Actuator>,
         speed: Speed,
@@ -35,8 +87,13 @@ impl DeviceAccess {
         handle: i32,
     ) {
         trace!("start scalar {:?} {} {}", speed, actuator, handle);
+        let previous_speed = self
+            .device_actions
+            .get(actuator.key())
+            .filter(|entry| entry.task_count > 0)
+            .map(|entry| entry.current_speed);
         self.device_actions
-            .entry(actuator.identifier().into())
+            .entry(actuator.key().into())
             .and_modify(|entry| {
                 entry.task_count += 1;
                 if ! is_pattern {
@@ -50,41 +107,48 @@ impl DeviceAccess {
                 } else {
                     vec![(handle, speed)]
                 },
+                current_speed: Speed::min(),
+                actuator: actuator.clone(),
             });
-        let _ = self.set_scalar(actuator, speed).await;
+        match previous_speed {
+            Some(from) if !self.crossfade.is_zero() && from.value != speed.value => {
+                self.fade_scalar(actuator, from, speed, self.crossfade)
+            }
+            _ => self.set_scalar(actuator, speed),
+        }
     }
 
     #[instrument(skip(self))]
-    pub async fn stop_scalar(
-        &mut self,
-        actuator: &Arc<Actuator>,
-        is_pattern: bool,
-        handle: i32,
-    ) -> Result<(), ButtplugClientError> {
+    pub fn stop_scalar(&mut self, actuator: &Arc<Actuator>, is_pattern: bool, handle: i32) {
         trace!("stop scalar");
-        if let Some(mut entry) = self.device_actions.remove(actuator.identifier()) {
+        if let Some(mut entry) = self.device_actions.remove(actuator.key()) {
             if ! is_pattern {
                 entry.linear_tasks.retain(|t| t.0 != handle);
             }
             let mut count = entry.task_count;
             count = count.saturating_sub(1);
             entry.task_count = count;
-            self.device_actions.insert(actuator.identifier().into(), entry);
+            self.device_actions.insert(actuator.key().into(), entry);
             if count == 0 {
                 // nothing else is controlling the device, stop it
-                return self.set_scalar(actuator, Speed::min()).await;
+                match self.device_actions.get(actuator.key()) {
+                    Some(entry) if !self.fade_out.is_zero() && entry.current_speed.value > 0 => {
+                        self.fade_scalar(actuator, entry.current_speed, Speed::min(), self.fade_out)
+                    }
+                    _ => self.set_scalar(actuator, Speed::min()),
+                }
+                self.clockwise.remove(actuator.key());
             } else if let Some(last_speed) = self.get_priority_speed(actuator) {
-                let _ = self.set_scalar(actuator, last_speed).await;
+                self.set_scalar(actuator, last_speed);
             }
         }
-        Ok(())
     }
 
     #[instrument(skip(self))]
-    pub async fn update_scalar(&mut self, actuator: &Arc<Actuator>, new_speed: Speed, is_pattern: bool, handle: i32) {
+    pub fn update_scalar(&mut self, actuator: &Arc<Actuator>, new_speed: Speed, is_pattern: bool, handle: i32) {
         trace!("update scalar scalar");
         if ! is_pattern {
-            self.device_actions.entry(actuator.identifier().into()).and_modify(|entry| {
+            self.device_actions.entry(actuator.key().into()).and_modify(|entry| {
                 entry.linear_tasks = entry.linear_tasks.iter().map(|t| {
                     if t.0 == handle {
                         return (handle, new_speed);
@@ -95,29 +159,175 @@ impl DeviceAccess {
         }
         let speed = self.get_priority_speed(actuator).unwrap_or(new_speed);
         debug!("updating {} speed to {}", actuator, speed);
-        let _ = self.set_scalar(actuator, speed).await;
+        self.set_scalar(actuator, speed)
     }
 
-    #[instrument(skip(self))]
-    async fn set_scalar(
-        &self,
-        actuator: &Arc<Actuator>,
-        speed: Speed,
-    ) -> Result<(), ButtplugClientError> {
-        let cmd = ScalarCommand::ScalarMap(HashMap::from([(
-            actuator.index_in_device,
-            (speed.as_float(), actuator.actuator),
-        )]));
+    /// Sets the rotation direction until no task uses the actuator anymore,
+    /// a running actuator is sent its current speed again with the new direction
+    pub fn set_direction(&mut self, actuator: &Arc<Actuator>, clockwise: bool) {
+        if self.is_clockwise(actuator) == clockwise {
+            return;
+        }
+        debug!(clockwise, "set direction {}", actuator);
+        self.clockwise.insert(actuator.key().into(), clockwise);
+        let running = self
+            .device_actions
+            .get(actuator.key())
+            .filter(|entry| entry.task_count > 0)
+            .map(|entry| entry.current_speed);
+        if let Some(speed) = running {
+            self.set_scalar(actuator, speed);
+        }
+    }
+
+    /// Stops all running actuators but keeps their current speed
+    pub fn pause(&mut self) {
+        debug!("pause");
+        self.paused = true;
+        self.pending.clear();
+        for entry in self.device_actions.values() {
+            // actuators that are fading out are stopped as well
+            if entry.task_count > 0 || self.fades.contains_key(entry.actuator.key()) {
+                self.pending.push((entry.actuator.clone(), Speed::min()));
+            }
+        }
+        for (_, fade) in self.fades.drain() {
+            fade.cancel();
+        }
+    }
+
+    /// Sends the current speed of all running actuators again
+    pub fn resume(&mut self) {
+        debug!("resume");
+        self.paused = false;
+        self.pending.clear();
+        for entry in self.device_actions.values().filter(|x| x.task_count > 0) {
+            self.pending.push((entry.actuator.clone(), entry.current_speed));
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn is_clockwise(&self, actuator: &Arc<Actuator>) -> bool {
+        self.clockwise.get(actuator.key()).copied().unwrap_or(true)
+    }
+
+    /// Changes the speed in steps over 'duration', any other speed
+    /// change on the actuator cancels the fade
+    fn fade_scalar(&mut self, actuator: &Arc<Actuator>, from: Speed, to: Speed, duration: Duration) {
+        debug!(%from, %to, "fade {}", actuator);
+        if self.paused {
+            self.set_scalar(actuator, to);
+            return;
+        }
+        let cancel = CancellationToken::new();
+        if let Some(previous) = self.fades.insert(actuator.key().into(), cancel.clone()) {
+            previous.cancel();
+        }
+        self.pending.retain(|(pending, _)| pending.key() != actuator.key());
+        self.set_current_speed(actuator, to);
+        let steps = (duration.as_millis() / CROSSFADE_STEP.as_millis()).max(1) as usize;
+        let clockwise = self.is_clockwise(actuator);
+        let actuator = actuator.clone();
+        let error_sender = self.error_sender.clone();
+        Handle::current().spawn(async move {
+            for speed in Speed::ramp(from, to, steps + 1).skip(1) {
+                if cancel.is_cancelled() {
+                    return;
+                }
+                if let Err(err) = send_scalar(&[(actuator.clone(), speed, clockwise)]).await {
+                    // the remaining steps would fail as well
+                    if let Some(error_sender) = error_sender {
+                        let _ = error_sender.send((actuator, err.to_string()));
+                    }
+                    return;
+                }
+                if !crate::cancellable_wait(CROSSFADE_STEP, &cancel).await {
+                    return;
+                }
+            }
+        });
+    }
 
-        if let Err(err) = actuator.device.scalar(&cmd).await {
-            error!("failed to set scalar speed {:?}", err);
-            return Err(err);
+    fn set_scalar(&mut self, actuator: &Arc<Actuator>, speed: Speed) {
+        if let Some(fade) = self.fades.remove(actuator.key()) {
+            fade.cancel();
+        }
+        self.set_current_speed(actuator, speed);
+        if !self.paused {
+            self.pending.push((actuator.clone(), speed));
+        }
+    }
+
+    /// Sends all pending speed changes, actuators of the same device are
+    /// batched into a single command. Returns the result of each actuator
+    ///
+    /// Devices that received a command within the command interval are
+    /// skipped and keep only their latest speed for the next flush
+    pub async fn flush(&mut self) -> Vec<(Arc<Actuator>, Result<(), ButtplugClientError>)> {
+        let mut ready = std::mem::take(&mut self.pending);
+        if !self.command_interval.is_zero() {
+            let mut latest: Vec<(Arc<Actuator>, Speed)> = vec![];
+            for (actuator, speed) in ready {
+                latest.retain(|(x, _)| x.key() != actuator.key());
+                latest.push((actuator, speed));
+            }
+            let now = Instant::now();
+            (ready, self.pending) = latest.into_iter().partition(|(actuator, _)| {
+                self.last_sent
+                    .get(&actuator.device.index())
+                    .is_none_or(|sent| now.duration_since(*sent) >= self.command_interval)
+            });
+        }
+        let mut batches: Vec<Vec<(Arc<Actuator>, Speed, bool)>> = vec![];
+        for (actuator, speed) in ready {
+            let clockwise = self.is_clockwise(&actuator);
+            let batch = batches.iter_mut().rfind(|batch| {
+                batch[0].0.device.index() == actuator.device.index()
+                    && batch[0].0.message_type == actuator.message_type
+            });
+            match batch {
+                // the same actuator twice needs a second command to keep every speed change
+                Some(batch) if !batch.iter().any(|(x, _, _)| x.key() == actuator.key()) => {
+                    batch.push((actuator, speed, clockwise))
+                }
+                _ => batches.push(vec![(actuator, speed, clockwise)]),
+            }
+        }
+        let mut results = vec![];
+        for batch in batches {
+            let result = send_scalar(&batch).await;
+            self.last_sent.insert(batch[0].0.device.index(), Instant::now());
+            for (actuator, _, _) in batch {
+                let result = match &result {
+                    Ok(()) => Ok(()),
+                    Err(err) => Err(copy_error(err)),
+                };
+                results.push((actuator, result));
+            }
+        }
+        results
+    }
+
+    /// When the speed changes held back by the command interval are due
+    pub fn next_flush(&self) -> Option<Instant> {
+        self.pending
+            .iter()
+            .filter_map(|(actuator, _)| self.last_sent.get(&actuator.device.index()))
+            .min()
+            .map(|sent| *sent + self.command_interval)
+    }
+
+    fn set_current_speed(&mut self, actuator: &Arc<Actuator>, speed: Speed) {
+        if let Some(entry) = self.device_actions.get_mut(actuator.key()) {
+            entry.current_speed = speed;
         }
-        Ok(())
     }
 
     fn get_priority_speed(&self, actuator: &Arc<Actuator>) -> Option<Speed> {
-        if let Some(entry) = self.device_actions.get(actuator.identifier()) {
+        if let Some(entry) = self.device_actions.get(actuator.key()) {
             let mut sorted: Vec<(i32, Speed)> = entry.linear_tasks.clone();
             sorted.sort_by_key(|b| b.0);
             if let Some(tuple) = sorted.last() {
@@ -127,7 +337,69 @@ impl DeviceAccess {
         None
     }
 
+    /// Forgets all device accesses and ends a pause, running actuators
+    /// are faded out if a fade out is configured
     pub fn clear_all(&mut self) {
-        self.device_actions.clear();
+        // paused actuators are already stopped
+        let was_paused = std::mem::take(&mut self.paused);
+        self.pending.clear();
+        self.clockwise.clear();
+        for (_, fade) in self.fades.drain() {
+            fade.cancel();
+        }
+        let entries: Vec<DeviceEntry> = self.device_actions.drain().map(|(_, entry)| entry).collect();
+        if !self.fade_out.is_zero() && !was_paused {
+            for entry in entries.into_iter().filter(|x| x.current_speed.value > 0) {
+                self.fade_scalar(&entry.actuator, entry.current_speed, Speed::min(), self.fade_out);
+            }
+        }
+    }
+}
+
+/// Sends the speeds of actuators that share a device and message type
+/// in a single command, the direction is only used by RotateCmd
+#[instrument]
+async fn send_scalar(
+    actuators: &[(Arc<Actuator>, Speed, bool)],
+) -> Result<(), ButtplugClientError> {
+    let device = &actuators[0].0.device;
+    let result = match actuators[0].0.message_type {
+        ButtplugDeviceMessageType::RotateCmd => {
+            let cmd = RotateCommand::RotateMap(
+                actuators
+                    .iter()
+                    .map(|(actuator, speed, clockwise)| {
+                        (actuator.index_in_device, (speed.as_float(), *clockwise))
+                    })
+                    .collect(),
+            );
+            device.rotate(&cmd).await
+        }
+        _ => {
+            let cmd = ScalarCommand::ScalarMap(
+                actuators
+                    .iter()
+                    .map(|(actuator, speed, _)| {
+                        (actuator.index_in_device, (speed.as_float(), actuator.actuator))
+                    })
+                    .collect(),
+            );
+            device.scalar(&cmd).await
+        }
+    };
+    if let Err(err) = result {
+        error!("failed to set scalar speed {:?}", err);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// A batched command has only one result, the error is reported to every actuator
+fn copy_error(err: &ButtplugClientError) -> ButtplugClientError {
+    match err {
+        ButtplugClientError::ButtplugError(err) => ButtplugClientError::ButtplugError(err.clone()),
+        ButtplugClientError::ButtplugConnectorError(err) => ButtplugClientError::ButtplugError(
+            ButtplugError::from(ButtplugDeviceError::DeviceCommunicationError(err.to_string())),
+        ),
     }
 }
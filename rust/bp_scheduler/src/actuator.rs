@@ -1,5 +1,5 @@
 use buttplug::client::ButtplugClientDevice;
-use buttplug::core::message::ActuatorType;
+use buttplug::core::message::{ActuatorType, ButtplugDeviceMessageType};
 use std::{
     fmt::{self, Display},
     sync::Arc,
@@ -10,7 +10,10 @@ pub struct Actuator {
     pub device: Arc<ButtplugClientDevice>,
     pub actuator: ActuatorType,
     pub index_in_device: u32,
+    /// Buttplug message used to control this actuator (ScalarCmd, LinearCmd or RotateCmd)
+    pub message_type: ButtplugDeviceMessageType,
     identifier: String,
+    key: String,
 }
 
 impl Actuator {
@@ -18,20 +21,68 @@ impl Actuator {
         device: &Arc<ButtplugClientDevice>,
         actuator: ActuatorType,
         index_in_device: usize,
+        message_type: ButtplugDeviceMessageType,
     ) -> Self {
         let identifier = Actuator::get_identifier(device, actuator, index_in_device);
+        let key = format!("{}:{}", device.index(), identifier);
         Actuator {
             device: device.clone(),
             actuator,
             index_in_device: index_in_device as u32,
+            message_type,
             identifier,
+            key,
         }
     }
 
+    /// All scalar, linear and rotate actuators of a single device
+    pub fn from_device(device: &Arc<ButtplugClientDevice>) -> Vec<Arc<Actuator>> {
+        let mut actuators = vec![];
+        if let Some(scalar_cmd) = device.message_attributes().scalar_cmd() {
+            for (idx, scalar_cmd) in scalar_cmd.iter().enumerate() {
+                actuators.push(Actuator::new(
+                    device,
+                    *scalar_cmd.actuator_type(),
+                    idx,
+                    ButtplugDeviceMessageType::ScalarCmd,
+                ))
+            }
+        }
+        if let Some(linear_cmd) = device.message_attributes().linear_cmd() {
+            for (idx, _) in linear_cmd.iter().enumerate() {
+                actuators.push(Actuator::new(
+                    device,
+                    ActuatorType::Position,
+                    idx,
+                    ButtplugDeviceMessageType::LinearCmd,
+                ));
+            }
+        }
+        if let Some(rotate_cmd) = device.message_attributes().rotate_cmd() {
+            for (idx, _) in rotate_cmd.iter().enumerate() {
+                actuators.push(Actuator::new(
+                    device,
+                    ActuatorType::Rotate,
+                    idx,
+                    ButtplugDeviceMessageType::RotateCmd,
+                ))
+            }
+        }
+        actuators.into_iter().map(Arc::new).collect()
+    }
+
+    /// Name of the actuator used for settings, i.e. 'Lovense Hush (Vibrate)'. Devices
+    /// with the same name have the same identifiers and share their settings
     pub fn identifier(&self) -> &str {
         &self.identifier
     }
 
+    /// Unique among all connected actuators, unlike 'identifier' it includes the
+    /// buttplug device index so devices with the same name are controlled separately
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
     fn get_identifier(
         device: &Arc<ButtplugClientDevice>,
         actuator: ActuatorType,
@@ -45,25 +96,7 @@ impl Actuator {
 }
 
 pub fn get_actuators(devices: Vec<Arc<ButtplugClientDevice>>) -> Vec<Arc<Actuator>> {
-    let mut actuators = vec![];
-    for device in devices {
-        if let Some(scalar_cmd) = device.message_attributes().scalar_cmd() {
-            for (idx, scalar_cmd) in scalar_cmd.iter().enumerate() {
-                actuators.push(Actuator::new(&device, *scalar_cmd.actuator_type(), idx))
-            }
-        }
-        if let Some(linear_cmd) = device.message_attributes().linear_cmd() {
-            for (idx, _) in linear_cmd.iter().enumerate() {
-                actuators.push(Actuator::new(&device, ActuatorType::Position, idx));
-            }
-        }
-        if let Some(rotate_cmd) = device.message_attributes().rotate_cmd() {
-            for (idx, _) in rotate_cmd.iter().enumerate() {
-                actuators.push(Actuator::new(&device, ActuatorType::Rotate, idx))
-            }
-        }
-    }
-    actuators.into_iter().map(Arc::new).collect()
+    devices.iter().flat_map(Actuator::from_device).collect()
 }
 
 impl Display for Actuator {
@@ -1,14 +1,23 @@
 use actuator::Actuator;
-use buttplug::client::ButtplugClientError;
+use buttplug::{
+    client::ButtplugClientError,
+    core::message::{ActuatorType, ButtplugDeviceMessageType},
+};
 use player::PatternPlayer;
 use settings::ActuatorSettings;
 use speed::Speed;
 use std::collections::HashMap;
 use worker::{ButtplugWorker, WorkerTask};
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::{
-    sync::mpsc::{unbounded_channel, UnboundedSender},
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        watch,
+    },
     time::sleep,
 };
 use tokio_util::sync::CancellationToken;
@@ -27,36 +36,112 @@ pub struct ButtplugScheduler {
     settings: PlayerSettings,
     control_handles: HashMap<i32, ControlHandle>,
     last_handle: i32,
+    /// Number of tasks created so far, orders the tasks since handles wrap around
+    tasks_created: watch::Sender<u64>,
+    /// Paused tasks keep running but the devices are stopped and their durations don't run down
+    paused: Arc<watch::Sender<bool>>,
+    /// Parent of all task tokens, replaced after each stop all
+    tasks: Arc<Mutex<CancellationToken>>,
+}
+
+/// Stops all tasks of the scheduler it was created from, can be used outside of
+/// the thread that owns the scheduler
+#[derive(Clone, Debug)]
+pub struct StopAllHandle {
+    worker_task_sender: UnboundedSender<WorkerTask>,
+    tasks: Arc<Mutex<CancellationToken>>,
+    paused: Arc<watch::Sender<bool>>,
+}
+
+impl StopAllHandle {
+    pub fn stop_all(&self) {
+        self.worker_task_sender
+            .send(WorkerTask::StopAll)
+            .unwrap_or_else(|_| error!("Event sender full"));
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.cancel();
+        *tasks = CancellationToken::new();
+        self.paused.send_replace(false);
+    }
 }
 
 #[derive(Debug)]
 struct ControlHandle {
     cancellation_token: CancellationToken,
-    update_sender: UnboundedSender<Speed>,
+    update_senders: Vec<UnboundedSender<Speed>>,
+    actuators: Vec<Arc<Actuator>>,
+    created: u64,
 }
 
 #[derive(Debug)]
 pub struct PlayerSettings {
     pub scalar_resolution_ms: i32,
+    /// Speed updates of a constant movement arriving within this window are
+    /// merged and only the latest one is sent, 0 sends every update
+    pub update_coalesce_ms: i32,
+    /// A new task taking over a running actuator fades to its speed over this time
+    pub crossfade_ms: u64,
+    /// Minimum time between two commands to the same device, speed changes in
+    /// between are held back and only the latest one is sent. 0 disables the limit
+    pub command_rate_ms: u64,
+    /// Stopped actuators ramp down to zero over this time, 0 stops instantly
+    pub fade_out_ms: u64,
+    /// Pattern speed changes smaller than this (in percent) are not sent, the
+    /// last point of the pattern is always sent. 0 sends every change
+    pub pattern_min_delta: u16,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        PlayerSettings {
+            scalar_resolution_ms: 100,
+            update_coalesce_ms: 0,
+            crossfade_ms: 0,
+            command_rate_ms: 0,
+            fade_out_ms: 0,
+            pattern_min_delta: 0,
+        }
+    }
 }
 
 impl ButtplugScheduler {
     pub fn create(settings: PlayerSettings) -> (ButtplugScheduler, ButtplugWorker) {
         let (worker_task_sender, task_receiver) = unbounded_channel::<WorkerTask>();
+        let crossfade = Duration::from_millis(settings.crossfade_ms);
+        let command_interval = Duration::from_millis(settings.command_rate_ms);
+        let fade_out = Duration::from_millis(settings.fade_out_ms);
         (
             ButtplugScheduler {
                 worker_task_sender,
                 settings,
                 control_handles: HashMap::new(),
                 last_handle: 0,
+                tasks_created: watch::Sender::new(0),
+                paused: Arc::new(watch::Sender::new(false)),
+                tasks: Arc::new(Mutex::new(CancellationToken::new())),
+            },
+            ButtplugWorker {
+                task_receiver,
+                error_sender: None,
+                crossfade,
+                command_interval,
+                fade_out,
             },
-            ButtplugWorker { task_receiver },
         )
     }
 
+    /// Always positive, wraps around to 1 and skips handles that are still in use
     fn get_next_handle(&mut self) -> i32 {
-        self.last_handle += 1;
-        self.last_handle
+        loop {
+            self.last_handle = if self.last_handle == i32::MAX {
+                1
+            } else {
+                self.last_handle + 1
+            };
+            if !self.control_handles.contains_key(&self.last_handle) {
+                return self.last_handle;
+            }
+        }
     }
 
     /// Clean up finished tasks
@@ -65,6 +150,27 @@ impl ButtplugScheduler {
             .retain(|_, handle| !handle.cancellation_token.is_cancelled());
     }
 
+    pub fn task_count(&self) -> usize {
+        self.control_handles.len()
+    }
+
+    /// Handle of the task that was started first
+    pub fn oldest_task(&self) -> Option<i32> {
+        self.control_handles
+            .iter()
+            .min_by_key(|(_, control)| control.created)
+            .map(|(handle, _)| *handle)
+    }
+
+    /// Changes whenever a task is created
+    pub fn subscribe_tasks_created(&self) -> watch::Receiver<u64> {
+        self.tasks_created.subscribe()
+    }
+
+    pub fn has_task(&self, handle: i32) -> bool {
+        self.control_handles.contains_key(&handle)
+    }
+
     pub fn stop_task(&mut self, handle: i32) {
         if self.control_handles.contains_key(&handle) {
             debug!("stop handle {}", handle);
@@ -81,12 +187,15 @@ impl ButtplugScheduler {
     pub fn update_task(&mut self, handle: i32, speed: Speed) -> bool {
         if self.control_handles.contains_key(&handle) {
             debug!("updating handle {}", handle);
-            let _ = self
+            for update_sender in self
                 .control_handles
                 .get(&handle)
                 .unwrap()
-                .update_sender
-                .send(speed);
+                .update_senders
+                .iter()
+            {
+                let _ = update_sender.send(speed);
+            }
             true
         } else {
             error!("Unknown handle {}", handle);
@@ -94,16 +203,79 @@ impl ButtplugScheduler {
         }
     }
 
+    /// Sets the rotation direction of all RotateCmd actuators of the task,
+    /// returns false for unknown handles
+    pub fn set_direction(&mut self, handle: i32, clockwise: bool) -> bool {
+        match self.control_handles.get(&handle) {
+            Some(control) => {
+                for actuator in control
+                    .actuators
+                    .iter()
+                    .filter(|x| x.message_type == ButtplugDeviceMessageType::RotateCmd)
+                {
+                    let _ = self
+                        .worker_task_sender
+                        .send(WorkerTask::Direction(actuator.clone(), clockwise));
+                }
+                true
+            }
+            None => {
+                error!("Unknown handle {}", handle);
+                false
+            }
+        }
+    }
+
+    /// Handles of all tasks that control at least one actuator of 'actuator_type'
+    pub fn tasks_using(&self, actuator_type: ActuatorType) -> Vec<i32> {
+        self.control_handles
+            .iter()
+            .filter(|(_, control)| control.actuators.iter().any(|x| x.actuator == actuator_type))
+            .map(|(handle, _)| *handle)
+            .collect()
+    }
+
+    /// Stops all devices without ending the running tasks, speed changes
+    /// are remembered and sent on 'resume_all'
+    pub fn pause_all(&mut self) -> bool {
+        if self.is_paused() {
+            return false;
+        }
+        debug!("pause all");
+        self.paused.send_replace(true);
+        let _ = self.worker_task_sender.send(WorkerTask::Pause);
+        true
+    }
+
+    /// Restores the speeds of all running tasks, the durations continue
+    /// with the time that was left when they were paused
+    pub fn resume_all(&mut self) -> bool {
+        if !self.is_paused() {
+            return false;
+        }
+        debug!("resume all");
+        let _ = self.worker_task_sender.send(WorkerTask::Resume);
+        self.paused.send_replace(false);
+        true
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
     pub fn stop_all(&mut self) {
-        let queue_full_err = "Event sender full";
-        self.worker_task_sender
-            .send(WorkerTask::StopAll)
-            .unwrap_or_else(|_| error!(queue_full_err));
+        self.stop_all_handle().stop_all();
         for entry in self.control_handles.drain() {
             debug!("stop-all - stopping handle {:?}", entry.0);
-            entry.1.cancellation_token.cancel();
         }
-        self.control_handles.clear();
+    }
+
+    pub fn stop_all_handle(&self) -> StopAllHandle {
+        StopAllHandle {
+            worker_task_sender: self.worker_task_sender.clone(),
+            tasks: self.tasks.clone(),
+            paused: self.paused.clone(),
+        }
     }
 
     pub fn create_player(&mut self, actuators: Vec<Arc<Actuator>>) -> PatternPlayer {
@@ -114,16 +286,57 @@ impl ButtplugScheduler {
     pub fn create_player_with_settings(&mut self, actuators: Vec<Arc<Actuator>>, settings: Vec<ActuatorSettings>) -> PatternPlayer {
         let (update_sender, update_receiver) = unbounded_channel::<Speed>();
 
-        let cancellation_token = CancellationToken::new();
+        let cancellation_token = self.tasks.lock().unwrap().child_token();
         let handle = self.get_next_handle();
+        self.tasks_created.send_modify(|created| *created += 1);
         self.control_handles.insert(
             handle,
             ControlHandle {
                 cancellation_token: cancellation_token.clone(),
-                update_sender,
+                update_senders: vec![update_sender],
+                actuators: actuators.clone(),
+                created: *self.tasks_created.borrow(),
             },
         );
+        self.new_player(
+            actuators,
+            settings,
+            handle,
+            cancellation_token,
+            update_receiver,
+        )
+    }
 
+    /// Creates another player for the existing 'handle', i.e. to play multiple patterns
+    /// at once. All players of a handle are updated and stopped together
+    pub fn create_linked_player(
+        &mut self,
+        handle: i32,
+        actuators: Vec<Arc<Actuator>>,
+        settings: Vec<ActuatorSettings>,
+    ) -> Option<PatternPlayer> {
+        let (update_sender, update_receiver) = unbounded_channel::<Speed>();
+        let control_handle = self.control_handles.get_mut(&handle)?;
+        control_handle.update_senders.push(update_sender);
+        control_handle.actuators.extend(actuators.iter().cloned());
+        let cancellation_token = control_handle.cancellation_token.clone();
+        Some(self.new_player(
+            actuators,
+            settings,
+            handle,
+            cancellation_token,
+            update_receiver,
+        ))
+    }
+
+    fn new_player(
+        &self,
+        actuators: Vec<Arc<Actuator>>,
+        settings: Vec<ActuatorSettings>,
+        handle: i32,
+        cancellation_token: CancellationToken,
+        update_receiver: UnboundedReceiver<Speed>,
+    ) -> PatternPlayer {
         let (result_sender, result_receiver) =
             unbounded_channel::<Result<(), ButtplugClientError>>();
         PatternPlayer {
@@ -135,7 +348,10 @@ impl ButtplugScheduler {
             handle,
             cancellation_token,
             worker_task_sender: self.worker_task_sender.clone(),
+            paused: self.paused.subscribe(),
             scalar_resolution_ms: self.settings.scalar_resolution_ms,
+            update_coalesce_ms: self.settings.update_coalesce_ms,
+            pattern_min_delta: self.settings.pattern_min_delta,
         }
     }
 }
@@ -157,6 +373,7 @@ mod tests {
     use crate::player::PatternPlayer;
     use crate::settings::ActuatorSettings;
     use crate::settings::LinearRange;
+    use crate::settings::ScalarRange;
     use crate::speed::Speed;
     use bp_fakes::get_test_client;
     use bp_fakes::FakeMessage;
@@ -189,6 +406,7 @@ mod tests {
                 all_devices,
                 PlayerSettings {
                     scalar_resolution_ms: 1,
+                    ..Default::default()
                 },
             )
         }
@@ -618,6 +836,38 @@ mod tests {
         assert_eq!(calls.len(), 5)
     }
 
+    #[tokio::test]
+    async fn test_scalar_pattern_loops_infinitely_until_stopped() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup(&client.created_devices);
+
+        let mut fs = FScript::default();
+        fs.actions.push(FSPoint { pos: 100, at: 0 });
+        fs.actions.push(FSPoint { pos: 50, at: 50 });
+
+        // act
+        let pattern_player = player.get_player();
+        let handle = pattern_player.handle;
+        let join = Handle::current().spawn(async move {
+            pattern_player
+                .play_scalar_pattern(Duration::MAX, fs, Speed::max())
+                .await
+                .unwrap();
+        });
+        wait_ms(325).await;
+        player.scheduler.stop_task(handle);
+        join.await.unwrap();
+
+        // assert
+        let calls = client.get_device_calls(1);
+        assert!(calls.len() > 3, "more calls than a single pass");
+        for call in &calls[..calls.len() - 1] {
+            assert!(!call.vibration_stopped(), "no stop between loops");
+        }
+        calls.last().unwrap().assert_strenth(0.0);
+    }
+
     #[tokio::test]
     async fn test_scalar_timing_remains_synced_with_clock() {
         // arrange
@@ -643,9 +893,7 @@ mod tests {
         let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
         let mut player = PlayerTest::setup_with_settings(
             &client.created_devices,
-            PlayerSettings {
-                scalar_resolution_ms: 100,
-            },
+            PlayerSettings::default(),
         );
 
         let mut fs = FScript::default();
@@ -667,6 +915,37 @@ mod tests {
         calls[1].assert_strenth(0.42).assert_time(100, start);
     }
 
+    #[tokio::test]
+    async fn test_scalar_pattern_min_delta_skips_small_changes() {
+        let mut strengths = vec![];
+        for pattern_min_delta in [0, 20] {
+            let mut fs = FScript::default();
+            for i in 0..=20 {
+                fs.actions.push(FSPoint { pos: i * 5, at: i * 5 });
+            }
+            let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+            let mut player = PlayerTest::setup_with_settings(
+                &client.created_devices,
+                PlayerSettings {
+                    scalar_resolution_ms: 1,
+                    pattern_min_delta,
+                    ..Default::default()
+                },
+            );
+            player
+                .play_scalar_pattern(Duration::from_millis(100), fs, Speed::max(), None)
+                .await;
+            let calls: Vec<f64> = client.get_device_calls(1).iter().map(|x| x.get_strength()).collect();
+            strengths.push(calls);
+        }
+
+        let (all, reduced) = (&strengths[0], &strengths[1]);
+        assert!(reduced.len() < all.len() / 2, "{:?} {:?}", all, reduced);
+        assert_eq!(reduced[0], 0.0);
+        assert!(reduced.contains(&1.0));
+        assert_eq!(*reduced.last().unwrap(), 0.0);
+    }
+
     #[tokio::test]
     async fn test_scalar_pattern_control() {
         // arrange
@@ -692,6 +971,90 @@ mod tests {
         calls[2].assert_strenth(0.0);
     }
 
+    #[tokio::test]
+    async fn test_scalar_rapid_updates_are_coalesced() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            &client.created_devices,
+            PlayerSettings {
+                scalar_resolution_ms: 1,
+                update_coalesce_ms: 50,
+                ..Default::default()
+            },
+        );
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(300), Speed::new(100), None);
+        for speed in 1..=10 {
+            wait_ms(5).await;
+            player.scheduler.update_task(1, Speed::new(speed * 5));
+        }
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        assert!(calls.len() < 10);
+        calls[0].assert_strenth(1.0);
+        calls[calls.len() - 2].assert_strenth(0.5);
+        calls[calls.len() - 1].assert_strenth(0.0);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_commands_respect_rate_limit() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            &client.created_devices,
+            PlayerSettings {
+                scalar_resolution_ms: 1,
+                command_rate_ms: 100,
+                ..Default::default()
+            },
+        );
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(500), Speed::new(100), None);
+        for speed in 1..=20 {
+            wait_ms(10).await;
+            player.scheduler.update_task(1, Speed::new(speed * 5));
+        }
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        assert!(calls.len() < 10);
+        for (previous, next) in calls.iter().zip(calls.iter().skip(1)) {
+            assert!(next.time.duration_since(previous.time) >= Duration::from_millis(100));
+        }
+        calls[0].assert_strenth(1.0);
+        calls[calls.len() - 2].assert_strenth(1.0);
+        calls[calls.len() - 1].assert_strenth(0.0);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_motors_of_device_are_batched() {
+        // arrange
+        let client = get_test_client(vec![scalars(1, "vib1", ActuatorType::Vibrate, 3)]).await;
+        let mut player = PlayerTest::setup(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(100), Speed::new(100), None);
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        assert_eq!(calls.len(), 2);
+        calls[0].assert_strengths(vec![(0, 1.0), (1, 1.0), (2, 1.0)]);
+        calls[1].assert_strengths(vec![(0, 0.0), (1, 0.0), (2, 0.0)]);
+    }
+
     #[tokio::test]
     async fn test_scalar_constant_control() {
         // arrange
@@ -722,6 +1085,163 @@ mod tests {
             .assert_time(300, start);
     }
 
+    #[tokio::test]
+    async fn test_pause_and_resume_scalar() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(300), Speed::new(70), None);
+        wait_ms(100).await;
+        assert!(player.scheduler.pause_all());
+        wait_ms(200).await;
+        assert!(player.scheduler.resume_all());
+        assert!(!player.scheduler.resume_all());
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0]
+            .assert_strenth(0.7)
+            .assert_time(0, start);
+        client.get_device_calls(1)[1]
+            .assert_strenth(0.0)
+            .assert_time(100, start);
+        client.get_device_calls(1)[2]
+            .assert_strenth(0.7)
+            .assert_time(300, start);
+        client.get_device_calls(1)[3]
+            .assert_strenth(0.0)
+            .assert_time(500, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_ramped_fades_in_and_out() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            &client.created_devices,
+            PlayerSettings {
+                scalar_resolution_ms: 50,
+                ..Default::default()
+            },
+        );
+
+        // act
+        let start = Instant::now();
+        player
+            .get_player()
+            .play_scalar_ramped(
+                Duration::from_millis(600),
+                Speed::max(),
+                Duration::from_millis(200),
+                Duration::from_millis(200),
+            )
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.25);
+        calls[1].assert_strenth(0.5);
+        calls[2].assert_strenth(0.75);
+        calls[3].assert_strenth(1.0);
+        assert!(calls.len() > 6, "fades out in steps");
+        calls.last().unwrap().assert_strenth(0.0).assert_time(600, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_random_stays_within_band() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            &client.created_devices,
+            PlayerSettings {
+                scalar_resolution_ms: 10,
+                ..Default::default()
+            },
+        );
+
+        // act
+        let start = Instant::now();
+        player
+            .get_player()
+            .play_scalar_random(
+                Duration::from_millis(500),
+                Speed::new(20),
+                Speed::new(60),
+                Duration::from_millis(50),
+                Some(42),
+            )
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        let strengths: Vec<f64> = calls[..calls.len() - 1]
+            .iter()
+            .map(|x| x.get_strength())
+            .collect();
+        assert!(strengths.iter().all(|x| (0.2..=0.6).contains(x)));
+        assert!(strengths.iter().any(|x| *x != strengths[0]));
+        calls.last().unwrap().assert_strenth(0.0);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_factor_per_device() {
+        // arrange
+        let client = get_test_client(vec![
+            scalar(1, "vib1", ActuatorType::Vibrate),
+            scalar(2, "vib2", ActuatorType::Vibrate),
+        ])
+        .await;
+        let mut player = PlayerTest::setup(&client.created_devices);
+        let actuators = vec![
+            get_actuators(vec![client.get_device(1)])[0].clone(),
+            get_actuators(vec![client.get_device(2)])[0].clone(),
+        ];
+        let settings = vec![
+            ActuatorSettings::Scalar(ScalarRange { factor: 0.5, ..Default::default() }),
+            ActuatorSettings::Scalar(ScalarRange { factor: 1.0, ..Default::default() }),
+        ];
+
+        // act
+        player
+            .scheduler
+            .create_player_with_settings(actuators, settings)
+            .play_scalar(Duration::from_millis(50), Speed::max())
+            .await
+            .unwrap();
+
+        // assert
+        client.get_device_calls(1)[0].assert_strenth(0.5);
+        client.get_device_calls(2)[0].assert_strenth(1.0);
+    }
+
+    #[tokio::test]
+    async fn test_stop_all_before_start_does_not_start_devices() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup(&client.created_devices);
+        let actuators = get_actuators(vec![client.get_device(1)]);
+        let queued = player.scheduler.create_player(actuators);
+
+        // act
+        player.scheduler.stop_all();
+        queued
+            .play_scalar(Duration::from_secs(1), Speed::max())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // assert
+        assert!(client.get_device_calls(1).is_empty());
+    }
+
     #[tokio::test]
     async fn test_clean_finished_tasks() {
         // arrange
@@ -743,6 +1263,56 @@ mod tests {
         assert_eq!(player.scheduler.control_handles.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_actuators_from_device() {
+        // arrange
+        let client = get_test_client(vec![scalars(1, "vib", ActuatorType::Vibrate, 2)]).await;
+
+        // act
+        let actuators = Actuator::from_device(&client.get_device(1));
+
+        // assert
+        assert_eq!(actuators.len(), 2);
+        assert_eq!(actuators[0].index_in_device, 0);
+        assert_eq!(actuators[0].identifier(), "vib (Vibrate)");
+        assert_eq!(actuators[1].index_in_device, 1);
+        assert_eq!(actuators[1].identifier(), "vib (Vibrate #1)");
+    }
+
+    #[tokio::test]
+    async fn test_handles_wrap_around_and_skip_active() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup(&client.created_devices);
+        let first = player.scheduler.create_player(vec![]).handle;
+        player.scheduler.last_handle = i32::MAX - 1;
+
+        // act
+        let handles: Vec<i32> = (0..3)
+            .map(|_| player.scheduler.create_player(vec![]).handle)
+            .collect();
+
+        // assert
+        assert_eq!(first, 1);
+        assert_eq!(handles, vec![i32::MAX, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_oldest_task_after_handles_wrap_around() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup(&client.created_devices);
+        player.scheduler.last_handle = i32::MAX - 1;
+
+        // act
+        let older = player.scheduler.create_player(vec![]).handle;
+        let newer = player.scheduler.create_player(vec![]).handle;
+
+        // assert
+        assert_eq!((older, newer), (i32::MAX, 1));
+        assert_eq!(player.scheduler.oldest_task(), Some(i32::MAX));
+    }
+
     // Concurrency Tests
 
     #[tokio::test]
@@ -904,6 +1474,38 @@ mod tests {
         client.get_device_calls(2)[1].assert_strenth(0.0);
     }
 
+    #[tokio::test]
+    async fn test_devices_with_same_name_are_controlled_separately() {
+        let client = get_test_client(vec![
+            scalar(1, "vib", ActuatorType::Vibrate),
+            scalar(2, "vib", ActuatorType::Vibrate),
+        ])
+        .await;
+        let mut player = PlayerTest::setup(&client.created_devices);
+
+        // act
+        player.play_scalar(
+            Duration::from_millis(300),
+            Speed::new(99),
+            Some(get_actuators(vec![client.get_device(1)])),
+        );
+        player.play_scalar(
+            Duration::from_millis(100),
+            Speed::new(88),
+            Some(get_actuators(vec![client.get_device(2)])),
+        );
+        player.await_all().await;
+
+        // assert
+        let calls_1 = client.get_device_calls(1);
+        let calls_2 = client.get_device_calls(2);
+        calls_1[0].assert_strenth(0.99);
+        calls_1[1].assert_strenth(0.0);
+        calls_2[0].assert_strenth(0.88);
+        calls_2[1].assert_strenth(0.0);
+        assert!(calls_1[1].time > calls_2[1].time + Duration::from_millis(150));
+    }
+
     async fn wait_ms(ms: u64) {
         tokio::time::sleep(Duration::from_millis(ms)).await;
     }
@@ -913,8 +1515,8 @@ mod tests {
     }
 
     fn check_timing(device_calls: Vec<FakeMessage>, n: usize, start: Instant) {
-        for i in 0..n - 1 {
-            device_calls[i].assert_time((i * 100) as i32, start);
+        for (i, call) in device_calls.iter().enumerate().take(n - 1) {
+            call.assert_time((i * 100) as i32, start);
         }
     }
 
@@ -939,4 +1541,13 @@ mod tests {
         assert_eq!(Speed::new(100).as_float(), 1.0);
         assert_eq!(Speed::new(1000).as_float(), 1.0);
     }
+
+    #[test]
+    fn speed_is_clamped() {
+        assert_eq!(Speed::new(200).value, 100);
+        assert_eq!(Speed::from_float(1.5).as_float(), 1.0);
+        assert_eq!(Speed::from_float(-0.5).as_float(), 0.0);
+        assert_eq!(Speed::from_float(f64::NAN).as_float(), 0.0);
+        assert_eq!(Speed::new(100).multiply(&Speed::new(100)).as_float(), 1.0);
+    }
 }
@@ -1,10 +1,14 @@
 use funscript::FScript;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use tokio::runtime::Handle;
 use tokio::task::JoinHandle;
 
 use std::{fmt, sync::Arc, time::Duration};
 use tokio::{
-    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        watch,
+    },
     time::{sleep, Instant},
 };
 use tokio_util::sync::CancellationToken;
@@ -18,6 +22,8 @@ use crate::{
 pub struct PatternPlayer {
     pub handle: i32,
     pub scalar_resolution_ms: i32,
+    pub update_coalesce_ms: i32,
+    pub pattern_min_delta: u16,
     pub actuators: Vec<Arc<Actuator>>,
     pub settings: Vec<ActuatorSettings>,
     pub result_sender: UnboundedSender<ButtplugClientResult>,
@@ -25,6 +31,7 @@ pub struct PatternPlayer {
     pub update_receiver: UnboundedReceiver<Speed>,
     pub cancellation_token: CancellationToken,
     pub worker_task_sender: UnboundedSender<WorkerTask>,
+    pub paused: watch::Receiver<bool>,
 }
 
 impl PatternPlayer {
@@ -50,6 +57,31 @@ impl PatternPlayer {
         Ok(())
     }
 
+    /// Moves to 'position' within 'duration_ms' and consumes the player
+    #[instrument]
+    pub async fn play_linear_stroke(
+        mut self,
+        position: f64,
+        duration_ms: u32,
+    ) -> ButtplugClientResult {
+        info!("linear stroke started");
+        if self.actuators.is_empty() {
+            self.cancellation_token.cancel();
+            return Ok(());
+        }
+        if self.cancelled() {
+            return Ok(());
+        }
+        let token = self.cancellation_token.clone();
+        let result = tokio::select! {
+            _ = token.cancelled() => { Ok(()) }
+            result = self.do_linear(position, duration_ms) => { result }
+        };
+        token.cancel();
+        info!("linear stroke done");
+        result
+    }
+
     /// Executes the linear 'fscript' for 'duration' and consumes the player
     #[instrument(skip(fscript))]
     pub async fn play_linear(
@@ -103,12 +135,16 @@ impl PatternPlayer {
             return Ok(());
         }
         info!("scalar pattern started");
+        if self.cancelled() {
+            return Ok(());
+        }
         let waiter = self.stop_after(duration);
         let action_len = fscript.actions.len();
         let mut started = false;
         let mut loop_started = Instant::now();
         let mut i: usize = 0;
         let mut current_speed = speed;
+        let mut last_sent: Option<Speed> = None;
         loop {
             let mut j = 1;
             while j + i < action_len - 1
@@ -123,11 +159,18 @@ impl PatternPlayer {
             }
 
             let speed = Speed::from_fs(current).multiply(&current_speed);
+            let is_last = (i + 1).is_multiple_of(action_len);
             if !started {
                 self.do_scalar(speed, true);
                 started = true;
-            } else {
+                last_sent = Some(speed);
+            } else if is_last
+                || last_sent.is_none_or(|last| last.value.abs_diff(speed.value) >= self.pattern_min_delta)
+            {
                 self.do_update(speed, true);
+                last_sent = Some(speed);
+            } else {
+                trace!(?speed, "skipping small change");
             }
             if let Some(waiting_time) =
                 Duration::from_millis(next.at as u64).checked_sub(loop_started.elapsed())
@@ -139,7 +182,7 @@ impl PatternPlayer {
                 }
             }
             i += j;
-            if (i % action_len) == 0 {
+            if i.is_multiple_of(action_len) {
                 loop_started = Instant::now();
             }
         }
@@ -149,20 +192,46 @@ impl PatternPlayer {
         result
     }
 
+    /// Waits for 'start_delay' before playing, returns false if the player
+    /// was stopped in the meantime
+    pub async fn wait_for_start(&self, start_delay: Duration) -> bool {
+        cancellable_wait(start_delay, &self.cancellation_token).await
+    }
+
     /// Executes a constant movement with 'speed' for 'duration' and consumes the player
     #[instrument]
     pub async fn play_scalar(mut self, duration: Duration, speed: Speed) -> ButtplugClientResult {
         info!("scalar started");
+        // stopped before it started, e.g. by stop_all
+        if self.cancelled() {
+            return Ok(());
+        }
         let waiter = self.stop_after(duration);
         self.do_scalar(speed, false);
+        // updates within the coalesce window are merged, the last one is sent when it ends
+        let window = Duration::from_millis(self.update_coalesce_ms.max(0) as u64);
+        let mut last_sent = Instant::now();
+        let mut pending: Option<Speed> = None;
         loop {
             tokio::select! {
                 _ = self.cancellation_token.cancelled() => {
                     break;
                 }
+                _ = tokio::time::sleep_until(last_sent + window), if pending.is_some() => {
+                    if let Some(speed) = pending.take() {
+                        self.do_update(speed, false);
+                        last_sent = Instant::now();
+                    }
+                }
                 update = self.update_receiver.recv() => {
                     if let Some(speed) = update {
-                        self.do_update(speed, false);
+                        if last_sent.elapsed() >= window {
+                            self.do_update(speed, false);
+                            last_sent = Instant::now();
+                        } else {
+                            trace!(?speed, "coalescing update");
+                            pending = Some(speed);
+                        }
                     }
                 }
             };
@@ -173,6 +242,105 @@ impl PatternPlayer {
         result
     }
 
+    /// Executes a constant movement with 'speed' for 'duration' that fades in from 0
+    /// over 'fade_in' and back to 0 over 'fade_out', consumes the player
+    #[instrument]
+    pub async fn play_scalar_ramped(
+        mut self,
+        duration: Duration,
+        speed: Speed,
+        fade_in: Duration,
+        fade_out: Duration,
+    ) -> ButtplugClientResult {
+        info!("scalar ramped started");
+        if self.cancelled() {
+            return Ok(());
+        }
+        let fade_in = fade_in.min(duration);
+        let fade_out = fade_out.min(duration - fade_in);
+        let step = Duration::from_millis(self.scalar_resolution_ms.max(1) as u64);
+        let waiter = self.stop_after(duration);
+        let started = Instant::now();
+        let mut current_speed = speed;
+        let mut last_speed = None;
+        let mut i: u32 = 0;
+        loop {
+            self.try_update(&mut current_speed);
+            let factor = get_ramp_factor(step * i, step, duration, fade_in, fade_out);
+            let speed = Speed::new((current_speed.as_float() * factor * 100.0).round() as i64);
+            match last_speed {
+                None => self.do_scalar(speed, false),
+                Some(last) if last != speed.value => self.do_update(speed, false),
+                _ => {}
+            }
+            last_speed = Some(speed.value);
+            i = i.saturating_add(1);
+            let waiting_time = (step * i).saturating_sub(started.elapsed());
+            if !(cancellable_wait(waiting_time, &self.cancellation_token).await) {
+                break;
+            }
+        }
+        waiter.abort();
+        let result = self.do_stop(false).await;
+        info!("scalar ramped done");
+        result
+    }
+
+    /// Executes a movement for 'duration' that moves towards a new random speed
+    /// between 'min' and 'max' every 'change', consumes the player. A fixed
+    /// 'seed' makes the sequence reproducible
+    #[instrument]
+    pub async fn play_scalar_random(
+        self,
+        duration: Duration,
+        min: Speed,
+        max: Speed,
+        change: Duration,
+        seed: Option<u64>,
+    ) -> ButtplugClientResult {
+        info!("scalar random started");
+        if self.cancelled() {
+            return Ok(());
+        }
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let (min, max) = (min.value.min(max.value), min.value.max(max.value));
+        let step = Duration::from_millis(self.scalar_resolution_ms.max(1) as u64);
+        let steps_per_change = (change.as_millis() / step.as_millis()).max(1) as u32;
+        let waiter = self.stop_after(duration);
+        let started = Instant::now();
+        let mut from = rng.gen_range(min..=max);
+        let mut to = rng.gen_range(min..=max);
+        let mut last_speed = None;
+        let mut i: u32 = 0;
+        loop {
+            if i > 0 && i.is_multiple_of(steps_per_change) {
+                from = to;
+                to = rng.gen_range(min..=max);
+            }
+            let progress = f64::from(i % steps_per_change) / f64::from(steps_per_change);
+            let value = f64::from(from) + (f64::from(to) - f64::from(from)) * progress;
+            let speed = Speed::new(value.round() as i64);
+            match last_speed {
+                None => self.do_scalar(speed, false),
+                Some(last) if last != speed.value => self.do_update(speed, false),
+                _ => {}
+            }
+            last_speed = Some(speed.value);
+            i = i.saturating_add(1);
+            let waiting_time = (step * i).saturating_sub(started.elapsed());
+            if !(cancellable_wait(waiting_time, &self.cancellation_token).await) {
+                break;
+            }
+        }
+        waiter.abort();
+        let result = self.do_stop(false).await;
+        info!("scalar random done");
+        result
+    }
+
     fn do_update(&self, speed: Speed, is_pattern: bool) {
         for (i, actuator) in self.actuators.iter().enumerate() {
             trace!("do_update {} {:?}", speed, actuator);
@@ -264,10 +432,25 @@ impl PatternPlayer {
         self.result_receiver.recv().await.unwrap()
     }
 
+    /// Cancels the player after 'duration', the time while paused is not counted
     fn stop_after(&self, duration: Duration) -> JoinHandle<()> {
         let cancellation_clone = self.cancellation_token.clone();
+        let mut paused = self.paused.clone();
         Handle::current().spawn(async move {
-            sleep(duration).await;
+            let mut remaining = duration;
+            loop {
+                if paused.wait_for(|paused| !paused).await.is_err() {
+                    sleep(remaining).await;
+                    break;
+                }
+                let started = Instant::now();
+                tokio::select! {
+                    _ = sleep(remaining) => break,
+                    Ok(_) = paused.wait_for(|paused| *paused) => {
+                        remaining = remaining.saturating_sub(started.elapsed());
+                    }
+                }
+            }
             cancellation_clone.cancel();
         })
     }
@@ -316,6 +499,26 @@ impl LinearRange {
     }
 }
 
+fn get_ramp_factor(
+    elapsed: Duration,
+    step: Duration,
+    duration: Duration,
+    fade_in: Duration,
+    fade_out: Duration,
+) -> f64 {
+    let fade_in_factor = if fade_in.is_zero() {
+        1.0
+    } else {
+        ((elapsed + step).as_secs_f64() / fade_in.as_secs_f64()).min(1.0)
+    };
+    let fade_out_factor = if fade_out.is_zero() {
+        1.0
+    } else {
+        (duration.saturating_sub(elapsed).as_secs_f64() / fade_out.as_secs_f64()).min(1.0)
+    };
+    fade_in_factor.min(fade_out_factor)
+}
+
 fn apply_scalar_settings(speed: Speed, settings: &ActuatorSettings) -> Speed {
     if speed.value == 0 {
         return speed;
@@ -14,17 +14,13 @@ impl Display for Speed {
 }
 
 impl Speed {
-    pub fn new(mut percentage: i64) -> Speed {
-        if percentage < 0 {
-            percentage = 0;
-        }
-        if percentage > 100 {
-            percentage = 100;
-        }
+    /// Percentage, clamped to 0..=100
+    pub fn new(percentage: i64) -> Speed {
         Speed {
-            value: percentage as u16,
+            value: percentage.clamp(0, 100) as u16,
         }
     }
+    /// Factor, clamped to 0.0..=1.0
     pub fn from_float(factor: f64) -> Speed {
         Speed::new((factor * 100.0) as i64)    
     }
@@ -45,4 +41,46 @@ impl Speed {
     pub fn as_float(self) -> f64 {
         self.value as f64 / 100.0
     }
+    /// Linear interpolation, 't' is clamped to 0.0..=1.0
+    pub fn lerp(from: Speed, to: Speed, t: f64) -> Speed {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let from_f = from.value as f64;
+        let to_f = to.value as f64;
+        Speed::new((from_f + (to_f - from_f) * t).round() as i64)
+    }
+    /// 'steps' evenly spaced speeds, starting at 'from' and ending at 'to'
+    pub fn ramp(from: Speed, to: Speed, steps: usize) -> impl Iterator<Item = Speed> {
+        let last = steps.saturating_sub(1).max(1) as f64;
+        (0..steps).map(move |i| Speed::lerp(from, to, i as f64 / last))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Speed;
+
+    #[test]
+    fn lerp_endpoints_and_midpoint() {
+        assert_eq!(Speed::lerp(Speed::new(20), Speed::new(80), 0.0).value, 20);
+        assert_eq!(Speed::lerp(Speed::new(20), Speed::new(80), 1.0).value, 80);
+        assert_eq!(Speed::lerp(Speed::new(20), Speed::new(80), 0.5).value, 50);
+        assert_eq!(Speed::lerp(Speed::new(80), Speed::new(20), 0.5).value, 50);
+    }
+
+    #[test]
+    fn lerp_clamps_t() {
+        assert_eq!(Speed::lerp(Speed::new(20), Speed::new(80), -1.0).value, 20);
+        assert_eq!(Speed::lerp(Speed::new(20), Speed::new(80), 2.0).value, 80);
+        assert_eq!(Speed::lerp(Speed::new(20), Speed::new(80), f64::NAN).value, 20);
+    }
+
+    #[test]
+    fn ramp_is_evenly_spaced() {
+        let speeds: Vec<u16> = Speed::ramp(Speed::min(), Speed::max(), 5)
+            .map(|x| x.value)
+            .collect();
+        assert_eq!(speeds, vec![0, 25, 50, 75, 100]);
+        assert_eq!(Speed::ramp(Speed::min(), Speed::max(), 1).count(), 1);
+        assert_eq!(Speed::ramp(Speed::min(), Speed::max(), 0).count(), 0);
+    }
 }
\ No newline at end of file
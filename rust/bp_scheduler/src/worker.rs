@@ -1,9 +1,9 @@
 use buttplug::client::{LinearCommand, ButtplugClientError};
-use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use tokio::{runtime::Handle, sync::mpsc::UnboundedReceiver};
-use tracing::{error, info, trace, warn};
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::{runtime::Handle, sync::mpsc::UnboundedReceiver, time::sleep_until};
+use tracing::{error, info, trace};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 
 use crate::{access::DeviceAccess, actuator::Actuator, speed::Speed};
 
@@ -16,8 +16,16 @@ pub type ButtplugClientResult<T = ()> = Result<T, ButtplugClientError>;
 /// its not necessary to introduce Mutex/etc to handle multithreaded access
 pub struct ButtplugWorker {
     pub task_receiver: UnboundedReceiver<WorkerTask>,
+    pub error_sender: Option<UnboundedSender<ActuatorError>>,
+    pub crossfade: Duration,
+    pub command_interval: Duration,
+    pub fade_out: Duration,
 }
 
+/// A command that failed on a single actuator, the other actuators of the
+/// same task are not affected
+pub type ActuatorError = (Arc<Actuator>, String);
+
 #[derive(Clone, Debug)]
 pub enum WorkerTask {
     Start(Arc<Actuator>, Speed, bool, i32),
@@ -35,52 +43,143 @@ pub enum WorkerTask {
         bool,
         UnboundedSender<ButtplugClientResult>,
     ),
+    /// Rotation direction, true is clockwise
+    Direction(Arc<Actuator>, bool),
+    /// Stops all devices but keeps track of the speeds until 'Resume'
+    Pause,
+    Resume,
     StopAll, // global but required for resetting device state
 }
 
 impl ButtplugWorker {
+    /// Receives every failed device command from now on
+    pub fn report_errors(&mut self) -> UnboundedReceiver<ActuatorError> {
+        let (error_sender, error_receiver) = unbounded_channel();
+        self.error_sender = Some(error_sender);
+        error_receiver
+    }
+
     pub async fn run_worker_thread(&mut self) {
-        let mut device_access = DeviceAccess::default();
+        let mut device_access = DeviceAccess::with_crossfade(self.crossfade)
+            .with_command_interval(self.command_interval)
+            .with_fade_out(self.fade_out)
+            .with_error_sender(self.error_sender.clone());
         loop {
-            if let Some(next_action) = self.task_receiver.recv().await {
-                trace!("worker exec action {:?}", next_action);
-                match next_action {
-                    WorkerTask::Start(actuator, speed, is_pattern, handle) => {
-                        device_access
-                            .start_scalar(&actuator, speed, is_pattern, handle)
-                            .await;
+            let next_action = match device_access.next_flush() {
+                Some(deadline) => tokio::select! {
+                    next_action = self.task_receiver.recv() => next_action,
+                    _ = sleep_until(deadline.into()) => {
+                        // send the speed changes that were held back by the rate limit
+                        self.flush(&mut device_access, &mut vec![]).await;
+                        continue;
                     }
-                    WorkerTask::Update(actuator, speed, is_pattern, handle) => {
-                        device_access.update_scalar(&actuator, speed, is_pattern, handle).await;
+                },
+                None => self.task_receiver.recv().await,
+            };
+            if let Some(next_action) = next_action {
+                // everything that is already queued is executed as one batch, so that
+                // the speeds of all actuators of a device are sent in a single command
+                let mut batch = vec![next_action];
+                while let Ok(next_action) = self.task_receiver.try_recv() {
+                    batch.push(next_action);
+                }
+                let mut ended = vec![];
+                for next_action in batch {
+                    trace!("worker exec action {:?}", next_action);
+                    self.exec(next_action, &mut device_access, &mut ended).await;
+                }
+                self.flush(&mut device_access, &mut ended).await;
+            }
+        }
+    }
+
+    async fn exec(
+        &self,
+        next_action: WorkerTask,
+        device_access: &mut DeviceAccess,
+        ended: &mut Vec<(Arc<Actuator>, UnboundedSender<ButtplugClientResult>)>,
+    ) {
+        match next_action {
+            WorkerTask::Start(actuator, speed, is_pattern, handle) => {
+                device_access.start_scalar(&actuator, speed, is_pattern, handle);
+            }
+            WorkerTask::Update(actuator, speed, is_pattern, handle) => {
+                device_access.update_scalar(&actuator, speed, is_pattern, handle);
+            }
+            WorkerTask::End(actuator, is_pattern, handle, result_sender) => {
+                device_access.stop_scalar(&actuator, is_pattern, handle);
+                ended.push((actuator, result_sender));
+            }
+            WorkerTask::Move(actuator, position, duration_ms, finish, result_sender) => {
+                if device_access.is_paused() {
+                    if finish {
+                        let _ = result_sender.send(Ok(()));
                     }
-                    WorkerTask::End(actuator, is_pattern, handle, result_sender) => {
-                        let result = device_access
-                            .stop_scalar(&actuator, is_pattern, handle)
-                            .await;
+                    return;
+                }
+                let cmd = LinearCommand::LinearMap(HashMap::from([(
+                    actuator.index_in_device,
+                    (duration_ms, position),
+                )]));
+                let error_sender = self.error_sender.clone();
+                Handle::current().spawn(async move {
+                    let result = actuator.device.linear(&cmd).await;
+                    if finish {
                         if let Err(err) = result_sender.send(result) {
-                            error!("failed sending scalar result {:?}", err)
+                            error!("failed sending linear result {:?}", err)
                         }
+                    } else if let (Err(err), Some(error_sender)) = (&result, error_sender) {
+                        let _ = error_sender.send((actuator.clone(), err.to_string()));
                     }
-                    WorkerTask::Move(actuator, position, duration_ms, finish, result_sender) => {
-                        let cmd = LinearCommand::LinearMap(HashMap::from([(
-                            actuator.index_in_device,
-                            (duration_ms, position),
-                        )]));
-                        Handle::current().spawn(async move {
-                            let result = actuator.device.linear(&cmd).await;
-                            if finish {
-                                if let Err(err) = result_sender.send(result) {
-                                    error!("failed sending linear result {:?}", err)
-                                }
-                            }
-                        });
-                    }
-                    WorkerTask::StopAll => {
-                        device_access.clear_all();
-                        info!("stop all action");
-                    }
-                }
+                });
+            }
+            WorkerTask::Direction(actuator, clockwise) => {
+                device_access.set_direction(&actuator, clockwise);
+            }
+            WorkerTask::Pause => {
+                device_access.pause();
+            }
+            WorkerTask::Resume => {
+                device_access.resume();
+            }
+            WorkerTask::StopAll => {
+                self.flush(device_access, ended).await;
+                device_access.clear_all();
+                info!("stop all action");
+            }
+        }
+    }
+
+    /// Sends the pending speed changes and reports the result to all ended tasks,
+    /// failures of actuators without an ended task go to the error receiver
+    async fn flush(
+        &self,
+        device_access: &mut DeviceAccess,
+        ended: &mut Vec<(Arc<Actuator>, UnboundedSender<ButtplugClientResult>)>,
+    ) {
+        let mut results = device_access.flush().await;
+        for (actuator, result) in results.iter() {
+            if !ended.iter().any(|(x, _)| x.key() == actuator.key()) {
+                self.report(actuator, result);
+            }
+        }
+        for (actuator, result_sender) in ended.drain(..) {
+            let sent = results
+                .iter()
+                .position(|(sent, _)| sent.key() == actuator.key());
+            let result = match sent {
+                Some(i) => results.remove(i).1,
+                None => Ok(()),
+            };
+            if let Err(err) = result_sender.send(result) {
+                error!("failed sending scalar result {:?}", err)
             }
         }
     }
+
+    fn report(&self, actuator: &Arc<Actuator>, result: &ButtplugClientResult) {
+        if let (Err(err), Some(error_sender)) = (result, &self.error_sender) {
+            let _ = error_sender.send((actuator.clone(), err.to_string()));
+        }
+    }
 }
\ No newline at end of file
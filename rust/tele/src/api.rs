@@ -122,6 +122,8 @@ pub struct ApiBuilder<T> {
     pub control: Vec<ApiControl<T>>,
     pub update: ApiUpdate<T>,
     pub stop: ApiStop<T>,
+    /// Runs before every command, control and update, but not before queries
+    pub on_cmd: fn(&mut T),
 }
 
 impl<T> ApiBuilder<T> {
@@ -140,6 +142,7 @@ impl<T> ApiBuilder<T> {
             control: vec![],
             update: ApiUpdate { exec: |_,_,_| false },
             stop: ApiStop { exec: |_,_| true },
+            on_cmd: |_| {},
         }
     }
     pub fn def_cmd(mut self, cmd: ApiCmd0<T>) -> Self {
@@ -166,6 +169,10 @@ impl<T> ApiBuilder<T> {
         self.stop = cmd;
         self
     }
+    pub fn def_on_cmd(mut self, on_cmd: fn(&mut T)) -> Self {
+        self.on_cmd = on_cmd;
+        self
+    }
     pub fn def_qry_str(mut self, cmd: ApiQryStr<T>) -> Self {
         self.qry_str.push(cmd);
         self
@@ -196,20 +203,27 @@ pub trait Api<T> {
     fn state(&mut self) -> Arc<Mutex<Option<T>>>;
     fn fns(&self) -> ApiBuilder<T>;
     fn destroy(&mut self) -> ApiCmd0<T>;
+    /// Receives the error of the last failed init, None once init succeeds
+    fn set_last_error(&mut self, _err: Option<String>) {}
     fn exec_cmd_0(&mut self, cmd: &str) -> bool {
         if cmd_matches(self.fns().init.name, cmd) {
-            if let Ok(mut guard) = self.state().try_lock() {
-                match (self.fns().init.exec)() {
+            let state = self.state();
+            let result = match state.try_lock() {
+                Ok(mut guard) => match (self.fns().init.exec)() {
                     Ok(state) => {
                         guard.replace(state);
-                        return true;
+                        Ok(())
                     }
                     Err(err) => {
                         error!("error constructing state: {:?}", err);
+                        Err(format!("{:#}", err))
                     }
-                }
-            }
-            return false;
+                },
+                Err(_) => Err(String::from("state is locked")),
+            };
+            let constructed = result.is_ok();
+            self.set_last_error(result.err());
+            return constructed;
         }
 
         if cmd_matches(self.destroy().name, cmd) {
@@ -222,21 +236,21 @@ pub trait Api<T> {
         }
 
         if let Some(api) = self.get_qry(self.fns().cmd, cmd) {
-            return self.try_exec(|tk| (api.exec)(tk), false);
+            return self.try_exec_cmd(|tk| (api.exec)(tk), false);
         }
         self.fail_dispatch(false)
     }
 
     fn exec_cmd_1(&mut self, cmd: &str, arg0: &str) -> bool {
         if let Some(api) = self.get_qry(self.fns().cmd_1, cmd) {
-            return self.try_exec(|tk| (api.exec)(tk, arg0), false);
+            return self.try_exec_cmd(|tk| (api.exec)(tk, arg0), false);
         }
         self.fail_dispatch(false)
     }
 
     fn exec_cmd_2(&mut self, cmd: &str, arg0: &str, arg1: &str) -> bool {
         if let Some(api) = self.get_qry(self.fns().cmd_2, cmd) {
-            return self.try_exec(|tk| (api.exec)(tk, arg0, arg1), false);
+            return self.try_exec_cmd(|tk| (api.exec)(tk, arg0, arg1), false);
         }
         self.fail_dispatch(false)
     }
@@ -293,14 +307,14 @@ pub trait Api<T> {
     ) -> i32 {
         let c = self.fns().control;
         if let Some(api) = self.get_qry(c, qry) {
-            return self.try_exec(|tk| (api.exec)(tk, arg0, arg1, arg2, arg3), -1);
+            return self.try_exec_cmd(|tk| (api.exec)(tk, arg0, arg1, arg2, arg3), -1);
         }
         self.fail_dispatch(-1)
     }
 
     fn exec_update(&mut self, arg0: i32, arg1: i32) -> bool {
         let update = self.fns().update;  
-        self.try_exec(|tk| (update.exec)(tk, arg0, arg1), false)
+        self.try_exec_cmd(|tk| (update.exec)(tk, arg0, arg1), false)
     }
 
     fn exec_stop(&mut self, arg0: i32) -> bool {
@@ -337,6 +351,18 @@ pub trait Api<T> {
         default
     }
 
+    fn try_exec_cmd<F, R>(&mut self, func: F, default: R) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+        R: std::fmt::Debug
+    {
+        let on_cmd = self.fns().on_cmd;
+        self.try_exec(|tk| {
+            on_cmd(tk);
+            func(tk)
+        }, default)
+    }
+
     fn fail_dispatch<D>(&self, default: D) -> D {
         error!("cmd not found");
         default
@@ -427,6 +453,43 @@ mod tests {
         }
     }
 
+    struct FailingApi {
+        pub state: Arc<Mutex<Option<EmptyState>>>,
+        pub last_error: Option<String>,
+    }
+
+    impl Api<EmptyState> for FailingApi {
+        fn fns(&self) -> ApiBuilder<EmptyState> {
+            ApiBuilder::new(ApiInit {
+                name: "ctor",
+                exec: || Err(anyhow::anyhow!("no runtime")),
+            })
+        }
+        fn state(&mut self) -> Arc<Mutex<Option<EmptyState>>> {
+            self.state.clone()
+        }
+        fn destroy(&mut self) -> ApiCmd0<EmptyState> {
+            ApiCmd0 {
+                name: "dtor",
+                exec: |_| false,
+            }
+        }
+        fn set_last_error(&mut self, err: Option<String>) {
+            self.last_error = err;
+        }
+    }
+
+    #[test]
+    fn failed_init_sets_last_error() {
+        let mut api = FailingApi {
+            state: Arc::new(Mutex::new(None)),
+            last_error: None,
+        };
+        assert!(!api.exec_cmd_0("ctor"));
+        assert_eq!(api.last_error, Some(String::from("no runtime")));
+        assert!(api.state.lock().unwrap().is_none());
+    }
+
     #[test]
     fn test_api() {
         // enable_log();
@@ -445,4 +508,52 @@ mod tests {
         assert!(api.exec_qry_bool("existing.query"));
         assert!(api.exec_qry_bool_1("existing.query", "something"));
     }
+
+    struct CountingApi {
+        pub state: Arc<Mutex<Option<usize>>>,
+    }
+
+    impl Api<usize> for CountingApi {
+        fn fns(&self) -> ApiBuilder<usize> {
+            ApiBuilder::new(ApiInit {
+                name: "ctor",
+                exec: || Ok(0),
+            })
+            .def_on_cmd(|calls| *calls += 1)
+            .def_cmd(ApiCmd0 {
+                name: "existing.command",
+                exec: |_| true,
+            })
+            .def_qry_str(ApiQryStr {
+                name: "existing.query",
+                exec: |_| String::from("working"),
+                default: "foobar",
+            })
+            .def_update(ApiUpdate {
+                exec: |_, _, _| true,
+            })
+        }
+        fn state(&mut self) -> Arc<Mutex<Option<usize>>> {
+            self.state.clone()
+        }
+        fn destroy(&mut self) -> ApiCmd0<usize> {
+            ApiCmd0 {
+                name: "dtor",
+                exec: |_| false,
+            }
+        }
+    }
+
+    #[test]
+    fn on_cmd_runs_for_commands_but_not_queries() {
+        let mut api = CountingApi {
+            state: Arc::new(Mutex::new(None)),
+        };
+        api.exec_cmd_0("ctor");
+        api.exec_qry_str("existing.query");
+        assert_eq!(*api.state.lock().unwrap(), Some(0));
+        api.exec_cmd_0("existing.command");
+        api.exec_update(1, 100);
+        assert_eq!(*api.state.lock().unwrap(), Some(2));
+    }
 }
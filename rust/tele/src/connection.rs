@@ -1,20 +1,28 @@
 use std::{
     fmt::{self, Display},
-    sync::Arc,
-    time::Duration,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use bp_scheduler::{
     actuator::{get_actuators, Actuator},
     speed::Speed,
+    StopAllHandle,
 };
 use buttplug::{
     client::{ButtplugClient, ButtplugClientDevice, ButtplugClientEvent},
     core::message::ActuatorType,
 };
-use crossbeam_channel::Sender;
-use futures::StreamExt;
-use tokio::runtime::Handle;
+use crossbeam_channel::{SendError, Sender};
+use futures::{Future, StreamExt};
+use itertools::Itertools;
+use tokio::{
+    sync::{broadcast, mpsc::Receiver, watch},
+    time::sleep_until,
+};
 use tracing::{debug, error, info};
 
 use crate::settings::TkConnectionType;
@@ -27,140 +35,426 @@ pub enum TkCommand {
     StopScan,
     StopAll,
     Disconect,
+    /// Handled as if it was received from the buttplug client
+    #[cfg(test)]
+    InjectEvent(ButtplugClientEvent),
 }
 
 #[derive(Clone, Debug)]
 pub enum Task {
     Scalar(Speed),
+    /// Constant rotation, true is clockwise
+    Rotate(Speed, bool),
+    ScalarRamped(Speed, Duration, Duration),
+    ScalarRandom(Speed, Speed, Duration),
     Pattern(Speed, ActuatorType, String),
+    MultiPattern(Speed, Vec<(ActuatorType, String)>),
     Linear(Speed, String),
-    LinearOscillate(Speed, String)
+    LinearOscillate(Speed, String),
+    LinearStroke(f64, u32),
 }
 
 #[derive(Clone, Debug)]
 pub enum TkConnectionEvent {
     Connected(String),
     ConnectionFailure(String),
+    /// The connection was closed on request, all devices are gone
+    Disconnected,
     DeviceAdded(Arc<ButtplugClientDevice>),
     DeviceRemoved(Arc<ButtplugClientDevice>),
     ActionStarted(Task, Vec<Arc<Actuator>>, Vec<String>, i32),
     ActionDone(Task, Duration, i32),
+    /// Sent when a task ended for any reason, after its done or error event
+    HandleFinished(i32),
     ActionError(Arc<Actuator>, String),
+    ActuatorError(Arc<Actuator>, String),
+    Reconnecting(u32),
+    CommandRejected(i32),
+    CommandDropped(usize),
+    PatternError(String, String),
+    /// A device selector could not be used, i.e. an invalid name pattern
+    SelectorError(String, String),
+    /// Value reported by a sensor of a device, see 'Telekinesis::subscribe_sensor'
+    SensorReading(String, i32),
+    /// The sensors of a device could not be subscribed
+    SensorError(String, String),
+    Heartbeat(Duration),
+    ScanStarted,
+    ScanStopped,
+    /// The scan could not be started, i.e. because the command queue stayed full
+    ScanFailed(String),
+}
+
+/// Why 'handle_connection' returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TkConnectionEnd {
+    Disconnected,
+    Lost,
+    /// No activity within the idle timeout, see 'wait_for_activity'
+    Idle,
 }
 
+/// Processes commands and buttplug events until the connection ends. With an
+/// 'idle_disconnect' the connection is closed when 'activity' was not updated
+/// for that long
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_connection(
-    event_sender: crossbeam_channel::Sender<TkConnectionEvent>,
-    event_sender_internal: crossbeam_channel::Sender<TkConnectionEvent>,
-    mut command_receiver: tokio::sync::mpsc::Receiver<TkCommand>,
+    event_sender: TkEventSender,
+    event_sender_internal: TkEventSender,
+    command_receiver: &mut Receiver<TkCommand>,
     client: ButtplugClient,
     connection_type: TkConnectionType,
-) {
-    let sender_interla_clone = event_sender_internal.clone();
+    stop_all_on_disconnect: Option<&StopAllHandle>,
+    idle_disconnect: Option<Duration>,
+    activity: &watch::Receiver<Instant>,
+) -> TkConnectionEnd {
     let mut buttplug_events = client.event_stream();
-    let sender_clone = event_sender.clone();
-    Handle::current().spawn(async move {   
-        // let span = span!(Level::INFO, "tk_conn_events");
-        // let _enter = span.enter();
-        debug!("starting...");
-
-        loop {
-            let next_cmd = command_receiver.recv().await;
-            if let Some(cmd) = next_cmd {
-                debug!("Executing command {:?}", cmd);
-                match cmd {
-                    TkCommand::Scan => {
-                        if let Err(err) = client.start_scanning().await {
-                            let error = err.to_string();
-                            error!("connection failure {}", error);
-                            let failure = TkConnectionEvent::ConnectionFailure(err.to_string());
-                            try_send_event(&sender_clone, failure.clone());
-                            try_send_event(&event_sender_internal, failure);
-                        } else {
-                            let settings = connection_type.to_string();
-                            info!(settings, "connection success");
-
-                            let connected = TkConnectionEvent::Connected(settings.clone());
-                            try_send_event(&sender_clone, connected.clone());
-                            try_send_event(&event_sender_internal, connected);
-                        }
-                    }
-                    TkCommand::StopScan => {
-                        if let Err(err) = client.stop_scanning().await {
-                            let error = err.to_string();
-                            error!(error, "failed stop scan");
-                            let err = TkConnectionEvent::ConnectionFailure(error);
-                            try_send_event(&sender_clone, err.clone());
-                            try_send_event(&event_sender_internal, err);
-                        }
-                    }
-                    TkCommand::Disconect => {
+    debug!("starting...");
+    let end = loop {
+        let idle_deadline = idle_disconnect.map(|idle| *activity.borrow() + idle);
+        tokio::select! {
+            next_cmd = command_receiver.recv() => {
+                match next_cmd {
+                    Some(TkCommand::Disconect) => {
+                        debug!("Executing command {:?}", TkCommand::Disconect);
+                        client
+                            .stop_all_devices()
+                            .await
+                            .unwrap_or_else(|_| error!("failed to stop all devices"));
                         client
                             .disconnect()
                             .await
                             .unwrap_or_else(|_| error!("failed to disconnect"));
-                        break;
+                        break TkConnectionEnd::Disconnected;
                     }
-                    TkCommand::StopAll => {
-                        client
-                            .stop_all_devices()
-                            .await
-                            .unwrap_or_else(|_| error!("failed to stop all devices"));
+                    #[cfg(test)]
+                    Some(TkCommand::InjectEvent(event)) => {
+                        let end = handle_client_event(
+                            Some(event),
+                            &client,
+                            &event_sender,
+                            &event_sender_internal,
+                            stop_all_on_disconnect,
+                        )
+                        .await;
+                        if let Some(end) = end {
+                            break end;
+                        }
+                    }
+                    Some(cmd) => {
+                        handle_command(cmd, &client, &event_sender, &event_sender_internal, &connection_type).await
                     }
+                    None => break TkConnectionEnd::Disconnected,
                 }
-            } else {
-                break;
             }
+            _ = sleep_until_some(idle_deadline) => {
+                // activity may have been updated while sleeping
+                if idle_disconnect.is_some_and(|idle| activity.borrow().elapsed() >= idle) {
+                    info!("idle, disconnecting");
+                    client
+                        .stop_all_devices()
+                        .await
+                        .unwrap_or_else(|_| error!("failed to stop all devices"));
+                    client
+                        .disconnect()
+                        .await
+                        .unwrap_or_else(|_| error!("failed to disconnect"));
+                    try_send_event(&event_sender, TkConnectionEvent::Disconnected);
+                    try_send_event(&event_sender_internal, TkConnectionEvent::Disconnected);
+                    break TkConnectionEnd::Idle;
+                }
+            }
+            next_event = buttplug_events.next() => {
+                let end = handle_client_event(
+                    next_event,
+                    &client,
+                    &event_sender,
+                    &event_sender_internal,
+                    stop_all_on_disconnect,
+                )
+                .await;
+                if let Some(end) = end {
+                    break end;
+                }
+            }
+        }
+    };
+    info!("stream closed");
+    end
+}
+
+/// Returns how the connection ended if the event (or the end of the stream) closes it
+async fn handle_client_event(
+    next_event: Option<ButtplugClientEvent>,
+    client: &ButtplugClient,
+    event_sender: &TkEventSender,
+    event_sender_internal: &TkEventSender,
+    stop_all_on_disconnect: Option<&StopAllHandle>,
+) -> Option<TkConnectionEnd> {
+    match (next_event, stop_all_on_disconnect) {
+        (Some(ButtplugClientEvent::ServerDisconnect) | None, _) => {
+            info!("server disconnected");
+            let failure = TkConnectionEvent::ConnectionFailure(String::from("Server disconnected"));
+            try_send_event(event_sender, failure.clone());
+            try_send_event(event_sender_internal, failure);
+            return Some(TkConnectionEnd::Lost);
+        }
+        (Some(ButtplugClientEvent::DeviceRemoved(device)), Some(stop_all)) => {
+            handle_event(ButtplugClientEvent::DeviceRemoved(device), event_sender, event_sender_internal);
+            info!("device lost, stopping all devices");
+            // ends the tasks so they don't start the remaining devices again
+            stop_all.stop_all();
+            client
+                .stop_all_devices()
+                .await
+                .unwrap_or_else(|_| error!("failed to stop all devices"));
         }
-        info!("stream closed");
-    });
-
-    // let span = span!(Level::INFO, "tk_device_events");
-    // let _enter = span.enter();
-
-    while let Some(event) = buttplug_events.next().await {
-        match event.clone() {
-            ButtplugClientEvent::DeviceAdded(device) => {
-                let name = device.name();
-                let index = device.index();
-                let actuators = get_actuators(vec![device.clone()]);
-                info!(name, index, ?actuators, "device connected");
-
-                let added = TkConnectionEvent::DeviceAdded(device);
-                try_send_event(&sender_interla_clone, added.clone());
-                try_send_event(&event_sender, added);
+        (Some(event), _) => handle_event(event, event_sender, event_sender_internal),
+    }
+    None
+}
+
+async fn sleep_until_some(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Blocks an idle connection until 'activity' is updated or a command is received.
+/// None if the connection should not be opened again, otherwise the command that
+/// ended the wait, which is executed once connected again
+pub async fn wait_for_activity(
+    command_receiver: &mut Receiver<TkCommand>,
+    activity: &mut watch::Receiver<Instant>,
+    idle_disconnect: Duration,
+) -> Option<Option<TkCommand>> {
+    // activity between going idle and waiting here
+    if activity.borrow_and_update().elapsed() < idle_disconnect {
+        return Some(None);
+    }
+    tokio::select! {
+        changed = activity.changed() => changed.ok().map(|_| None),
+        cmd = command_receiver.recv() => match cmd {
+            Some(TkCommand::Disconect) | None => None,
+            Some(cmd) => Some(Some(cmd)),
+        },
+    }
+}
+
+/// Awaits 'future' while no client is connected, i.e. the reconnect backoff.
+/// Commands received meanwhile are dropped, None if a disconnect was requested
+pub async fn unless_disconnected<F: Future>(
+    command_receiver: &mut Receiver<TkCommand>,
+    future: F,
+) -> Option<F::Output> {
+    tokio::pin!(future);
+    loop {
+        tokio::select! {
+            output = &mut future => return Some(output),
+            cmd = command_receiver.recv() => match cmd {
+                Some(TkCommand::Disconect) | None => return None,
+                Some(cmd) => debug!("not connected, dropping command {:?}", cmd),
             }
-            ButtplugClientEvent::DeviceRemoved(device) => {
-                let name = device.name();
-                let index = device.index();
-                info!(name, index, "device disconnected");
-
-                let removed = TkConnectionEvent::DeviceRemoved(device);
-                try_send_event(&sender_interla_clone, removed.clone());
-                try_send_event(&event_sender, removed);
+        }
+    }
+}
+
+pub async fn handle_command(
+    cmd: TkCommand,
+    client: &ButtplugClient,
+    event_sender: &TkEventSender,
+    event_sender_internal: &TkEventSender,
+    connection_type: &TkConnectionType,
+) {
+    debug!("Executing command {:?}", cmd);
+    match cmd {
+        TkCommand::Scan => {
+            if let Err(err) = client.start_scanning().await {
+                let error = err.to_string();
+                error!("connection failure {}", error);
+                let failure = TkConnectionEvent::ConnectionFailure(err.to_string());
+                try_send_event(event_sender, failure.clone());
+                try_send_event(event_sender_internal, failure);
+            } else {
+                let settings = connection_type.to_string();
+                info!(settings, "connection success");
+
+                let connected = TkConnectionEvent::Connected(settings.clone());
+                try_send_event(event_sender, connected.clone());
+                try_send_event(event_sender_internal, connected);
+                try_send_event(event_sender, TkConnectionEvent::ScanStarted);
+                try_send_event(event_sender_internal, TkConnectionEvent::ScanStarted);
             }
-            ButtplugClientEvent::Error(err) => {
-                error!(?err, "client error event");
+        }
+        TkCommand::StopScan => {
+            if let Err(err) = client.stop_scanning().await {
+                let error = err.to_string();
+                error!(error, "failed stop scan");
+                let err = TkConnectionEvent::ConnectionFailure(error);
+                try_send_event(event_sender, err.clone());
+                try_send_event(event_sender_internal, err);
+            } else {
+                try_send_event(event_sender, TkConnectionEvent::ScanStopped);
+                try_send_event(event_sender_internal, TkConnectionEvent::ScanStopped);
             }
-            _ => {}
+        }
+        TkCommand::Disconect => {
+            client
+                .disconnect()
+                .await
+                .unwrap_or_else(|_| error!("failed to disconnect"));
+        }
+        TkCommand::StopAll => {
+            client
+                .stop_all_devices()
+                .await
+                .unwrap_or_else(|_| error!("failed to stop all devices"));
+        }
+        // only reaches the client events within 'handle_connection'
+        #[cfg(test)]
+        TkCommand::InjectEvent(_) => {}
+    }
+}
+
+fn handle_event(
+    event: ButtplugClientEvent,
+    event_sender: &TkEventSender,
+    event_sender_internal: &TkEventSender,
+) {
+    match event {
+        ButtplugClientEvent::DeviceAdded(device) => {
+            let name = device.name();
+            let index = device.index();
+            let actuators = get_actuators(vec![device.clone()]);
+            info!(name, index, ?actuators, "device connected");
+
+            let added = TkConnectionEvent::DeviceAdded(device);
+            try_send_event(event_sender_internal, added.clone());
+            try_send_event(event_sender, added);
+        }
+        ButtplugClientEvent::DeviceRemoved(device) => {
+            let name = device.name();
+            let index = device.index();
+            info!(name, index, "device disconnected");
+
+            let removed = TkConnectionEvent::DeviceRemoved(device);
+            try_send_event(event_sender_internal, removed.clone());
+            try_send_event(event_sender, removed);
+        }
+        ButtplugClientEvent::ScanningFinished => {
+            info!("scanning finished");
+            try_send_event(event_sender_internal, TkConnectionEvent::ScanStopped);
+            try_send_event(event_sender, TkConnectionEvent::ScanStopped);
+        }
+        ButtplugClientEvent::Error(err) => {
+            error!(?err, "client error event");
+        }
+        _ => {}
+    };
+}
+
+/// Event channel that counts the events that were sent or dropped,
+/// async tasks can 'subscribe' to receive a copy of all further events
+#[derive(Clone, Debug)]
+pub struct TkEventSender {
+    sender: Sender<TkConnectionEvent>,
+    listeners: broadcast::Sender<TkConnectionEvent>,
+    emitted: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl TkEventSender {
+    pub fn new(sender: Sender<TkConnectionEvent>) -> Self {
+        TkEventSender {
+            sender,
+            listeners: broadcast::channel(64).0,
+            emitted: Arc::new(AtomicUsize::new(0)),
+            dropped: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn send(&self, evt: TkConnectionEvent) -> Result<(), SendError<TkConnectionEvent>> {
+        if self.listeners.receiver_count() > 0 {
+            let _ = self.listeners.send(evt.clone());
+        }
+        let result = self.sender.try_send(evt).map_err(|err| SendError(err.into_inner()));
+        match result {
+            Ok(_) => self.emitted.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.dropped.fetch_add(1, Ordering::Relaxed),
         };
+        result
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TkConnectionEvent> {
+        self.listeners.subscribe()
+    }
+
+    pub fn emitted(&self) -> usize {
+        self.emitted.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
     }
 }
 
-fn try_send_event(sender: &Sender<TkConnectionEvent>, evt: TkConnectionEvent) {
+pub fn try_send_event(sender: &TkEventSender, evt: TkConnectionEvent) {
     sender
-        .try_send(evt)
+        .send(evt)
         .unwrap_or_else(|_| error!("event sender full"));
 }
 
+/// Sends a 'Heartbeat' with the time since the first call every 'interval', never returns
+pub async fn send_heartbeats(interval: Duration, sender: TkEventSender) {
+    let started = Instant::now();
+    let mut interval = tokio::time::interval(interval);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        try_send_event(&sender, TkConnectionEvent::Heartbeat(started.elapsed()));
+    }
+}
+
 impl Display for Task {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Task::Scalar(speed) => write!(f, "Constant({}%)", speed),
+            Task::Rotate(speed, clockwise) => write!(
+                f,
+                "Rotate({}%, {})",
+                speed,
+                if *clockwise { "CW" } else { "CCW" }
+            ),
+            Task::ScalarRamped(speed, fade_in, fade_out) => write!(
+                f,
+                "Ramped({}%, {:.1}s, {:.1}s)",
+                speed,
+                fade_in.as_secs_f32(),
+                fade_out.as_secs_f32()
+            ),
+            Task::ScalarRandom(min, max, change) => write!(
+                f,
+                "Random({}%-{}%, {:.1}s)",
+                min,
+                max,
+                change.as_secs_f32()
+            ),
             Task::Pattern(speed, actuator, pattern) => {
                 write!(f, "Pattern({}, {}, {})", speed, actuator, pattern)
             }
+            Task::MultiPattern(speed, channels) => write!(
+                f,
+                "MultiPattern({}, {})",
+                speed,
+                channels
+                    .iter()
+                    .map(|(actuator, pattern)| format!("{}: {}", actuator, pattern))
+                    .join(", ")
+            ),
             Task::Linear(speed, pattern) => write!(f, "Linear({}, {})", speed, pattern),
             Task::LinearOscillate(speed, _) => write!(f, "Stroke({})", speed),
+            Task::LinearStroke(pos, ms) => write!(f, "Move({:.2}, {}ms)", pos, ms),
         }
     }
 }
@@ -1,6 +1,7 @@
-use std::{sync::Arc, time::Duration};
+use std::{fmt, sync::Arc, time::Duration};
 
-use bp_scheduler::{actuator::Actuator, settings::ActuatorSettings};
+use anyhow::bail;
+use bp_scheduler::actuator::Actuator;
 use buttplug::core::message::ActuatorType;
 use cxx::{CxxString, CxxVector};
 use tracing::{debug, error};
@@ -46,6 +47,7 @@ pub fn read_scalar_actuator(actuator: &str) -> ActuatorType {
         "constrict" => ActuatorType::Constrict,
         "inflate" => ActuatorType::Inflate,
         "oscillate" => ActuatorType::Oscillate,
+        "rotate" => ActuatorType::Rotate,
         "vibrate" => ActuatorType::Vibrate,
         _ => {
             error!("unknown actuator {:?}", lower);
@@ -54,6 +56,120 @@ pub fn read_scalar_actuator(actuator: &str) -> ActuatorType {
     }
 }
 
+/// Reads 'actuator:pattern' pairs like 'vibrate:Tease,rotate:Wave',
+/// 'position' selects linear actuators
+pub fn read_pattern_channels(input: &str) -> Vec<(ActuatorType, String)> {
+    input
+        .split(',')
+        .filter_map(|x| x.split_once(':'))
+        .map(|(actuator, pattern)| {
+            let actuator = match actuator.trim().to_ascii_lowercase().as_str() {
+                "position" => ActuatorType::Position,
+                other => read_scalar_actuator(other),
+            };
+            (actuator, String::from(pattern.trim()))
+        })
+        .filter(|x| !x.1.is_empty())
+        .collect()
+}
+
+/// Selects connected devices by their name
+#[derive(Debug, Clone)]
+pub enum TkDeviceSelector {
+    /// Exact names, ignoring case and surrounding whitespace
+    Names(Vec<String>),
+    /// Glob on the name, see 'NameGlob'
+    NamePattern(String),
+}
+
+impl fmt::Display for TkDeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TkDeviceSelector::Names(names) => write!(f, "{}", names.join(",")),
+            TkDeviceSelector::NamePattern(pattern) => write!(f, "{}", pattern),
+        }
+    }
+}
+
+/// Case insensitive glob, '*' matches any text, '?' a single character
+/// and '[abc]' one of the listed characters
+#[derive(Debug, Clone)]
+pub struct NameGlob {
+    tokens: Vec<GlobToken>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum GlobToken {
+    Any,
+    One,
+    Class(Vec<char>),
+    Char(char),
+}
+
+impl NameGlob {
+    /// Fails for empty patterns and unclosed or empty '[]'
+    pub fn compile(pattern: &str) -> Result<NameGlob, anyhow::Error> {
+        let pattern = pattern.trim().to_lowercase();
+        if pattern.is_empty() {
+            bail!("empty name pattern");
+        }
+        let mut tokens = vec![];
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '*' if tokens.last() == Some(&GlobToken::Any) => continue,
+                '*' => GlobToken::Any,
+                '?' => GlobToken::One,
+                '[' => {
+                    let mut class = vec![];
+                    let mut closed = false;
+                    for x in chars.by_ref() {
+                        if x == ']' {
+                            closed = true;
+                            break;
+                        }
+                        class.push(x);
+                    }
+                    if !closed {
+                        bail!("unclosed '[' in name pattern '{}'", pattern);
+                    }
+                    if class.is_empty() {
+                        bail!("empty '[]' in name pattern '{}'", pattern);
+                    }
+                    GlobToken::Class(class)
+                }
+                c => GlobToken::Char(c),
+            };
+            tokens.push(token);
+        }
+        Ok(NameGlob { tokens })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.trim().to_lowercase().chars().collect();
+        matches_from(&self.tokens, &name)
+    }
+}
+
+fn matches_from(tokens: &[GlobToken], name: &[char]) -> bool {
+    match tokens.split_first() {
+        None => name.is_empty(),
+        Some((GlobToken::Any, rest)) => (0..=name.len()).any(|i| matches_from(rest, &name[i..])),
+        Some((token, rest)) => match name.split_first() {
+            Some((c, name)) => {
+                let matched = match token {
+                    GlobToken::One => true,
+                    GlobToken::Class(class) => class.contains(c),
+                    GlobToken::Char(x) => x == c,
+                    GlobToken::Any => unreachable!(),
+                };
+                matched && matches_from(rest, name)
+            }
+            None => false,
+        },
+    }
+}
+
 pub struct TkParams {}
 
 impl TkParams {
@@ -64,15 +180,7 @@ impl TkParams {
         device_settings: &[TkDeviceSettings]
         ) -> Vec<Arc<Actuator>> {
         let body_parts = sanitize_name_list(input_body_parts);
-        let selected_settings = device_settings.iter().filter( |setting| { 
-            if ! setting.enabled {
-                return false;
-            }
-            if body_parts.is_empty() {
-                return true;
-            }
-            setting.events.iter().any( |y| body_parts.contains(y) )
-        }).cloned().collect::<Vec<TkDeviceSettings>>();
+        let selected_settings = device_settings.iter().filter( |setting| setting.responds_to(&body_parts) ).cloned().collect::<Vec<TkDeviceSettings>>();
 
         let selected = selected_settings.iter().map(|x| x.actuator_id.clone()).collect::<Vec<String>>();
         
@@ -83,11 +191,35 @@ impl TkParams {
                 .cloned()
                 .collect::<Vec<Arc<Actuator>>>();
 
-        let actuator_settings = used.iter().map(|x| device_settings.iter().find( |y| y.actuator_id == x.identifier() ) );
-
         debug!("connected: {:?}", actuators.iter().map( |x| x.identifier() ).collect::<Vec<&str>>());
         debug!(?used);
         used
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::NameGlob;
+
+    #[test]
+    fn glob_matches_names() {
+        let glob = NameGlob::compile("Lovense*").unwrap();
+        assert!(glob.matches("Lovense Hush"));
+        assert!(glob.matches("lovense"));
+        assert!(!glob.matches("The Lovense"));
+
+        let glob = NameGlob::compile("*h?sh #[12]").unwrap();
+        assert!(glob.matches("Lovense Hush #2"));
+        assert!(!glob.matches("Lovense Hush #3"));
+        assert!(!glob.matches("Lovense Hush"));
+    }
+
+    #[test]
+    fn glob_rejects_invalid_patterns() {
+        assert!(NameGlob::compile(" ").is_err());
+        assert!(NameGlob::compile("Lovense [12").is_err());
+        assert!(NameGlob::compile("Lovense []").is_err());
+        assert!(NameGlob::compile("[12] [3").is_err());
+    }
+}
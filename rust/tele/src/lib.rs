@@ -6,14 +6,25 @@ use bp_scheduler::{
 use buttplug::core::message::ActuatorType;
 use connection::{Task, TkConnectionEvent};
 use ffi::SKSEModEvent;
-use input::{get_duration_from_secs, read_scalar_actuator};
+use input::{get_duration_from_secs, read_pattern_channels, read_scalar_actuator};
 use itertools::Itertools;
-use pattern::{get_pattern_names, read_pattern};
-use std::sync::{Arc, Mutex};
-use tracing::{debug, info, instrument};
+use pattern::get_pattern_names;
+use serde_json::{json, Value};
+use std::{
+    ffi::{c_char, CStr, CString},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use crossbeam_channel::RecvTimeoutError;
+use tracing::{debug, error, instrument};
 
 use cxx::{CxxString, CxxVector};
-use telekinesis::{Telekinesis, ERROR_HANDLE};
+use telekinesis::{Telekinesis, TkResult, ERROR_HANDLE};
 
 use crate::{
     input::{parse_csv, read_input_string},
@@ -25,14 +36,63 @@ mod connection;
 mod input;
 mod logging;
 mod pattern;
-mod settings;
+pub mod settings;
 mod status;
 pub mod telekinesis;
 mod util;
 
-#[derive(Debug)]
+type EventCallback = Arc<dyn Fn(&SKSEModEvent) + Send + Sync>;
+
 pub struct TkApi {
     pub state: Arc<Mutex<Option<Telekinesis>>>,
+    event_forwarder: Option<EventForwarder>,
+    /// Kept to attach it again when 'connect' creates a new event receiver
+    event_callback: Option<EventCallback>,
+    /// Why the last 'connect' failed, see 'tk_last_error'
+    last_error: Option<String>,
+}
+
+/// Thread that passes all events to a callback until stopped or disconnected
+#[derive(Debug)]
+struct EventForwarder {
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl EventForwarder {
+    fn start<F>(
+        connection_events: crossbeam_channel::Receiver<TkConnectionEvent>,
+        callback: F,
+    ) -> Self
+    where
+        F: Fn(&SKSEModEvent) + Send + 'static,
+    {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_clone = stopped.clone();
+        let thread = thread::spawn(move || {
+            while !stopped_clone.load(Ordering::SeqCst) {
+                match connection_events.recv_timeout(Duration::from_millis(100)) {
+                    Ok(evt) => callback(&to_mod_event(evt)),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            debug!("event forwarder stopped");
+        });
+        EventForwarder {
+            stopped,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for EventForwarder {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap_or_else(|_| error!("event forwarder panicked"));
+        }
+    }
 }
 
 /// Methods exposed as papyrus native functions
@@ -40,6 +100,8 @@ pub struct TkApi {
 ///    functionality and the (rather tedious) `Plugin.cxx <-> Cxx <-> RustFFI` Sandwich
 ///    basically, I don't want to change 5 method signatures whenever one of those methods changes
 /// - All ffi methods except  are non-blocking, triggering an async action somewhere in the future
+/// - Speeds are always passed as i32 percentages (`tk_control` arg0, `tk_update` arg1)
+///   and clamped to 0-100, there is no 0.0-1.0 float variant
 /// - All error conditions during the function call (i.e. mutex not available) will
 ///   be swallowed and logged to Telekinesis.log
 #[cxx::bridge]
@@ -73,8 +135,10 @@ mod ffi {
         ) -> i32;
         fn tk_update(&mut self, arg0: i32, arg1: i32) -> bool;
         fn tk_stop(&mut self, arg0: i32) -> bool;
+        fn tk_last_error(&mut self) -> String;
         // blocking
         fn tk_qry_nxt_evt(&mut self) -> Vec<SKSEModEvent>;
+        fn tk_qry_nxt_evt_json(&mut self) -> Vec<String>;
     }
 }
 
@@ -99,6 +163,9 @@ impl SKSEModEvent {
 fn tk_new() -> Box<TkApi> {
     Box::new(TkApi {
         state: Arc::new(Mutex::new(None)),
+        event_forwarder: None,
+        event_callback: None,
+        last_error: None,
     })
 }
 
@@ -110,6 +177,9 @@ impl Api<Telekinesis> for TkApi {
     fn fns(&self) -> ApiBuilder<Telekinesis> {
         build_api()
     }
+    fn set_last_error(&mut self, err: Option<String>) {
+        self.last_error = err;
+    }
     fn destroy(&mut self) -> ApiCmd0<Telekinesis> {
         ApiCmd0 {
             name: "disconnect",
@@ -124,7 +194,14 @@ impl Api<Telekinesis> for TkApi {
 impl TkApi {
     #[instrument(skip(self))]
     fn tk_cmd(&mut self, cmd: &str) -> bool {
-        self.exec_cmd_0(cmd)
+        if cmd == self.destroy().name {
+            self.event_forwarder.take();
+        }
+        let executed = self.exec_cmd_0(cmd);
+        if executed && cmd == self.fns().init.name {
+            self.attach_event_callback();
+        }
+        executed
     }
 
     #[instrument(skip(self))]
@@ -189,19 +266,23 @@ impl TkApi {
         self.exec_stop(arg0)
     }
 
+    /// Error of the last failed 'connect', empty if it succeeded
+    fn tk_last_error(&mut self) -> String {
+        self.last_error.clone().unwrap_or_default()
+    }
+
     /// Return type Vec cause cxx crate does not support Option
     /// and Result enforces try catch with some weird template
-    /// I don't wanna get into
+    /// I don't wanna get into. Returns nothing while an event callback is set
     fn tk_qry_nxt_evt(&mut self) -> Vec<SKSEModEvent> {
-        let tele = &self.state();
-        let mut receiver = None;
-        if let Ok(mut guard) = tele.lock() {
-            if let Some(tk) = guard.take() {
-                let evt_receiver = tk.connection_events.clone();
-                guard.replace(tk);
-                receiver = Some(evt_receiver);
-            }
+        if self.event_forwarder.is_some() {
+            return vec![];
         }
+        // only the receiver is cloned, so the state is not locked while blocking
+        let receiver = match self.state().lock() {
+            Ok(guard) => guard.as_ref().map(|tk| tk.connection_events.clone()),
+            Err(_) => None,
+        };
         match receiver {
             Some(receiver) => {
                 if let Some(evt) = get_next_events_blocking(&receiver) {
@@ -213,55 +294,323 @@ impl TkApi {
         }
     }
 
-    #[instrument]
-    fn tk_destroy(&mut self) {
-        self.destroy();
+    /// Like 'tk_qry_nxt_evt' but the event is a json object with a 'kind'
+    /// and the typed fields of the event, see 'to_json_event'
+    fn tk_qry_nxt_evt_json(&mut self) -> Vec<String> {
+        if self.event_forwarder.is_some() {
+            return vec![];
+        }
+        let receiver = match self.state().lock() {
+            Ok(guard) => guard.as_ref().map(|tk| tk.connection_events.clone()),
+            Err(_) => None,
+        };
+        match receiver.and_then(|receiver| get_next_json_events_blocking(&receiver)) {
+            Some(evt) => vec![evt.to_string()],
+            None => vec![],
+        }
+    }
+
+    /// Replaces any previous callback, 'callback' is invoked from a background thread
+    /// and must not call back into TkApi. Events are no longer returned by tk_qry_nxt_evt.
+    /// The callback stays attached when connecting again
+    pub fn set_event_callback<F>(&mut self, callback: F) -> bool
+    where
+        F: Fn(&SKSEModEvent) + Send + Sync + 'static,
+    {
+        self.event_callback = Some(Arc::new(callback));
+        if !self.attach_event_callback() {
+            error!("cannot set event callback before connecting");
+            self.event_callback = None;
+            return false;
+        }
+        true
+    }
+
+    /// Blocks until the callback is no longer invoked
+    pub fn clear_event_callback(&mut self) {
+        self.event_callback.take();
+        self.event_forwarder.take();
+    }
+
+    /// Forwards the events of the current connection to the callback, if one is set
+    fn attach_event_callback(&mut self) -> bool {
+        self.event_forwarder.take();
+        let Some(callback) = self.event_callback.clone() else {
+            return false;
+        };
+        let receiver = match self.state().lock() {
+            Ok(guard) => guard.as_ref().map(|tk| tk.connection_events.clone()),
+            Err(_) => None,
+        };
+        match receiver {
+            Some(receiver) => {
+                self.event_forwarder = Some(EventForwarder::start(receiver, move |evt| callback(evt)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs 'exec' and tells why it failed. The last result is reset first
+    /// because not every command reports one
+    fn exec_with_result<F>(&mut self, exec: F) -> TkResult
+    where
+        F: FnOnce(&mut TkApi) -> bool,
+    {
+        if let Ok(mut guard) = self.state.lock() {
+            if let Some(tk) = guard.as_mut() {
+                tk.last_result = TkResult::Ok;
+            }
+        }
+        let succeeded = exec(self);
+        match (succeeded, self.last_result()) {
+            (false, TkResult::Ok) => TkResult::Failed,
+            (_, result) => result,
+        }
+    }
+
+    fn last_result(&mut self) -> TkResult {
+        match self.state.lock() {
+            Ok(guard) => guard
+                .as_ref()
+                .map(|tk| tk.last_result)
+                .unwrap_or(TkResult::NotConnected),
+            Err(_) => TkResult::NotConnected,
+        }
+    }
+}
+
+/// # Safety
+/// 'ptr' must be null or a valid nul terminated string
+unsafe fn read_c_str<'a>(ptr: *const c_char) -> &'a str {
+    if ptr.is_null() {
+        return "";
     }
+    CStr::from_ptr(ptr).to_str().unwrap_or_default()
+}
+
+/// `TkResult tk_cmd_ex(TkApi& tk, const char* cmd)`, like 'tk_cmd' but tells why
+/// the command failed. The other '_ex' functions work the same
+///
+/// # Safety
+/// 'cmd' must be a valid nul terminated string
+#[no_mangle]
+pub unsafe extern "C" fn tk_cmd_ex(tk: &mut TkApi, cmd: *const c_char) -> TkResult {
+    let cmd = read_c_str(cmd);
+    tk.exec_with_result(|tk| tk.tk_cmd(cmd))
+}
+
+/// `TkResult tk_cmd_1_ex(TkApi& tk, const char* cmd, const char* arg0)`
+///
+/// # Safety
+/// All arguments must be valid nul terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn tk_cmd_1_ex(tk: &mut TkApi, cmd: *const c_char, arg0: *const c_char) -> TkResult {
+    let (cmd, arg0) = (read_c_str(cmd), read_c_str(arg0));
+    tk.exec_with_result(|tk| tk.tk_cmd_1(cmd, arg0))
+}
+
+/// `TkResult tk_cmd_2_ex(TkApi& tk, const char* cmd, const char* arg0, const char* arg1)`
+///
+/// # Safety
+/// All arguments must be valid nul terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn tk_cmd_2_ex(
+    tk: &mut TkApi,
+    cmd: *const c_char,
+    arg0: *const c_char,
+    arg1: *const c_char,
+) -> TkResult {
+    let (cmd, arg0, arg1) = (read_c_str(cmd), read_c_str(arg0), read_c_str(arg1));
+    tk.exec_with_result(|tk| tk.tk_cmd_2(cmd, arg0, arg1))
+}
+
+/// `TkResult tk_update_ex(TkApi& tk, int handle, int speed)`
+#[no_mangle]
+pub extern "C" fn tk_update_ex(tk: &mut TkApi, handle: i32, speed: i32) -> TkResult {
+    tk.exec_with_result(|tk| tk.tk_update(handle, speed))
+}
+
+/// `TkResult tk_stop_ex(TkApi& tk, int handle)`
+#[no_mangle]
+pub extern "C" fn tk_stop_ex(tk: &mut TkApi, handle: i32) -> TkResult {
+    tk.exec_with_result(|tk| tk.tk_stop(handle))
+}
+
+/// `TkResult tk_last_result(TkApi& tk)`, why the last command failed. Used for
+/// 'tk_control' which already returns the task handle
+#[no_mangle]
+pub extern "C" fn tk_last_result(tk: &mut TkApi) -> TkResult {
+    tk.last_result()
+}
+
+/// cxx does not support passing function pointers to rust, so this is exported as plain C:
+/// `bool tk_set_event_callback(TkApi& tk, void (*callback)(const char*, const char*, double))`
+#[no_mangle]
+pub extern "C" fn tk_set_event_callback(
+    tk: &mut TkApi,
+    callback: extern "C" fn(event_name: *const c_char, str_arg: *const c_char, num_arg: f64),
+) -> bool {
+    tk.set_event_callback(move |evt| {
+        let event_name = CString::new(evt.event_name.as_str()).unwrap_or_default();
+        let str_arg = CString::new(evt.str_arg.as_str()).unwrap_or_default();
+        callback(event_name.as_ptr(), str_arg.as_ptr(), evt.num_arg);
+    })
+}
+
+/// `void tk_clear_event_callback(TkApi& tk)`
+#[no_mangle]
+pub extern "C" fn tk_clear_event_callback(tk: &mut TkApi) {
+    tk.clear_event_callback();
 }
 
 pub fn get_next_events_blocking(
     connection_events: &crossbeam_channel::Receiver<TkConnectionEvent>,
 ) -> Option<SKSEModEvent> {
     if let Ok(result) = connection_events.recv() {
-        let event = match result {
-            TkConnectionEvent::Connected(connector) => {
-                SKSEModEvent::from("Tele_Connected", &connector)
-            }
-            TkConnectionEvent::ConnectionFailure(err) => {
-                SKSEModEvent::from("Tele_ConnectionError", &err)
-            }
-            TkConnectionEvent::DeviceAdded(device) => {
-                SKSEModEvent::from("Tele_DeviceAdded", device.name())
-            }
-            TkConnectionEvent::DeviceRemoved(device) => {
-                SKSEModEvent::from("Tele_DeviceRemoved", device.name())
-            }
-            TkConnectionEvent::ActionStarted(task, actuators, tags, handle) => {
-                let str_arg = format!(
-                    "{}{} on ({})",
-                    task,
-                    if !tags.is_empty() {
-                        format!(" {}", tags.iter().join(","))
-                    } else {
-                        String::default()
-                    },
-                    actuators.iter().map(|x| x.identifier()).join(",")
-                );
-                SKSEModEvent::new("Tele_DeviceActionStarted", &str_arg, f64::from(handle))
-            }
-            TkConnectionEvent::ActionDone(task, duration, handle) => {
-                let str_arg = format!("{} done after {:.1}s", task, duration.as_secs());
-                SKSEModEvent::new("Tele_DeviceActionDone", &str_arg, f64::from(handle))
-            }
-            TkConnectionEvent::ActionError(_, err) => {
-                SKSEModEvent::new("Tele_DeviceError", &err, 0.0)
-            }
-        };
-        return Some(event);
+        return Some(to_mod_event(result));
     }
     None
 }
 
+pub fn get_next_json_events_blocking(
+    connection_events: &crossbeam_channel::Receiver<TkConnectionEvent>,
+) -> Option<Value> {
+    connection_events.recv().ok().map(to_json_event)
+}
+
+/// Typed alternative to 'to_mod_event', i.e. {"kind":"DeviceAdded","device":"Lovense Hush"}
+pub fn to_json_event(event: TkConnectionEvent) -> Value {
+    match event {
+        TkConnectionEvent::Connected(connector) => json!({ "kind": "Connected", "connection": connector }),
+        TkConnectionEvent::ConnectionFailure(err) => json!({ "kind": "ConnectionFailure", "reason": err }),
+        TkConnectionEvent::Disconnected => json!({ "kind": "Disconnected" }),
+        TkConnectionEvent::DeviceAdded(device) => json!({ "kind": "DeviceAdded", "device": device.name() }),
+        TkConnectionEvent::DeviceRemoved(device) => {
+            json!({ "kind": "DeviceRemoved", "device": device.name() })
+        }
+        TkConnectionEvent::ActionStarted(task, actuators, tags, handle) => json!({
+            "kind": "ActionStarted",
+            "handle": handle,
+            "task": task.to_string(),
+            "actuators": actuators.iter().map(|x| x.identifier()).collect::<Vec<_>>(),
+            "tags": tags,
+        }),
+        TkConnectionEvent::ActionDone(task, duration, handle) => json!({
+            "kind": "ActionDone",
+            "handle": handle,
+            "task": task.to_string(),
+            "duration_secs": duration.as_secs_f64(),
+        }),
+        TkConnectionEvent::HandleFinished(handle) => json!({ "kind": "HandleFinished", "handle": handle }),
+        TkConnectionEvent::ActionError(actuator, err) => json!({
+            "kind": "ActionError",
+            "device": actuator.device.name(),
+            "actuator": actuator.identifier(),
+            "reason": err,
+        }),
+        TkConnectionEvent::ActuatorError(actuator, err) => json!({
+            "kind": "ActuatorError",
+            "device": actuator.device.name(),
+            "actuator": actuator.identifier(),
+            "reason": err,
+        }),
+        TkConnectionEvent::Reconnecting(attempt) => json!({ "kind": "Reconnecting", "attempt": attempt }),
+        TkConnectionEvent::CommandRejected(handle) => json!({ "kind": "CommandRejected", "handle": handle }),
+        TkConnectionEvent::CommandDropped(dropped) => json!({ "kind": "CommandDropped", "dropped": dropped }),
+        TkConnectionEvent::PatternError(pattern, err) => {
+            json!({ "kind": "PatternError", "pattern": pattern, "reason": err })
+        }
+        TkConnectionEvent::SelectorError(selector, err) => {
+            json!({ "kind": "SelectorError", "selector": selector, "reason": err })
+        }
+        TkConnectionEvent::SensorReading(device, value) => {
+            json!({ "kind": "SensorReading", "device": device, "value": value })
+        }
+        TkConnectionEvent::SensorError(device, err) => {
+            json!({ "kind": "SensorError", "device": device, "reason": err })
+        }
+        TkConnectionEvent::Heartbeat(uptime) => {
+            json!({ "kind": "Heartbeat", "uptime_secs": uptime.as_secs_f64() })
+        }
+        TkConnectionEvent::ScanStarted => json!({ "kind": "ScanStarted" }),
+        TkConnectionEvent::ScanStopped => json!({ "kind": "ScanStopped" }),
+        TkConnectionEvent::ScanFailed(err) => json!({ "kind": "ScanFailed", "reason": err }),
+    }
+}
+
+fn to_mod_event(event: TkConnectionEvent) -> SKSEModEvent {
+    match event {
+        TkConnectionEvent::Connected(connector) => {
+            SKSEModEvent::from("Tele_Connected", &connector)
+        }
+        TkConnectionEvent::ConnectionFailure(err) => {
+            SKSEModEvent::from("Tele_ConnectionError", &err)
+        }
+        TkConnectionEvent::DeviceAdded(device) => {
+            SKSEModEvent::from("Tele_DeviceAdded", device.name())
+        }
+        TkConnectionEvent::DeviceRemoved(device) => {
+            SKSEModEvent::from("Tele_DeviceRemoved", device.name())
+        }
+        TkConnectionEvent::ActionStarted(task, actuators, tags, handle) => {
+            let str_arg = format!(
+                "{}{} on ({})",
+                task,
+                if !tags.is_empty() {
+                    format!(" {}", tags.iter().join(","))
+                } else {
+                    String::default()
+                },
+                actuators.iter().map(|x| x.identifier()).join(",")
+            );
+            SKSEModEvent::new("Tele_DeviceActionStarted", &str_arg, f64::from(handle))
+        }
+        TkConnectionEvent::ActionDone(task, duration, handle) => {
+            let str_arg = format!("{} done after {:.1}s", task, duration.as_secs());
+            SKSEModEvent::new("Tele_DeviceActionDone", &str_arg, f64::from(handle))
+        }
+        TkConnectionEvent::HandleFinished(handle) => {
+            SKSEModEvent::new("Tele_HandleFinished", "", f64::from(handle))
+        }
+        TkConnectionEvent::ActionError(_, err) => {
+            SKSEModEvent::new("Tele_DeviceError", &err, 0.0)
+        }
+        TkConnectionEvent::ActuatorError(actuator, err) => SKSEModEvent::from(
+            "Tele_ActuatorError",
+            &format!("{}: {}", actuator.device.name(), err),
+        ),
+        TkConnectionEvent::Reconnecting(attempt) => {
+            SKSEModEvent::new("Tele_Reconnecting", "", f64::from(attempt))
+        }
+        TkConnectionEvent::CommandRejected(handle) => {
+            SKSEModEvent::new("Tele_CommandRejected", "", f64::from(handle))
+        }
+        TkConnectionEvent::CommandDropped(dropped) => {
+            SKSEModEvent::new("Tele_CommandDropped", "", dropped as f64)
+        }
+        TkConnectionEvent::PatternError(pattern, err) => {
+            SKSEModEvent::from("Tele_PatternError", &format!("{}: {}", pattern, err))
+        }
+        TkConnectionEvent::SelectorError(selector, err) => {
+            SKSEModEvent::from("Tele_SelectorError", &format!("{}: {}", selector, err))
+        }
+        TkConnectionEvent::SensorReading(device, value) => {
+            SKSEModEvent::new("Tele_SensorReading", &device, f64::from(value))
+        }
+        TkConnectionEvent::SensorError(device, err) => {
+            SKSEModEvent::from("Tele_SensorError", &format!("{}: {}", device, err))
+        }
+        TkConnectionEvent::Heartbeat(uptime) => {
+            SKSEModEvent::new("Tele_Heartbeat", "", uptime.as_secs_f64())
+        }
+        TkConnectionEvent::Disconnected => SKSEModEvent::from("Tele_Disconnected", ""),
+        TkConnectionEvent::ScanStarted => SKSEModEvent::from("Tele_ScanStarted", ""),
+        TkConnectionEvent::ScanStopped => SKSEModEvent::from("Tele_ScanStopped", ""),
+        TkConnectionEvent::ScanFailed(err) => SKSEModEvent::from("Tele_ScanFailed", &err),
+    }
+}
+
 pub fn build_api() -> ApiBuilder<Telekinesis> {
     ApiBuilder::new(ApiInit {
         name: "connect",
@@ -276,7 +625,7 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
     .def_cmd(ApiCmd0 {
         name: "connection.inprocess",
         exec: |tk| {
-            tk.settings.connection = TkConnectionType::InProcess;
+            tk.settings.connection = TkConnectionType::in_process();
             true
         },
     })
@@ -287,6 +636,44 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
             true
         },
     })
+    .def_cmd1(ApiCmd1 {
+        name: "connection.test",
+        exec: |tk, device_names| {
+            tk.settings.connection = TkConnectionType::Test;
+            tk.settings.test_devices = parse_csv(device_names);
+            true
+        },
+    })
+    .def_qry_lst_1(ApiQryList1 {
+        name: "debug.test_calls",
+        exec: |tk, device_index| match (&tk.test_calls, device_index.parse()) {
+            (Some(test_calls), Ok(device_index)) => test_calls
+                .get_device(device_index)
+                .iter()
+                .map(|x| format!("{:?}", x.message))
+                .collect(),
+            _ => vec![],
+        },
+    })
+    .def_qry_bool(ApiQryBool {
+        name: "scanning",
+        exec: |tk| tk.status.is_scanning(),
+    })
+    .def_qry_str(ApiQryStr {
+        name: "status.json",
+        default: "{}",
+        exec: |tk| serde_json::to_string(&tk.status.get_snapshot()).unwrap_or_default(),
+    })
+    .def_qry_str(ApiQryStr {
+        name: "devices.json",
+        default: "[]",
+        exec: |tk| serde_json::to_string(&tk.get_devices_snapshot()).unwrap_or_default(),
+    })
+    .def_qry_str(ApiQryStr {
+        name: "metrics.json",
+        default: "{}",
+        exec: |tk| serde_json::to_string(&tk.get_metrics()).unwrap_or_default(),
+    })
     .def_qry_str(ApiQryStr {
         name: "connection.status",
         default: "Not Connected",
@@ -305,12 +692,10 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
     .def_control(ApiControl {
         name: "vibrate",
         exec: |tk, speed, time_sec, _, events| {
-            tk.scalar(
-                Task::Scalar(Speed::new(speed.into())),
+            tk.vibrate(
+                Speed::new(speed.into()),
                 get_duration_from_secs(time_sec),
                 read_input_string(events),
-                None,
-                &[ActuatorType::Vibrate],
             )
         },
         default: ERROR_HANDLE,
@@ -329,31 +714,261 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
         default: ERROR_HANDLE,
     })
     .def_control(ApiControl {
-        name: "vibrate.pattern",
-        exec: |tk, speed, time_sec, pattern_name, events| match read_pattern(
-            &tk.settings.pattern_path,
-            pattern_name,
-            true,
-        ) {
-            Some(fscript) => tk.scalar(
-                Task::Pattern(
+        name: "vibrate.ramped",
+        exec: |tk, speed, time_sec, fades, events| {
+            let fades = parse_csv(fades);
+            let fade_secs = |i: usize| -> f32 {
+                fades.get(i).and_then(|x| x.parse().ok()).unwrap_or(0.0)
+            };
+            tk.scalar(
+                Task::ScalarRamped(
                     Speed::new(speed.into()),
-                    ActuatorType::Vibrate,
-                    pattern_name.into(),
+                    Duration::from_secs_f32(fade_secs(0).max(0.0)),
+                    Duration::from_secs_f32(fade_secs(1).max(0.0)),
                 ),
                 get_duration_from_secs(time_sec),
                 read_input_string(events),
-                Some(fscript),
+                None,
                 &[ActuatorType::Vibrate],
-            ),
-            None => ERROR_HANDLE,
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.random",
+        exec: |tk, speed, time_sec, args, events| {
+            // args: '<min speed>,<secs between changes>'
+            let args = parse_csv(args);
+            let arg = |i: usize, default: f32| -> f32 {
+                args.get(i).and_then(|x| x.parse().ok()).unwrap_or(default)
+            };
+            tk.scalar(
+                Task::ScalarRandom(
+                    Speed::new(arg(0, 0.0) as i64),
+                    Speed::new(speed.into()),
+                    Duration::from_secs_f32(arg(1, 1.0).max(0.0)),
+                ),
+                get_duration_from_secs(time_sec),
+                read_input_string(events),
+                None,
+                &[ActuatorType::Vibrate],
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "trigger_event",
+        exec: |tk, event| !tk.trigger_event(event).is_empty(),
+    })
+    .def_control(ApiControl {
+        name: "vibrate.actuator",
+        exec: |tk, speed, time_sec, actuator_id, _| {
+            tk.vibrate_actuator(
+                actuator_id,
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.delayed",
+        exec: |tk, speed, time_sec, start_delay_sec, events| {
+            tk.vibrate_delayed(
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+                Duration::from_secs_f32(start_delay_sec.parse().unwrap_or(0.0_f32).max(0.0)),
+                read_input_string(events),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "pattern.multi",
+        exec: |tk, speed, time_sec, channels, events| {
+            tk.multi_pattern(
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+                read_pattern_channels(channels),
+                read_input_string(events),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.grouped",
+        exec: |tk, speed, time_sec, group, events| {
+            tk.vibrate_grouped(
+                group,
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+                read_input_string(events),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.until",
+        exec: |tk, speed, _, stop_event, events| {
+            tk.vibrate_until(stop_event, Speed::new(speed.into()), read_input_string(events))
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.matching",
+        exec: |tk, speed, time_sec, name_pattern, _| {
+            tk.vibrate_matching(name_pattern, Speed::new(speed.into()), get_duration_from_secs(time_sec))
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.devices",
+        exec: |tk, speed, time_sec, device_names, _| {
+            tk.vibrate_devices(
+                parse_csv(device_names),
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "rotate",
+        exec: |tk, speed, time_sec, _, events| {
+            tk.scalar(
+                Task::Scalar(Speed::new(speed.into())),
+                get_duration_from_secs(time_sec),
+                read_input_string(events),
+                None,
+                &[ActuatorType::Rotate],
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "rotate.directional",
+        exec: |tk, speed, time_sec, direction, events| {
+            tk.rotate_directional(
+                Speed::new(speed.into()),
+                !direction.trim().eq_ignore_ascii_case("ccw"),
+                get_duration_from_secs(time_sec),
+                read_input_string(events),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_cmd2(ApiCmd2 {
+        name: "rotate.direction",
+        exec: |tk, handle, direction| match handle.parse() {
+            Ok(handle) => tk.set_direction(handle, !direction.trim().eq_ignore_ascii_case("ccw")),
+            Err(_) => false,
+        },
+    })
+    .def_control(ApiControl {
+        name: "oscillate",
+        exec: |tk, speed, time_sec, _, events| {
+            tk.scalar(
+                Task::Scalar(Speed::new(speed.into())),
+                get_duration_from_secs(time_sec),
+                read_input_string(events),
+                None,
+                &[ActuatorType::Oscillate],
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "constrict",
+        exec: |tk, speed, time_sec, _, events| {
+            tk.scalar(
+                Task::Scalar(Speed::new(speed.into())),
+                get_duration_from_secs(time_sec),
+                read_input_string(events),
+                None,
+                &[ActuatorType::Constrict],
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "inflate",
+        exec: |tk, speed, time_sec, _, events| {
+            tk.scalar(
+                Task::Scalar(Speed::new(speed.into())),
+                get_duration_from_secs(time_sec),
+                read_input_string(events),
+                None,
+                &[ActuatorType::Inflate],
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.pattern",
+        exec: |tk, speed, time_sec, pattern_name, events| {
+            tk.vibrate_pattern(
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+                None,
+                pattern_name,
+                1.0,
+                read_input_string(events),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.pattern.inline",
+        exec: |tk, speed, time_sec, json, events| {
+            tk.vibrate_pattern_inline(
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+                json,
+                read_input_string(events),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.pattern.from",
+        exec: |tk, speed, time_sec, pattern, events| {
+            // pattern is given as '<directory>/<name>' relative to the pattern path
+            let path = Path::new(pattern);
+            let pattern_dir = path.parent().and_then(|x| x.to_str()).unwrap_or_default();
+            let pattern_name = path.file_name().and_then(|x| x.to_str()).unwrap_or_default();
+            tk.vibrate_pattern(
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+                Some(pattern_dir),
+                pattern_name,
+                1.0,
+                read_input_string(events),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_control(ApiControl {
+        name: "vibrate.pattern.scaled",
+        exec: |tk, speed, time_sec, args, events| {
+            // args are given as '<name>,<speed_scale>'
+            let args = parse_csv(args);
+            let pattern_name = args.first().map(|x| x.as_str()).unwrap_or_default();
+            let speed_scale = args.get(1).and_then(|x| x.parse().ok()).unwrap_or(1.0);
+            tk.vibrate_pattern(
+                Speed::new(speed.into()),
+                get_duration_from_secs(time_sec),
+                None,
+                pattern_name,
+                speed_scale,
+                read_input_string(events),
+            )
         },
         default: ERROR_HANDLE,
     })
     .def_control(ApiControl {
         name: "linear.pattern",
-        exec: |tk, speed, time_sec, pattern_name, events| match read_pattern(
-            &tk.settings.pattern_path,
+        exec: |tk, speed, time_sec, pattern_name, events| match tk.load_pattern(
+            None,
             pattern_name,
             false,
         ) {
@@ -379,26 +994,157 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
         },
         default: ERROR_HANDLE,
     })
+    .def_control(ApiControl {
+        name: "linear.stroke",
+        exec: |tk, position, time_sec, _, events| {
+            tk.linear_stroke(
+                Speed::new(position.into()).as_float(),
+                (time_sec.max(0.0) * 1000.0) as u32,
+                read_input_string(events),
+            )
+        },
+        default: ERROR_HANDLE,
+    })
+    .def_on_cmd(|tk| tk.record_activity())
     .def_update(ApiUpdate {
         exec: |tk, handle, speed| tk.update(handle, Speed::new(speed.into())),
     })
     .def_stop(ApiStop {
         exec: Telekinesis::stop,
     })
+    .def_cmd1(ApiCmd1 {
+        name: "stop_event",
+        exec: Telekinesis::stop_event,
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "stop_group",
+        exec: Telekinesis::stop_group,
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "trigger_stop_event",
+        exec: Telekinesis::trigger_stop_event,
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "stop_actuator_type",
+        exec: |tk, actuator_type| tk.stop_actuator_type(read_scalar_actuator(actuator_type)),
+    })
     .def_cmd(ApiCmd0 {
         name: "stop_all",
         exec: Telekinesis::stop_all,
     })
+    .def_cmd(ApiCmd0 {
+        name: "pause_all",
+        exec: Telekinesis::pause_all,
+    })
+    .def_cmd(ApiCmd0 {
+        name: "resume_all",
+        exec: Telekinesis::resume_all,
+    })
+    .def_qry_str1(ApiQryStr1 {
+        name: "handle.remaining",
+        default: "",
+        exec: |tk, handle| {
+            // seconds left, 'inf' for tasks without duration and empty if the task ended
+            match handle.parse().ok().and_then(|handle| tk.get_handle_remaining(handle)) {
+                Some(Duration::MAX) => f64::INFINITY.to_string(),
+                Some(remaining) => format!("{:.1}", remaining.as_secs_f64()),
+                None => String::default(),
+            }
+        },
+    })
+    .def_qry_bool(ApiQryBool {
+        name: "events.pending",
+        exec: |tk| tk.events_pending(),
+    })
+    .def_qry_str(ApiQryStr {
+        name: "commands.dropped",
+        default: "0",
+        exec: |tk| tk.dropped_commands().to_string(),
+    })
+    .def_qry_str(ApiQryStr {
+        name: "last_result",
+        default: "NotConnected",
+        exec: |tk| tk.last_result.to_string(),
+    })
     // settings
     .def_cmd(ApiCmd0 {
         name: "settings.store",
         exec: |tk| tk.settings.try_write(SETTINGS_PATH, SETTINGS_FILE),
     })
+    .def_qry_str(ApiQryStr {
+        name: "settings.global_factor",
+        default: "",
+        exec: |tk| tk.settings.global_factor.to_string(),
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "settings.global_factor",
+        exec: |tk, factor| match factor.parse() {
+            Ok(factor) => {
+                tk.settings.set_global_factor(factor);
+                true
+            }
+            Err(_) => false,
+        },
+    })
     // devices settings
     .def_qry_lst(ApiQryList {
         name: "devices",
         exec: |tk| tk.status.get_known_actuator_ids(),
     })
+    .def_qry_lst(ApiQryList {
+        name: "devices.actuators",
+        exec: |tk| {
+            tk.get_actuators()
+                .iter()
+                .map(|x| String::from(x.identifier()))
+                .collect()
+        },
+    })
+    .def_qry_lst(ApiQryList {
+        name: "devices.names",
+        exec: |tk| tk.status.get_device_names(),
+    })
+    .def_cmd2(ApiCmd2 {
+        name: "device.link",
+        exec: Telekinesis::link_devices,
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "device.unlink",
+        exec: |tk, follower| {
+            tk.settings.unlink_device(follower);
+            true
+        },
+    })
+    .def_qry_lst_1(ApiQryList1 {
+        name: "device.followers",
+        exec: |tk, device_name| tk.settings.get_followers(device_name),
+    })
+    .def_qry_str1(ApiQryStr1 {
+        name: "device.index",
+        default: "",
+        exec: |tk, device_name| {
+            tk.status
+                .get_device_index(device_name)
+                .map(|index| index.to_string())
+                .unwrap_or_default()
+        },
+    })
+    .def_qry_str1(ApiQryStr1 {
+        name: "device.name_by_index",
+        default: "",
+        exec: |tk, index| {
+            index
+                .trim()
+                .parse()
+                .ok()
+                .and_then(|index| tk.status.get_device_name(index))
+                .unwrap_or_default()
+        },
+    })
+    .def_qry_lst_1(ApiQryList1 {
+        name: "device.capabilities",
+        exec: |tk, device_name| tk.status.get_device_capabilities(device_name),
+    })
     .def_qry_str1(ApiQryStr1 {
         name: "device.actuator",
         default: "Not Connected",
@@ -438,6 +1184,37 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
             "1".into()
         },
     })
+    .def_qry_str1(ApiQryStr1 {
+        name: "device.battery",
+        default: "-1",
+        exec: |tk, actuator_id| {
+            if let Some(actuator) = tk.status.get_actuator(actuator_id) {
+                if let Some(level) = tk.read_device_battery(&actuator.device) {
+                    return level.to_string();
+                }
+            }
+            "-1".into()
+        },
+    })
+    .def_qry_str1(ApiQryStr1 {
+        name: "device.rssi",
+        default: "",
+        exec: |tk, device_name| {
+            // dBm of the device, empty if it is unavailable since 0 is a valid reading
+            match tk.get_device_rssi(device_name) {
+                Some(level) => level.to_string(),
+                None => String::new(),
+            }
+        },
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "device.sensor.subscribe",
+        exec: Telekinesis::subscribe_sensor,
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "device.sensor.unsubscribe",
+        exec: Telekinesis::unsubscribe_sensor,
+    })
     .def_cmd1(ApiCmd1 {
         name: "device.settings.enable",
         exec: |tk, actuator_id| {
@@ -452,6 +1229,24 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
             true
         },
     })
+    .def_cmd1(ApiCmd1 {
+        name: "device.settings.reset",
+        exec: |tk, device_name| {
+            tk.settings.reset_device(device_name);
+            true
+        },
+    })
+    .def_cmd(ApiCmd0 {
+        name: "device.settings.prune",
+        exec: |tk| {
+            tk.settings_prune_disconnected();
+            true
+        },
+    })
+    .def_qry_lst(ApiQryList {
+        name: "device.settings.prune.dry_run",
+        exec: Telekinesis::settings_prune_disconnected_dry_run,
+    })
     .def_qry_bool_1(ApiQryBool1 {
         name: "device.settings.enabled",
         exec: |tk, actuator_id| tk.settings.get_enabled(actuator_id),
@@ -467,6 +1262,53 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
         name: "device.settings.events",
         exec: |tk, actuator_id| tk.settings.get_events(actuator_id),
     })
+    .def_cmd2(ApiCmd2 {
+        name: "device.settings.event_pattern",
+        exec: |tk, actuator_id, event_pattern| {
+            // '<event>,<pattern>', an empty pattern removes the mapping
+            let (event, pattern) = event_pattern.split_once(',').unwrap_or((event_pattern, ""));
+            tk.settings.set_event_pattern(actuator_id, event, pattern);
+            true
+        },
+    })
+    .def_qry_lst_1(ApiQryList1 {
+        name: "device.settings.event_patterns",
+        exec: |tk, actuator_id| {
+            tk.settings
+                .get_or_create(actuator_id)
+                .event_patterns
+                .iter()
+                .map(|(event, pattern)| format!("{},{}", event, pattern))
+                .collect()
+        },
+    })
+    .def_cmd2(ApiCmd2 {
+        name: "device.settings.event_cap",
+        exec: |tk, actuator_id, event_cap| {
+            // '<event>,<percent>', an empty cap removes it
+            let (event, cap) = event_cap.split_once(',').unwrap_or((event_cap, ""));
+            let cap = match cap.trim() {
+                "" => None,
+                cap => match cap.parse() {
+                    Ok(cap) => Some(cap),
+                    Err(_) => return false,
+                },
+            };
+            tk.settings.set_event_cap(actuator_id, event, cap);
+            true
+        },
+    })
+    .def_qry_lst_1(ApiQryList1 {
+        name: "device.settings.event_caps",
+        exec: |tk, actuator_id| {
+            tk.settings
+                .get_or_create(actuator_id)
+                .event_caps
+                .iter()
+                .map(|(event, cap)| format!("{},{}", event, cap))
+                .collect()
+        },
+    })
     .def_qry_str1(ApiQryStr1 {
         name: "device.scalar.min_speed",
         default: "",
@@ -614,6 +1456,15 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
         exec: |tk, actuator_id| tk.status.get_actuator_status(actuator_id).to_string(),
     })
     // patterns
+    .def_qry_str(ApiQryStr {
+        name: "patterns.path",
+        default: "",
+        exec: |tk| tk.get_pattern_path().to_owned(),
+    })
+    .def_cmd1(ApiCmd1 {
+        name: "patterns.path",
+        exec: Telekinesis::set_pattern_path,
+    })
     .def_qry_lst(ApiQryList {
         name: "patterns.vibrator",
         exec: |tk| get_pattern_names(&tk.settings.pattern_path, true),
@@ -622,4 +1473,35 @@ pub fn build_api() -> ApiBuilder<Telekinesis> {
         name: "patterns.stroker",
         exec: |tk| get_pattern_names(&tk.settings.pattern_path, false),
     })
+    .def_qry_str1(ApiQryStr1 {
+        name: "pattern.vibrator.duration",
+        default: "-1",
+        exec: |tk, pattern_name| match tk.patterns.get_duration(
+            &tk.settings.pattern_path,
+            pattern_name,
+            true,
+        ) {
+            Some(duration) => duration.as_secs_f32().to_string(),
+            None => "-1".into(),
+        },
+    })
+    .def_qry_str1(ApiQryStr1 {
+        name: "pattern.stroker.duration",
+        default: "-1",
+        exec: |tk, pattern_name| match tk.patterns.get_duration(
+            &tk.settings.pattern_path,
+            pattern_name,
+            false,
+        ) {
+            Some(duration) => duration.as_secs_f32().to_string(),
+            None => "-1".into(),
+        },
+    })
+    .def_cmd(ApiCmd0 {
+        name: "patterns.reload",
+        exec: |tk| {
+            tk.patterns.clear();
+            true
+        },
+    })
 }
@@ -1,17 +1,24 @@
 use std::{
     fs::File,
-    sync::Mutex,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
 };
 
 
-use tracing::{Level, info};
+use tracing::{Level, Subscriber, info};
 
-use crate::settings::{TkSettings, SETTINGS_FILE, SETTINGS_PATH};
+use crate::settings::{TkLogLevel, TkSettings, SETTINGS_FILE, SETTINGS_PATH};
+
+static FILE_LOGGING: AtomicBool = AtomicBool::new(false);
 
 #[cxx::bridge]
 mod ffi {
     extern "Rust" {
         fn tk_init_logging(logPath: String) -> bool;
+        fn tk_init_logging_level(logPath: String, level: i32) -> bool;
         fn tk_init_logging_stdout() -> bool;
         fn tk_log_info(message: String);
     }
@@ -37,23 +44,90 @@ pub fn tk_init_logging_stdout() -> bool {
 
 pub fn tk_init_logging(file_path: String) -> bool {
     let settings = TkSettings::try_read_or_default(SETTINGS_PATH, SETTINGS_FILE);
-    let file = match File::create(file_path) {
-        Ok(file) => file,
+    init_logging(&file_path, settings.log_level)
+}
+
+/// 'level' goes from 0 (Trace) to 4 (Error), anything else uses Debug
+pub fn tk_init_logging_level(file_path: String, level: i32) -> bool {
+    let log_level = match level {
+        0 => TkLogLevel::Trace,
+        2 => TkLogLevel::Info,
+        3 => TkLogLevel::Warn,
+        4 => TkLogLevel::Error,
+        _ => TkLogLevel::Debug,
+    };
+    init_logging(&file_path, log_level)
+}
+
+/// Writes all logs with at least 'level' to 'file_path'. Only the first
+/// successful call registers the logger, later calls are ignored
+pub fn init_logging(file_path: &str, level: TkLogLevel) -> bool {
+    if FILE_LOGGING
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        info!(file_path, "logging already initialized");
+        return true;
+    }
+    let subscriber = match file_subscriber(file_path, level) {
+        Ok(subscriber) => subscriber,
         Err(err) => {
             eprintln!("Couldn't write to log file, no logs available: {:?}", err);
+            FILE_LOGGING.store(false, Ordering::SeqCst);
             return false;
         }
     };
-    let log_lvl : Level = settings.log_level.into();
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(log_lvl)
-        .with_ansi(false)
-        .with_writer(Mutex::new(file))
-        .with_thread_ids(true)
-        .finish();
     if tracing::subscriber::set_global_default(subscriber).is_err() {
         eprintln!("Setting global tracing subscriber failed.");
+        FILE_LOGGING.store(false, Ordering::SeqCst);
         return false;
     }
     true
 }
+
+fn file_subscriber(file_path: &str, level: TkLogLevel) -> Result<impl Subscriber, io::Error> {
+    let file = File::create(file_path)?;
+    let log_lvl : Level = level.into();
+    Ok(tracing_subscriber::fmt()
+        .with_max_level(log_lvl)
+        .with_ansi(false)
+        .with_writer(Mutex::new(file))
+        .with_thread_ids(true)
+        .finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use tracing::{debug, info};
+
+    use crate::settings::TkLogLevel;
+
+    use super::{file_subscriber, init_logging, FILE_LOGGING};
+
+    #[test]
+    fn file_subscriber_writes_to_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log_file = tmp_dir.path().join("Telekinesis.log");
+
+        let subscriber = file_subscriber(log_file.to_str().unwrap(), TkLogLevel::Info).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            info!("logging test message");
+            debug!("filtered test message");
+        });
+
+        let content = std::fs::read_to_string(log_file).unwrap();
+        assert!(content.contains("logging test message"));
+        assert!(!content.contains("filtered test message"));
+    }
+
+    #[test]
+    fn init_logging_with_invalid_path_can_be_retried() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log_file = tmp_dir.path().join("missing").join("Telekinesis.log");
+
+        assert!(!init_logging(log_file.to_str().unwrap(), TkLogLevel::Info));
+        assert!(!FILE_LOGGING.load(Ordering::SeqCst));
+    }
+}
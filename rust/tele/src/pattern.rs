@@ -1,7 +1,8 @@
-use std::{path::PathBuf, time::Instant, fs};
+use std::{collections::HashMap, path::{Path, PathBuf}, time::{Duration, Instant}, fs};
 use anyhow::anyhow;
-use funscript::FScript;
-use tracing::{error, debug};
+use itertools::Itertools;
+use funscript::{FSPoint, FScript};
+use tracing::{error, debug, warn};
 
 pub fn get_pattern_names(pattern_path: &str, vibration_patterns: bool) -> Vec<String> {
     match get_pattern_paths(pattern_path) {
@@ -9,6 +10,7 @@ pub fn get_pattern_names(pattern_path: &str, vibration_patterns: bool) -> Vec<St
             .iter()
             .filter(|p| p.is_vibration == vibration_patterns)
             .map(|p| p.name.clone())
+            .sorted()
             .collect::<Vec<String>>(),
         Err(err) => {
             error!("Failed reading patterns {}", err);
@@ -20,6 +22,7 @@ pub fn get_pattern_names(pattern_path: &str, vibration_patterns: bool) -> Vec<St
 struct TkPatternFile {
     path: PathBuf,
     is_vibration: bool,
+    is_csv: bool,
     name: String,
 }
 
@@ -36,20 +39,26 @@ fn get_pattern_paths(pattern_path: &str) -> Result<Vec<TkPatternFile>, anyhow::E
             .ok_or_else(|| anyhow!("No file name"))?
             .to_str()
             .ok_or_else(|| anyhow!("Invalid unicode"))?;
-        if !file_name.to_lowercase().ends_with(".funscript") {
+        let lower_name = file_name.to_lowercase();
+        let extension = if lower_name.ends_with(".funscript") {
+            ".funscript"
+        } else if lower_name.ends_with(".csv") {
+            ".csv"
+        } else {
             continue;
-        }
+        };
 
-        let is_vibration = file_name.to_lowercase().ends_with(".vibrator.funscript");
+        let is_vibration = lower_name.ends_with(&format!(".vibrator{}", extension));
         let removal: usize = if is_vibration {
-            file_name.len() - ".vibrator.funscript".len()
+            file_name.len() - ".vibrator".len() - extension.len()
         } else {
-            file_name.len() - ".funscript".len()
+            file_name.len() - extension.len()
         };
 
         patterns.push(TkPatternFile {
             path: path_clone,
             is_vibration,
+            is_csv: extension == ".csv",
             name: String::from(&file_name[0..removal]),
         })
     }
@@ -60,17 +69,13 @@ pub fn read_pattern(
     pattern_path: &str,
     pattern_name: &str,
     vibration_pattern: bool,
-) -> Option<FScript> {
-    match read_pattern_name(pattern_path, pattern_name, vibration_pattern) {
-        Ok(funscript) => Some(funscript),
-        Err(err) => {
-            error!(
-                "Error loading funscript vibration pattern={} err={}",
-                pattern_name, err
-            );
-            None
-        }
-    }
+) -> Result<FScript, anyhow::Error> {
+    read_pattern_name(pattern_path, pattern_name, vibration_pattern).inspect_err(|err| {
+        error!(
+            "Error loading funscript vibration pattern={} err={}",
+            pattern_name, err
+        )
+    })
 }
 
 pub fn read_pattern_name(
@@ -88,7 +93,361 @@ pub fn read_pattern_name(
         })
         .ok_or_else(|| anyhow!("Pattern '{}' not found", pattern_name))?;
 
-    let fs = funscript::load_funscript(pattern.path.to_str().unwrap())?;
+    let fs = if pattern.is_csv {
+        load_csv_pattern(&pattern.path)?
+    } else {
+        funscript::load_funscript(pattern.path.to_str().unwrap())?
+    };
     debug!("Read pattern {} in {:?}", pattern_name, now.elapsed());
-    Ok(fs)
+    Ok(sanitize_pattern(fs, pattern_name))
+}
+
+/// Reads a pattern from funscript json instead of a file
+pub fn parse_pattern(json: &str, pattern_name: &str) -> Result<FScript, anyhow::Error> {
+    let fs: FScript = serde_json::from_str(json)?;
+    if fs.actions.is_empty() {
+        return Err(anyhow!("Pattern has no actions"));
+    }
+    Ok(sanitize_pattern(fs, pattern_name))
+}
+
+/// Reads a pattern with one 'time_ms,strength' row per line, empty lines
+/// and lines starting with '#' are ignored and malformed rows are skipped
+fn load_csv_pattern(path: &Path) -> Result<FScript, anyhow::Error> {
+    let content = fs::read_to_string(path)?;
+    let mut actions = vec![];
+    for (line_nr, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let point = line
+            .split_once(',')
+            .and_then(|(at, pos)| Some((at.trim().parse().ok()?, pos.trim().parse().ok()?)));
+        match point {
+            Some((at, pos)) => actions.push(FSPoint { at, pos }),
+            None => warn!("Skipping malformed row {} in {:?}: '{}'", line_nr + 1, path, line),
+        }
+    }
+    Ok(FScript {
+        actions,
+        ..Default::default()
+    })
+}
+
+/// Repairs common mistakes of funscripts found in the wild: points are sorted
+/// by time, of points with the same time only the last one is kept and
+/// positions are clamped to 0-100
+fn sanitize_pattern(mut fscript: FScript, pattern_name: &str) -> FScript {
+    let unsorted = fscript
+        .actions
+        .iter()
+        .tuple_windows()
+        .filter(|(a, b)| b.at < a.at)
+        .count();
+    fscript.actions.sort_by_key(|point| point.at);
+
+    let mut actions: Vec<FSPoint> = Vec::with_capacity(fscript.actions.len());
+    let mut duplicates = 0;
+    for point in fscript.actions {
+        match actions.last_mut() {
+            Some(last) if last.at == point.at => {
+                *last = point;
+                duplicates += 1;
+            }
+            _ => actions.push(point),
+        }
+    }
+
+    let mut clamped = 0;
+    for point in actions.iter_mut() {
+        if !(0..=100).contains(&point.pos) {
+            point.pos = point.pos.clamp(0, 100);
+            clamped += 1;
+        }
+    }
+    fscript.actions = actions;
+
+    if unsorted + duplicates + clamped > 0 {
+        warn!(unsorted, duplicates, clamped, "Repaired malformed pattern {}", pattern_name);
+    }
+    fscript
+}
+
+/// Divides all timestamps by 'speed_scale', i.e. 2.0 plays twice as fast
+/// and 0.5 half as fast. Scales that are not positive are ignored
+pub fn scale_pattern_speed(mut fscript: FScript, speed_scale: f64) -> FScript {
+    if speed_scale.is_nan() || speed_scale <= 0.0 || speed_scale == 1.0 {
+        return fscript;
+    }
+    for point in fscript.actions.iter_mut() {
+        point.at = (f64::from(point.at) / speed_scale).round() as i32;
+    }
+    fscript
+}
+
+/// Resolves 'pattern_dir' relative to 'pattern_path', directories
+/// outside of 'pattern_path' are rejected
+pub fn resolve_pattern_dir(pattern_path: &str, pattern_dir: &str) -> Option<PathBuf> {
+    let root = fs::canonicalize(pattern_path).ok()?;
+    let dir = fs::canonicalize(Path::new(pattern_path).join(pattern_dir)).ok()?;
+    if !dir.starts_with(&root) {
+        error!("Pattern dir {:?} is outside of {:?}", pattern_dir, pattern_path);
+        return None;
+    }
+    Some(dir)
+}
+
+/// Keeps patterns in memory after they have been read once
+#[derive(Default)]
+pub struct PatternCache {
+    patterns: HashMap<(String, String, bool), FScript>,
+}
+
+impl PatternCache {
+    /// Returns a copy of the cached pattern, only the actions are copied
+    pub fn get(
+        &mut self,
+        pattern_path: &str,
+        pattern_name: &str,
+        vibration_pattern: bool,
+    ) -> Option<FScript> {
+        self.try_get(pattern_path, pattern_name, vibration_pattern).ok()
+    }
+
+    /// Like 'get' but returns why the pattern could not be read
+    pub fn try_get(
+        &mut self,
+        pattern_path: &str,
+        pattern_name: &str,
+        vibration_pattern: bool,
+    ) -> Result<FScript, anyhow::Error> {
+        self.load(pattern_path, pattern_name, vibration_pattern)
+            .map(|cached| FScript {
+                actions: cached
+                    .actions
+                    .iter()
+                    .map(|x| FSPoint { pos: x.pos, at: x.at })
+                    .collect(),
+                ..Default::default()
+            })
+    }
+
+    /// Looks up the pattern in 'pattern_dir' first and falls back to 'pattern_path'
+    pub fn get_from(
+        &mut self,
+        pattern_path: &str,
+        pattern_dir: &str,
+        pattern_name: &str,
+        vibration_pattern: bool,
+    ) -> Result<FScript, anyhow::Error> {
+        match resolve_pattern_dir(pattern_path, pattern_dir)
+            .and_then(|dir| self.get(dir.to_str()?, pattern_name, vibration_pattern))
+        {
+            Some(fscript) => Ok(fscript),
+            None => self.try_get(pattern_path, pattern_name, vibration_pattern),
+        }
+    }
+
+    /// Time of the last action, i.e. the length of a single playback
+    pub fn get_duration(
+        &mut self,
+        pattern_path: &str,
+        pattern_name: &str,
+        vibration_pattern: bool,
+    ) -> Option<Duration> {
+        self.load(pattern_path, pattern_name, vibration_pattern)
+            .ok()?
+            .actions
+            .iter()
+            .map(|x| x.at)
+            .max()
+            .map(|at| Duration::from_millis(at.max(0) as u64))
+    }
+
+    fn load(
+        &mut self,
+        pattern_path: &str,
+        pattern_name: &str,
+        vibration_pattern: bool,
+    ) -> Result<&FScript, anyhow::Error> {
+        let key = (
+            pattern_path.to_owned(),
+            pattern_name.to_lowercase(),
+            vibration_pattern,
+        );
+        if !self.patterns.contains_key(&key) {
+            let fscript = read_pattern(pattern_path, pattern_name, vibration_pattern)?;
+            self.patterns.insert(key.clone(), fscript);
+        }
+        Ok(&self.patterns[&key])
+    }
+
+    pub fn clear(&mut self) {
+        self.patterns.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PATTERN_PATH: &str = "../../contrib/Distribution/SKSE/Plugins/Telekinesis/Patterns";
+
+    #[test]
+    fn parse_pattern_rejects_invalid_json_and_empty_patterns() {
+        let fs = parse_pattern(r#"{"actions":[{"at":100,"pos":50},{"at":0,"pos":300}]}"#, "p").unwrap();
+        assert_eq!(fs.actions[0].pos, 100);
+        assert_eq!(fs.actions[1].at, 100);
+        assert!(parse_pattern(r#"{"actions":[{"at":0,"#, "p").is_err());
+        assert!(parse_pattern(r#"{"actions":[]}"#, "p").is_err());
+    }
+
+    #[test]
+    fn vibration_pattern_names_are_sorted() {
+        let names = get_pattern_names(PATTERN_PATH, true);
+        assert!(names.contains(&String::from("02_Cruel-Tease")));
+        assert!(!names.contains(&String::from("60_Blowjob")));
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn linear_pattern_names() {
+        let names = get_pattern_names(PATTERN_PATH, false);
+        assert!(names.contains(&String::from("60_Blowjob")));
+        assert!(!names.contains(&String::from("02_Cruel-Tease")));
+    }
+
+    #[test]
+    fn cached_pattern_is_read_only_once() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let file = tmp_dir.path().join("Test.vibrator.funscript");
+        fs::copy(
+            [PATTERN_PATH, "02_Cruel-Tease.vibrator.funscript"].iter().collect::<PathBuf>(),
+            &file,
+        )
+        .unwrap();
+        let path = tmp_dir.path().to_str().unwrap();
+
+        let mut cache = PatternCache::default();
+        let first = cache.get(path, "test", true).unwrap();
+        fs::remove_file(&file).unwrap();
+        let second = cache.get(path, "Test", true).unwrap();
+
+        assert!(!first.actions.is_empty());
+        assert_eq!(first.actions.len(), second.actions.len());
+        cache.clear();
+        assert!(cache.get(path, "test", true).is_none());
+    }
+
+    #[test]
+    fn unsorted_pattern_is_sorted_and_deduplicated() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            tmp_dir.path().join("Unsorted.vibrator.funscript"),
+            r#"{"actions":[{"at":200,"pos":20},{"at":0,"pos":0},{"at":100,"pos":10},{"at":100,"pos":15}]}"#,
+        )
+        .unwrap();
+
+        let fscript = read_pattern(tmp_dir.path().to_str().unwrap(), "Unsorted", true).unwrap();
+
+        let points: Vec<(i32, i32)> = fscript.actions.iter().map(|x| (x.at, x.pos)).collect();
+        assert_eq!(points, vec![(0, 0), (100, 15), (200, 20)]);
+    }
+
+    #[test]
+    fn out_of_range_positions_are_clamped() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            tmp_dir.path().join("Loud.vibrator.funscript"),
+            r#"{"actions":[{"at":0,"pos":-20},{"at":100,"pos":250},{"at":200,"pos":50}]}"#,
+        )
+        .unwrap();
+
+        let fscript = read_pattern(tmp_dir.path().to_str().unwrap(), "Loud", true).unwrap();
+
+        let positions: Vec<i32> = fscript.actions.iter().map(|x| x.pos).collect();
+        assert_eq!(positions, vec![0, 100, 50]);
+    }
+
+    #[test]
+    fn csv_pattern_skips_malformed_rows() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            tmp_dir.path().join("Simple.vibrator.csv"),
+            "time_ms,strength\n0,10\n\n# comment\n100, 50\n150\n200,100\n",
+        )
+        .unwrap();
+        let path = tmp_dir.path().to_str().unwrap();
+
+        let fscript = read_pattern(path, "Simple", true).unwrap();
+
+        let points: Vec<(i32, i32)> = fscript.actions.iter().map(|x| (x.at, x.pos)).collect();
+        assert_eq!(points, vec![(0, 10), (100, 50), (200, 100)]);
+        assert_eq!(get_pattern_names(path, true), vec![String::from("Simple")]);
+        assert!(get_pattern_names(path, false).is_empty());
+    }
+
+    #[test]
+    fn pattern_duration_is_last_action() {
+        let fscript = read_pattern(PATTERN_PATH, "02_Cruel-Tease", true).unwrap();
+        let last = fscript.actions.last().unwrap().at;
+
+        let mut cache = PatternCache::default();
+        assert_eq!(
+            cache.get_duration(PATTERN_PATH, "02_Cruel-Tease", true),
+            Some(Duration::from_millis(last as u64))
+        );
+        assert_eq!(cache.get_duration(PATTERN_PATH, "does not exist", true), None);
+    }
+
+    #[test]
+    fn pattern_speed_scales_timestamps() {
+        let fscript = read_pattern(PATTERN_PATH, "02_Cruel-Tease", true).unwrap();
+        let faster = scale_pattern_speed(read_pattern(PATTERN_PATH, "02_Cruel-Tease", true).unwrap(), 2.0);
+        let slower = scale_pattern_speed(read_pattern(PATTERN_PATH, "02_Cruel-Tease", true).unwrap(), 0.5);
+        for (i, point) in fscript.actions.iter().enumerate() {
+            assert_eq!(faster.actions[i].pos, point.pos);
+            assert_eq!(faster.actions[i].at, (f64::from(point.at) / 2.0).round() as i32);
+            assert_eq!(slower.actions[i].at, point.at * 2);
+        }
+    }
+
+    #[test]
+    fn pattern_from_custom_dir() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let custom_dir = tmp_dir.path().join("MyMod");
+        fs::create_dir(&custom_dir).unwrap();
+        fs::copy(
+            [PATTERN_PATH, "02_Cruel-Tease.vibrator.funscript"].iter().collect::<PathBuf>(),
+            custom_dir.join("Custom.vibrator.funscript"),
+        )
+        .unwrap();
+        let root = tmp_dir.path().to_str().unwrap();
+
+        let mut cache = PatternCache::default();
+        assert!(cache.get(root, "Custom", true).is_none());
+        assert!(cache.get_from(root, "MyMod", "Custom", true).is_ok());
+        assert!(cache.get_from(PATTERN_PATH, "MyMod", "02_Cruel-Tease", true).is_ok());
+    }
+
+    #[test]
+    fn pattern_dir_outside_of_root_is_rejected() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let root = tmp_dir.path().join("Patterns");
+        fs::create_dir(&root).unwrap();
+        let root = root.to_str().unwrap();
+
+        assert!(resolve_pattern_dir(root, ".").is_some());
+        assert!(resolve_pattern_dir(root, "..").is_none());
+        assert!(resolve_pattern_dir(root, tmp_dir.path().to_str().unwrap()).is_none());
+        assert!(resolve_pattern_dir(root, "does_not_exist").is_none());
+    }
+
+    #[test]
+    fn missing_pattern_dir_returns_empty() {
+        assert!(get_pattern_names("does/not/exist", true).is_empty());
+    }
 }
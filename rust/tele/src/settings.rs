@@ -1,14 +1,18 @@
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display},
     fs::{self},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 use bp_scheduler::{actuator::Actuator, settings::{ActuatorSettings, LinearRange, LinearSpeedScaling, ScalarRange}};
 use buttplug::core::message::ActuatorType;
+use anyhow::bail;
 use itertools::Itertools;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 use tracing::{debug, error, event, info, instrument, Level};
+use url::Url;
 
 use crate::input::sanitize_name_list;
 
@@ -18,15 +22,68 @@ pub static SETTINGS_FILE: &str = "Telekinesis.v2.json";
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum TkConnectionType {
-    InProcess,
+    InProcess { managers: Vec<CommManagerKind> },
     WebSocket(String),
     Test,
 }
 
+/// Device communication managers of the in-process server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommManagerKind {
+    Btle,
+    Serial,
+    /// Lovense USB dongle in serial mode
+    LovenseDongle,
+    LovenseConnect,
+    /// Only available on windows
+    XInput,
+}
+
+impl TkConnectionType {
+    /// In-process server that only uses bluetooth
+    pub fn in_process() -> TkConnectionType {
+        TkConnectionType::InProcess {
+            managers: vec![CommManagerKind::Btle],
+        }
+    }
+}
+
+/// Endpoints without scheme use 'ws://', 'wss://' connects via TLS
+pub fn get_websocket_uri(endpoint: &str) -> Option<String> {
+    let endpoint = endpoint.trim();
+    let uri = if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+        endpoint.to_owned()
+    } else {
+        format!("ws://{}", endpoint)
+    };
+    match Url::parse(&uri) {
+        Ok(url) if url.host_str().is_some_and(|host| !host.is_empty()) => Some(uri),
+        _ => None,
+    }
+}
+
+/// Settings written before the managers could be chosen store a plain "InProcess"
+fn deserialize_connection<'de, D>(deserializer: D) -> Result<TkConnectionType, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredConnection {
+        Current(TkConnectionType),
+        Legacy(String),
+    }
+    match StoredConnection::deserialize(deserializer)? {
+        StoredConnection::Current(connection) => Ok(connection),
+        StoredConnection::Legacy(name) if name == "InProcess" => Ok(TkConnectionType::in_process()),
+        StoredConnection::Legacy(name) => Err(D::Error::custom(format!("unknown connection '{}'", name))),
+    }
+}
+
 impl Display for TkConnectionType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TkConnectionType::InProcess => write!(f, "In-Process"),
+            TkConnectionType::InProcess { .. } => write!(f, "In-Process"),
             TkConnectionType::WebSocket(host) => write!(f, "WebSocket {}", host),
             TkConnectionType::Test => write!(f, "Test"),
         }
@@ -58,12 +115,112 @@ impl From<TkLogLevel> for Level {
 pub struct TkSettings {
     pub version: u32,
     pub log_level: TkLogLevel,
+    #[serde(deserialize_with = "deserialize_connection")]
     pub connection: TkConnectionType,
     pub devices: Vec<TkDeviceSettings>,
+    #[serde(default)]
+    pub reconnect: TkReconnectSettings,
+    #[serde(default)]
+    pub task_limit: TkTaskLimitSettings,
+    /// Scanning is stopped automatically after this time
+    #[serde(default)]
+    pub scan_duration_ms: Option<u64>,
+    /// Names of the vibrators simulated by the test connection, uses demo devices if empty
+    #[serde(default)]
+    pub test_devices: Vec<String>,
+    /// Scales the speed of all scalar actuators after their own factor
+    #[serde(default = "default_global_factor")]
+    pub global_factor: f64,
+    /// Commands that don't fit into the queue are dropped
+    #[serde(default = "default_command_queue_size")]
+    pub command_queue_size: usize,
+    /// Speed updates that arrive faster than this are merged, see 'PlayerSettings'
+    #[serde(default = "default_update_coalesce_ms")]
+    pub update_coalesce_ms: i32,
+    /// Sends a heartbeat event in this interval until disconnected
+    #[serde(default)]
+    pub heartbeat_ms: Option<u64>,
+    /// Stops all devices and disconnects when no command was issued for this
+    /// long, the next command connects again
+    #[serde(default)]
+    pub idle_disconnect_secs: Option<u64>,
+    /// Stops all devices as soon as any device disconnects
+    #[serde(default)]
+    pub stop_all_on_disconnect: bool,
+    /// Fades between speeds when a new task takes over a running actuator, 0 switches instantly
+    #[serde(default)]
+    pub crossfade_ms: u64,
+    /// Minimum time between two commands to the same device, 0 sends every command right away
+    #[serde(default)]
+    pub command_rate_ms: u64,
+    /// Stopped actuators ramp down to zero over this time, 0 stops instantly
+    #[serde(default)]
+    pub fade_out_ms: u64,
+    /// Pattern steps that change the speed by less than this (in percent) are not
+    /// sent to the device, the last step of a pattern is always sent
+    #[serde(default)]
+    pub pattern_min_delta: u16,
+    /// Scalar commands that match no device within this time after connecting
+    /// wait for a matching device instead of doing nothing, 0 disables waiting
+    #[serde(default)]
+    pub device_ready_grace_ms: u64,
+    /// Lower case device names and the devices that mirror their commands
+    #[serde(default)]
+    pub device_links: BTreeMap<String, Vec<String>>,
     #[serde(skip)]
     pub pattern_path: String,
 }
 
+fn default_global_factor() -> f64 {
+    1.0
+}
+
+fn default_command_queue_size() -> usize {
+    256
+}
+
+fn default_update_coalesce_ms() -> i32 {
+    50
+}
+
+/// Retries after the websocket connection to the server was lost
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TkReconnectSettings {
+    pub backoff_ms: u64,
+    pub max_retries: u32,
+}
+
+impl Default for TkReconnectSettings {
+    fn default() -> Self {
+        Self {
+            backoff_ms: 5000,
+            max_retries: 10,
+        }
+    }
+}
+
+/// Maximum number of tasks running at the same time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TkTaskLimitSettings {
+    pub max_active: usize,
+    pub overflow: TkOverflowPolicy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TkOverflowPolicy {
+    RejectNew,
+    DropOldest,
+}
+
+impl Default for TkTaskLimitSettings {
+    fn default() -> Self {
+        Self {
+            max_active: 256,
+            overflow: TkOverflowPolicy::RejectNew,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TkDeviceSettings {
     pub actuator_id: String,
@@ -71,6 +228,12 @@ pub struct TkDeviceSettings {
     pub events: Vec<String>,
     #[serde(default = "ActuatorSettings::default")]
     pub actuator_settings: ActuatorSettings,
+    /// Vibration pattern played when the event is triggered
+    #[serde(default)]
+    pub event_patterns: BTreeMap<String, String>,
+    /// Highest speed in percent of tasks that were started for the event
+    #[serde(default)]
+    pub event_caps: BTreeMap<String, i64>,
 }
 
 impl TkDeviceSettings {
@@ -80,6 +243,8 @@ impl TkDeviceSettings {
             enabled: false,
             events: vec![],
             actuator_settings: ActuatorSettings::None,
+            event_patterns: BTreeMap::new(),
+            event_caps: BTreeMap::new(),
         }
     }
     pub fn from_actuator(actuator: &Actuator) -> TkDeviceSettings {
@@ -96,20 +261,61 @@ impl TkDeviceSettings {
                 ActuatorType::Position => ActuatorSettings::Linear(LinearRange::default()),
                 _ => ActuatorSettings::None,
             },
+            event_patterns: BTreeMap::new(),
+            event_caps: BTreeMap::new(),
         }
     }
+
+    /// Whether a task selecting the (sanitized) 'body_parts' uses this actuator,
+    /// an empty selection matches all enabled actuators
+    pub fn responds_to(&self, body_parts: &[String]) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if body_parts.is_empty() {
+            return true;
+        }
+        self.events.iter().any(|event| body_parts.contains(event))
+    }
+
+    pub fn belongs_to(&self, device_name: &str) -> bool {
+        self.actuator_id == device_name
+            || self
+                .actuator_id
+                .strip_prefix(device_name)
+                .is_some_and(|rest| rest.starts_with(" ("))
+    }
 }
 
-impl TkSettings {
-    pub fn default() -> Self {
+impl Default for TkSettings {
+    fn default() -> Self {
         TkSettings {
             version: 2,
             log_level: TkLogLevel::Debug,
-            connection: TkConnectionType::InProcess,
+            connection: TkConnectionType::in_process(),
             devices: vec![],
+            reconnect: TkReconnectSettings::default(),
+            task_limit: TkTaskLimitSettings::default(),
+            scan_duration_ms: None,
+            test_devices: vec![],
+            global_factor: default_global_factor(),
+            command_queue_size: default_command_queue_size(),
+            update_coalesce_ms: default_update_coalesce_ms(),
+            heartbeat_ms: None,
+            idle_disconnect_secs: None,
+            stop_all_on_disconnect: false,
+            crossfade_ms: 0,
+            command_rate_ms: 0,
+            fade_out_ms: 0,
+            pattern_min_delta: 0,
+            device_ready_grace_ms: 0,
+            device_links: BTreeMap::new(),
             pattern_path: String::from(DEFAULT_PATTERN_PATH),
         }
     }
+}
+
+impl TkSettings {
     pub fn try_read_or_default(settings_path: &str, settings_file: &str) -> Self {
         let path = [settings_path, settings_file].iter().collect::<PathBuf>();
         match fs::read_to_string(path) {
@@ -157,6 +363,53 @@ impl TkSettings {
         }
     }
 
+    /// Settings passed to the player, with the global factor applied to scalar actuators
+    pub fn get_actuator_settings(&mut self, actuator_id: &str) -> ActuatorSettings {
+        let global_factor = self.global_factor;
+        match self.get_or_create(actuator_id).actuator_settings {
+            ActuatorSettings::Scalar(scalar) => ActuatorSettings::Scalar(ScalarRange {
+                factor: scalar.factor * global_factor,
+                ..scalar
+            }),
+            ActuatorSettings::None if global_factor != 1.0 => {
+                ActuatorSettings::Scalar(ScalarRange {
+                    factor: global_factor,
+                    ..Default::default()
+                })
+            }
+            settings => settings,
+        }
+    }
+
+    pub fn set_global_factor(&mut self, factor: f64) {
+        self.global_factor = factor.max(0.0);
+    }
+
+    /// Like 'get_actuator_settings' but the speed is limited by the lowest
+    /// cap of the 'events' the task was started for
+    pub fn get_event_actuator_settings(&mut self, actuator_id: &str, events: &[String]) -> ActuatorSettings {
+        let events = sanitize_name_list(events);
+        let cap = self
+            .get_or_create(actuator_id)
+            .event_caps
+            .iter()
+            .filter(|(event, _)| events.contains(event))
+            .map(|(_, cap)| *cap)
+            .min();
+        match (self.get_actuator_settings(actuator_id), cap) {
+            (ActuatorSettings::Scalar(scalar), Some(cap)) => ActuatorSettings::Scalar(ScalarRange {
+                min_speed: scalar.min_speed.min(cap),
+                max_speed: scalar.max_speed.min(cap),
+                ..scalar
+            }),
+            (ActuatorSettings::None, Some(cap)) => ActuatorSettings::Scalar(ScalarRange {
+                max_speed: cap,
+                ..Default::default()
+            }),
+            (settings, _) => settings,
+        }
+    }
+
     pub fn try_get_actuator_settings(&mut self, actuator_id: &str) -> ActuatorSettings {
         if let Some(setting) = self.get_device(actuator_id) {
             return setting.actuator_settings;
@@ -252,9 +505,146 @@ impl TkSettings {
         self.get_or_create(actuator_id).events
     }
 
+    /// An empty pattern removes the mapping
+    pub fn set_event_pattern(&mut self, actuator_id: &str, event: &str, pattern: &str) {
+        let mut device = self.get_or_create(actuator_id);
+        let event = event.to_lowercase().trim().to_owned();
+        match pattern.trim() {
+            "" => device.event_patterns.remove(&event),
+            pattern => device.event_patterns.insert(event, pattern.to_owned()),
+        };
+        self.update_device(device);
+    }
+
+    pub fn get_event_pattern(&mut self, actuator_id: &str, event: &str) -> Option<String> {
+        self.get_or_create(actuator_id)
+            .event_patterns
+            .get(event.to_lowercase().trim())
+            .cloned()
+    }
+
+    /// Caps the speed while the actuator is used for 'event', None removes the cap
+    pub fn set_event_cap(&mut self, actuator_id: &str, event: &str, cap: Option<i64>) {
+        let mut device = self.get_or_create(actuator_id);
+        let event = event.to_lowercase().trim().to_owned();
+        match cap {
+            Some(cap) => device.event_caps.insert(event, cap.clamp(0, 100)),
+            None => device.event_caps.remove(&event),
+        };
+        self.update_device(device);
+    }
+
     pub fn get_enabled(&mut self, actuator_id: &str) -> bool {
         self.get_or_create(actuator_id).enabled
     }
+
+    /// Mirrors all commands to 'primary' onto 'follower', returns false
+    /// when linking a device to itself
+    pub fn link_devices(&mut self, primary: &str, follower: &str) -> bool {
+        let primary = primary.to_lowercase().trim().to_owned();
+        let follower = follower.to_lowercase().trim().to_owned();
+        if primary.is_empty() || follower.is_empty() || primary == follower {
+            return false;
+        }
+        let followers = self.device_links.entry(primary).or_default();
+        if !followers.contains(&follower) {
+            followers.push(follower);
+        }
+        true
+    }
+
+    /// Stops mirroring commands onto 'follower'
+    pub fn unlink_device(&mut self, follower: &str) {
+        let follower = follower.to_lowercase().trim().to_owned();
+        for followers in self.device_links.values_mut() {
+            followers.retain(|x| *x != follower);
+        }
+        self.device_links.retain(|_, followers| !followers.is_empty());
+    }
+
+    pub fn get_followers(&self, device_name: &str) -> Vec<String> {
+        self.device_links
+            .get(device_name.to_lowercase().trim())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Removes the settings of all actuators of the device, they
+    /// revert to the defaults (disabled, no events)
+    pub fn reset_device(&mut self, device_name: &str) {
+        self.devices.retain(|d| !d.belongs_to(device_name));
+    }
+
+    /// Actuator ids of all settings entries that are not in 'actuator_ids'
+    pub fn get_devices_not_in(&self, actuator_ids: &[String]) -> Vec<String> {
+        self.devices
+            .iter()
+            .filter(|d| !actuator_ids.contains(&d.actuator_id))
+            .map(|d| d.actuator_id.clone())
+            .collect()
+    }
+
+    pub fn remove_devices(&mut self, actuator_ids: &[String]) {
+        self.devices.retain(|d| !actuator_ids.contains(&d.actuator_id));
+    }
+
+    /// Whether a task selecting 'events' would use any actuator of the device,
+    /// follows the same rules as task selection
+    pub fn device_responds_to(&self, device_name: &str, events: &[String]) -> bool {
+        let events = sanitize_name_list(events);
+        self.devices
+            .iter()
+            .any(|d| d.belongs_to(device_name) && d.responds_to(&events))
+    }
+}
+
+/// Creates settings that are checked for an invalid connection or pattern path
+pub struct TkSettingsBuilder {
+    settings: TkSettings,
+}
+
+impl TkSettingsBuilder {
+    pub fn new() -> Self {
+        TkSettingsBuilder {
+            settings: TkSettings::default(),
+        }
+    }
+
+    pub fn pattern_path(mut self, pattern_path: &str) -> Self {
+        self.settings.pattern_path = String::from(pattern_path);
+        self
+    }
+
+    pub fn connection(mut self, connection: TkConnectionType) -> Self {
+        self.settings.connection = connection;
+        self
+    }
+
+    pub fn scan_duration(mut self, scan_duration: Duration) -> Self {
+        self.settings.scan_duration_ms = Some(scan_duration.as_millis() as u64);
+        self
+    }
+
+    /// Fails if the websocket endpoint is malformed or the pattern
+    /// path is neither absolute nor an existing directory
+    pub fn build(self) -> Result<TkSettings, anyhow::Error> {
+        if let TkConnectionType::WebSocket(endpoint) = &self.settings.connection {
+            if get_websocket_uri(endpoint).is_none() {
+                bail!("Invalid websocket endpoint '{}'", endpoint);
+            }
+        }
+        let pattern_path = Path::new(&self.settings.pattern_path);
+        if !pattern_path.is_absolute() && !pattern_path.is_dir() {
+            bail!("Pattern path '{}' not found", self.settings.pattern_path);
+        }
+        Ok(self.settings)
+    }
+}
+
+impl Default for TkSettingsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -263,6 +653,20 @@ mod tests {
     use tempfile::{tempdir, TempDir};
     use tokio_test::assert_ok;
 
+    #[test]
+    fn websocket_uri_uses_scheme_of_endpoint() {
+        assert_eq!(
+            get_websocket_uri("127.0.0.1:12345"),
+            Some(String::from("ws://127.0.0.1:12345"))
+        );
+        assert_eq!(
+            get_websocket_uri("wss://intiface.example:443"),
+            Some(String::from("wss://intiface.example:443"))
+        );
+        assert_eq!(get_websocket_uri(""), None);
+        assert_eq!(get_websocket_uri("bogus host"), None);
+    }
+
     #[test]
     fn serialize_deserialize_works() {
         // Arrange
@@ -387,6 +791,115 @@ mod tests {
         assert_ok!(tmpdir.close());
     }
 
+    #[test]
+    fn write_and_read_keeps_enabled_and_events() {
+        let mut settings = TkSettings::default();
+        settings.set_enabled("a", true);
+        settings.set_events("a", &[String::from("vaginal")]);
+        settings.set_enabled("b", false);
+        settings.set_events("b", &[String::from("anal"), String::from("nipple")]);
+
+        // act
+        let target_file = "roundtrip.json";
+        let (_, tmpdir) = create_temp_file(target_file, "");
+        assert!(settings.try_write(tmpdir.path().to_str().unwrap(), target_file));
+        let mut settings2 =
+            TkSettings::try_read_or_default(tmpdir.path().to_str().unwrap(), target_file);
+
+        // assert
+        assert!(settings2.get_enabled("a"));
+        assert!(!settings2.get_enabled("b"));
+        assert_eq!(settings2.get_events("a"), vec!["vaginal"]);
+        assert_eq!(settings2.get_events("b"), vec!["anal", "nipple"]);
+    }
+
+    #[test]
+    fn reset_device_reverts_to_defaults() {
+        let mut settings = TkSettings::default();
+        settings.set_enabled("vib1 (Vibrate)", true);
+        settings.set_events("vib1 (Vibrate)", &[String::from("vaginal")]);
+        settings.set_enabled("vib1 (Vibrate #1)", true);
+        settings.set_enabled("vib2 (Vibrate)", true);
+
+        // act
+        settings.reset_device("vib1");
+
+        // assert
+        assert!(!settings.get_enabled("vib1 (Vibrate)"));
+        assert!(settings.get_events("vib1 (Vibrate)").is_empty());
+        assert!(!settings.get_enabled("vib1 (Vibrate #1)"));
+        assert!(settings.get_enabled("vib2 (Vibrate)"));
+    }
+
+    #[test]
+    fn device_without_events_responds_to_all() {
+        let mut settings = TkSettings::default();
+        settings.set_enabled("vib1 (Vibrate)", true);
+
+        assert!(settings.device_responds_to("vib1", &[]));
+        assert!(!settings.device_responds_to("vib1", &[String::from("vaginal")]));
+        assert!(!settings.device_responds_to("vib2", &[]));
+    }
+
+    #[test]
+    fn device_with_events_responds_to_its_events() {
+        let mut settings = TkSettings::default();
+        settings.set_enabled("vib1 (Vibrate)", true);
+        settings.set_events("vib1 (Vibrate)", &[String::from("nipple")]);
+        settings.set_events("vib1 (Vibrate #1)", &[String::from("anal")]);
+
+        assert!(settings.device_responds_to("vib1", &[]));
+        assert!(settings.device_responds_to("vib1", &[String::from(" Nipple")]));
+        assert!(settings.device_responds_to("vib1", &[String::from("vaginal"), String::from("nipple")]));
+        assert!(!settings.device_responds_to("vib1", &[String::from("vaginal")]));
+        assert!(!settings.device_responds_to("vib1", &[String::from("anal")]));
+    }
+
+    #[test]
+    fn file_with_unknown_fields_is_parsed() {
+        let mut setting = TkSettings::default();
+        setting.set_enabled("a", true);
+        let mut json = serde_json::to_value(setting).unwrap();
+        json["no_longer_used"] = serde_json::Value::Bool(true);
+        json["devices"][0]["no_longer_used"] = serde_json::Value::Bool(true);
+
+        let file = "unknown_fields.json";
+        let (_, tmpdir) = create_temp_file(file, &json.to_string());
+        let mut settings = TkSettings::try_read_or_default(tmpdir.path().to_str().unwrap(), file);
+
+        assert!(settings.get_enabled("a"));
+    }
+
+    #[test]
+    fn file_with_legacy_in_process_connection_is_parsed() {
+        let mut setting = TkSettings::default();
+        setting.set_enabled("a", true);
+        let mut json = serde_json::to_value(setting).unwrap();
+        json["connection"] = serde_json::Value::String(String::from("InProcess"));
+
+        let file = "legacy_connection.json";
+        let (_, tmpdir) = create_temp_file(file, &json.to_string());
+        let mut settings = TkSettings::try_read_or_default(tmpdir.path().to_str().unwrap(), file);
+
+        assert!(settings.get_enabled("a"));
+        assert_eq!(settings.connection, TkConnectionType::in_process());
+    }
+
+    #[test]
+    fn in_process_managers_are_stored() {
+        let settings = TkSettings {
+            connection: TkConnectionType::InProcess {
+                managers: vec![CommManagerKind::Serial, CommManagerKind::LovenseConnect],
+            },
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&settings).unwrap();
+        let settings2: TkSettings = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(settings2.connection, settings.connection);
+    }
+
     #[test]
     fn set_valid_websocket_endpoint() {
         let mut settings = TkSettings::default();
@@ -411,6 +924,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn builder_rejects_malformed_websocket_endpoint() {
+        let tmpdir = tempdir().unwrap();
+        let result = TkSettingsBuilder::new()
+            .pattern_path(tmpdir.path().to_str().unwrap())
+            .connection(TkConnectionType::WebSocket(String::from("localhost:port")))
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn builder_rejects_missing_pattern_path() {
+        let result = TkSettingsBuilder::new()
+            .pattern_path("does/not/exist")
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn builder_accepts_valid_settings() {
+        let tmpdir = tempdir().unwrap();
+        let pattern_path = tmpdir.path().to_str().unwrap();
+        let settings = TkSettingsBuilder::new()
+            .pattern_path(pattern_path)
+            .connection(TkConnectionType::WebSocket(String::from("127.0.0.1:12345")))
+            .scan_duration(Duration::from_secs(30))
+            .build()
+            .unwrap();
+        assert_eq!(settings.pattern_path, pattern_path);
+        assert_eq!(settings.scan_duration_ms, Some(30_000));
+    }
+
+    #[test]
+    fn event_cap_takes_lowest_ceiling() {
+        let mut settings = TkSettings::default();
+        settings.access_scalar("a", |x| {
+            x.min_speed = 70;
+            x.max_speed = 80;
+        });
+        settings.set_event_cap("a", "combat", Some(60));
+        settings.set_event_cap("a", "magic", Some(40));
+
+        let capped = |settings: &mut TkSettings, events: &[&str]| {
+            let events: Vec<String> = events.iter().map(|x| x.to_string()).collect();
+            match settings.get_event_actuator_settings("a", &events) {
+                ActuatorSettings::Scalar(scalar) => (scalar.min_speed, scalar.max_speed),
+                _ => panic!(),
+            }
+        };
+        assert_eq!(capped(&mut settings, &["Combat"]), (60, 60));
+        assert_eq!(capped(&mut settings, &["combat", "magic"]), (40, 40));
+        assert_eq!(capped(&mut settings, &["other"]), (70, 80));
+
+        settings.set_event_cap("a", "combat", None);
+        assert_eq!(capped(&mut settings, &["combat"]), (70, 80));
+    }
+
     fn create_temp_file(name: &str, content: &str) -> (String, TempDir) {
         let tmp_path = tempdir().unwrap();
         assert_ok!(fs::create_dir_all(tmp_path.path().to_str().unwrap()));
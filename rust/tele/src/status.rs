@@ -4,18 +4,54 @@ use std::{
 };
 
 use bp_scheduler::actuator::{get_actuators, Actuator};
-use buttplug::client::ButtplugClientDevice;
+use buttplug::{client::ButtplugClientDevice, core::message::ButtplugDeviceMessageType};
 use crossbeam_channel::Receiver;
 use itertools::Itertools;
+use serde::Serialize;
 use tracing::debug;
 
-use crate::{connection::TkConnectionEvent, settings::TkSettings};
+use crate::{
+    connection::TkConnectionEvent,
+    input::{sanitize_name_list, NameGlob, TkDeviceSelector},
+    settings::TkSettings,
+};
 
 pub struct Status {
     status_events: Receiver<TkConnectionEvent>,
     connection: TkConnectionStatus,
     actuators: Vec<(Arc<Actuator>, TkConnectionStatus)>,
     known_actuators: Vec<String>,
+    scanning: bool,
+    last_error: Option<String>,
+}
+
+/// Summary of the connection state for diagnostics
+#[derive(Debug, Clone, Serialize)]
+pub struct TkStatusSnapshot {
+    pub status: String,
+    pub device_count: usize,
+    pub scanning: bool,
+    pub last_error: Option<String>,
+}
+
+/// Summary of a connected device, 'enabled' is true if any of its actuators is enabled
+#[derive(Debug, Clone, Serialize)]
+pub struct TkDeviceSnapshot {
+    pub name: String,
+    pub index: u32,
+    pub capabilities: Vec<String>,
+    pub enabled: bool,
+    pub battery: Option<f64>,
+}
+
+/// Counters of the command and event queues since connecting
+#[derive(Debug, Clone, Serialize)]
+pub struct TkMetrics {
+    pub commands_sent: usize,
+    pub commands_dropped: usize,
+    pub events_emitted: usize,
+    pub events_dropped: usize,
+    pub active_handles: usize,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -36,6 +72,8 @@ impl Status {
                 .iter()
                 .map(|x| x.actuator_id.clone())
                 .collect(),
+            scanning: false,
+            last_error: None,
         }
     }
 
@@ -70,6 +108,43 @@ impl Status {
             .cloned()
     }
 
+    pub fn is_scanning(&mut self) -> bool {
+        self.process_status_events();
+        self.scanning
+    }
+
+    pub fn get_snapshot(&mut self) -> TkStatusSnapshot {
+        TkStatusSnapshot {
+            status: self.connection_status().to_string(),
+            device_count: self.connected_devices().len(),
+            scanning: self.is_scanning(),
+            last_error: self.last_error.clone(),
+        }
+    }
+
+    /// Accepts both the device name and the name returned by 'get_device_names'
+    pub fn get_device(&mut self, device_name: &str) -> Option<Arc<ButtplugClientDevice>> {
+        let devices = self.connected_devices();
+        devices
+            .iter()
+            .find(|x| x.0 == device_name)
+            .or_else(|| devices.iter().find(|x| x.1.name() == device_name))
+            .map(|x| x.1.clone())
+    }
+
+    /// Buttplug index of a connected device, accepts the same names as 'get_device'
+    pub fn get_device_index(&mut self, device_name: &str) -> Option<u32> {
+        self.get_device(device_name).map(|x| x.index())
+    }
+
+    /// Name of the connected device with the buttplug 'index', as listed by 'get_device_names'
+    pub fn get_device_name(&mut self, index: u32) -> Option<String> {
+        self.connected_devices()
+            .into_iter()
+            .find(|x| x.1.index() == index)
+            .map(|x| x.0)
+    }
+
     pub fn get_actuator_status(&mut self, actuator_id: &str) -> TkConnectionStatus {
         self.process_status_events();
         let entry: Option<&(Arc<Actuator>, TkConnectionStatus)> = self
@@ -82,6 +157,89 @@ impl Status {
         TkConnectionStatus::NotConnected
     }
 
+    /// Devices matching any of the names, ignoring case and surrounding whitespace
+    pub fn get_devices_by_name(&mut self, device_names: &[String]) -> Vec<Arc<ButtplugClientDevice>> {
+        let names = sanitize_name_list(device_names);
+        self.connected_devices()
+            .into_iter()
+            .filter(|(name, device)| {
+                names.contains(&name.to_lowercase()) || names.contains(&device.name().to_lowercase())
+            })
+            .map(|x| x.1)
+            .collect()
+    }
+
+    /// Devices picked by 'selector', a name pattern matches both the device name and
+    /// the name returned by 'get_device_names'. Fails for invalid name patterns
+    pub fn get_devices_matching(
+        &mut self,
+        selector: &TkDeviceSelector,
+    ) -> Result<Vec<Arc<ButtplugClientDevice>>, anyhow::Error> {
+        match selector {
+            TkDeviceSelector::Names(names) => Ok(self.get_devices_by_name(names)),
+            TkDeviceSelector::NamePattern(pattern) => {
+                let glob = NameGlob::compile(pattern)?;
+                Ok(self
+                    .connected_devices()
+                    .into_iter()
+                    .filter(|(name, device)| glob.matches(name) || glob.matches(device.name()))
+                    .map(|x| x.1)
+                    .collect())
+            }
+        }
+    }
+
+    /// Devices sharing the same name get their buttplug index appended, i.e. 'Lovense Hush #2'
+    pub fn get_device_names(&mut self) -> Vec<String> {
+        self.connected_devices().into_iter().map(|x| x.0).collect()
+    }
+
+    /// Sorted actuator types of a connected device, i.e. ['Rotate(CW/CCW)', 'Vibrate'].
+    /// Rotators controlled with RotateCmd support both directions and are listed
+    /// as 'Rotate(CW/CCW)', scalar rotators without direction as 'Rotate'
+    pub fn get_device_capabilities(&mut self, device_name: &str) -> Vec<String> {
+        match self.get_device(device_name) {
+            Some(device) => get_actuators(vec![device])
+                .iter()
+                .map(|x| match x.message_type {
+                    ButtplugDeviceMessageType::RotateCmd => format!("{}(CW/CCW)", x.actuator),
+                    _ => x.actuator.to_string(),
+                })
+                .unique()
+                .sorted()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn connected_devices(&mut self) -> Vec<(String, Arc<ButtplugClientDevice>)> {
+        let devices: Vec<Arc<ButtplugClientDevice>> = self
+            .connected_actuators()
+            .iter()
+            .map(|x| x.device.clone())
+            .unique_by(|x| x.index())
+            .collect();
+        devices
+            .iter()
+            .map(|device| {
+                let same_name = devices.iter().filter(|x| x.name() == device.name()).count();
+                let name = if same_name > 1 {
+                    format!("{} #{}", device.name(), device.index())
+                } else {
+                    String::from(device.name())
+                };
+                (name, device.clone())
+            })
+            .collect()
+    }
+
+    /// Stops listing actuators that are only known from settings
+    pub fn forget_actuators(&mut self, actuator_ids: &[String]) {
+        self.known_actuators.retain(|x| !actuator_ids.contains(x));
+    }
+
+    /// Actuator ids used for settings, devices with the same name have the
+    /// same ids and share their settings (see 'Actuator::identifier')
     pub fn get_known_actuator_ids(&mut self) -> Vec<String> {
         let known_ids = self.known_actuators.clone();
         self.actuators()
@@ -98,8 +256,17 @@ impl Status {
             match evt {
                 TkConnectionEvent::Connected(_) => self.connection = TkConnectionStatus::Connected,
                 TkConnectionEvent::ConnectionFailure(err) => {
+                    self.scanning = false;
+                    self.last_error = Some(err.clone());
                     self.connection = TkConnectionStatus::Failed(err)
                 }
+                TkConnectionEvent::Disconnected => {
+                    self.scanning = false;
+                    self.connection = TkConnectionStatus::NotConnected;
+                    for actuator in self.actuators.iter_mut() {
+                        actuator.1 = TkConnectionStatus::NotConnected;
+                    }
+                }
                 TkConnectionEvent::DeviceAdded(device) => {
                     self.set_status(device.clone(), TkConnectionStatus::Connected);
                 }
@@ -107,10 +274,29 @@ impl Status {
                     self.set_status(device.clone(), TkConnectionStatus::NotConnected)
                 }
                 TkConnectionEvent::ActionError(actuator, err) => {
+                    self.last_error = Some(err.clone());
                     self.set_status(actuator.device.clone(), TkConnectionStatus::Failed(err))
                 }
+                TkConnectionEvent::ActuatorError(actuator, err) => {
+                    self.last_error = Some(format!("{}: {}", actuator.device.name(), err));
+                }
                 TkConnectionEvent::ActionStarted(_, _, _, _) => {}
                 TkConnectionEvent::ActionDone(_, _, _) => {}
+                TkConnectionEvent::HandleFinished(_) => {}
+                TkConnectionEvent::Reconnecting(_) => {}
+                TkConnectionEvent::CommandRejected(_) => {}
+                TkConnectionEvent::CommandDropped(_) => {}
+                TkConnectionEvent::Heartbeat(_) => {}
+                TkConnectionEvent::PatternError(_, err) => self.last_error = Some(err),
+                TkConnectionEvent::SelectorError(_, err) => self.last_error = Some(err),
+                TkConnectionEvent::SensorReading(_, _) => {}
+                TkConnectionEvent::SensorError(_, err) => self.last_error = Some(err),
+                TkConnectionEvent::ScanStarted => self.scanning = true,
+                TkConnectionEvent::ScanStopped => self.scanning = false,
+                TkConnectionEvent::ScanFailed(err) => {
+                    self.scanning = false;
+                    self.last_error = Some(err);
+                }
             };
         }
     }
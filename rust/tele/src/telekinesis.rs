@@ -1,56 +1,131 @@
 use anyhow::Error;
-use bp_fakes::FakeDeviceConnector;
-use bp_scheduler::settings::LinearRange;
+use bp_fakes::{vibrator, FakeConnectorCallRegistry, FakeDeviceConnector};
+use bp_scheduler::actuator::{get_actuators, Actuator};
+use bp_scheduler::settings::{ActuatorSettings, LinearRange};
 use bp_scheduler::speed::Speed;
 use bp_scheduler::ButtplugScheduler;
 use bp_scheduler::PlayerSettings;
 use buttplug::core::message::ActuatorType;
 use buttplug::{
-    client::ButtplugClient,
+    client::{ButtplugClient, ButtplugClientDevice, ButtplugClientDeviceEvent, ButtplugClientError},
     core::{
         connector::{
-            new_json_ws_client_connector, ButtplugConnector,
-            ButtplugInProcessClientConnectorBuilder,
+            ButtplugConnector, ButtplugInProcessClientConnectorBuilder,
+            ButtplugRemoteClientConnector, ButtplugWebsocketClientTransport,
+        },
+        message::{
+            serializer::ButtplugClientJSONSerializer, ButtplugCurrentSpecClientMessage,
+            ButtplugCurrentSpecServerMessage,
         },
-        message::{ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage},
     },
     server::{
-        device::hardware::communication::btleplug::BtlePlugCommunicationManagerBuilder,
+        device::hardware::communication::{
+            btleplug::BtlePlugCommunicationManagerBuilder,
+            lovense_connect_service::LovenseConnectServiceCommunicationManagerBuilder,
+            lovense_dongle::LovenseSerialDongleCommunicationManagerBuilder,
+            serialport::SerialPortCommunicationManagerBuilder,
+        },
         ButtplugServerBuilder,
     },
 };
 use funscript::FScript;
-use futures::Future;
+use futures::{future::join_all, Future, StreamExt};
 use tracing::instrument;
 
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use std::{
     fmt::{self},
     time::Instant,
 };
-use tokio::sync::mpsc::Sender;
-use tokio::{runtime::Runtime, sync::mpsc::channel};
-use tracing::{debug, error, info};
+use tokio::sync::mpsc::{error::TrySendError, Sender};
+use tokio::{
+    runtime::Runtime,
+    sync::{broadcast::error::RecvError, mpsc::channel, watch},
+    task::JoinHandle,
+    time::{sleep, timeout, timeout_at},
+};
+use tracing::{debug, error, info, warn};
+use tokio_util::sync::CancellationToken;
 
 use crate::connection::Task;
-use crate::input::TkParams;
-use crate::status::Status;
+use crate::input::{sanitize_name_list, TkDeviceSelector, TkParams};
+use crate::pattern::{parse_pattern, scale_pattern_speed, PatternCache};
+use crate::status::{Status, TkConnectionStatus, TkDeviceSnapshot, TkMetrics};
 use crate::{
-    connection::{handle_connection, TkCommand, TkConnectionEvent},
-    settings::{TkConnectionType, TkSettings},
+    connection::{
+        handle_command, handle_connection, send_heartbeats, try_send_event, unless_disconnected, wait_for_activity,
+        TkCommand,
+        TkConnectionEnd, TkConnectionEvent, TkEventSender,
+    },
+    settings::{get_websocket_uri, CommManagerKind, TkConnectionType, TkOverflowPolicy, TkSettings},
 };
 
 pub static ERROR_HANDLE: i32 = -1;
+static DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+static SENSOR_READ_TIMEOUT: Duration = Duration::from_secs(1);
+static SCAN_RETRIES: u32 = 3;
+static SCAN_RETRY_BACKOFF: Duration = Duration::from_millis(50);
+/// Reported as pattern name of patterns played with 'vibrate_pattern_inline'
+static INLINE_PATTERN_NAME: &str = "inline";
+
+/// Errors that prevent creating Telekinesis
+#[derive(Debug)]
+pub enum TkError {
+    /// The async runtime could not be started, i.e. because of thread limits
+    RuntimeInit(std::io::Error),
+}
+
+/// Outcome of the last command, so callers can tell apart why something failed.
+/// Returned to C by the '_ex' functions, the values must not change
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TkResult {
+    Ok = 0,
+    NotConnected = 1,
+    QueueFull = 2,
+    NoDevices = 3,
+    InvalidHandle = 4,
+    TaskLimit = 5,
+    PatternError = 6,
+    InvalidDuration = 7,
+    InvalidSelector = 8,
+    /// The command failed without a more specific reason, i.e. it does not exist
+    Failed = 9,
+}
 
 pub struct Telekinesis {
     pub settings: TkSettings,
     pub connection_events: crossbeam_channel::Receiver<TkConnectionEvent>,
     pub status: Status,
+    pub patterns: PatternCache,
+    task_events: HashMap<i32, Vec<String>>,
+    task_deadlines: HashMap<i32, Instant>,
+    /// Set while paused, the task deadlines are moved back by the paused time on resume
+    paused_at: Option<Instant>,
+    task_groups: HashMap<i32, String>,
+    /// Tasks without a duration that end once their stop event is triggered
+    task_stop_events: HashMap<i32, String>,
+    battery_levels: HashMap<u32, f64>,
+    /// Running sensor subscriptions by device name, cancelled to unsubscribe
+    sensor_subscriptions: HashMap<String, CancellationToken>,
+    pub last_result: TkResult,
     runtime: Runtime,
     command_sender: Sender<TkCommand>,
+    sent_commands: usize,
+    dropped_commands: usize,
     scheduler: ButtplugScheduler,
-    client_event_sender: crossbeam_channel::Sender<TkConnectionEvent>,
-    status_event_sender: crossbeam_channel::Sender<TkConnectionEvent>,
+    client_event_sender: TkEventSender,
+    status_event_sender: TkEventSender,
+    connection_task: Option<JoinHandle<()>>,
+    connected_at: Instant,
+    /// Time of the last command, see 'TkSettings::idle_disconnect_secs'
+    activity: watch::Sender<Instant>,
+    scan_timeout: Option<JoinHandle<()>>,
+    /// Messages sent to the simulated devices of the test connection
+    pub test_calls: Option<FakeConnectorCallRegistry>,
 }
 
 impl Telekinesis {
@@ -65,36 +140,84 @@ impl Telekinesis {
         T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage>
             + 'static,
     {
-        let settings = provided_settings.unwrap_or_else(TkSettings::default);
+        let mut connect_action = Some(connect_action);
+        Telekinesis::connect_with_reconnect(
+            move || connect_action.take().map(|connect| connect()),
+            provided_settings,
+            type_name,
+        )
+    }
+
+    /// 'connect_action' is called again whenever the connection to the server is lost,
+    /// returning None stops reconnecting
+    pub fn connect_with_reconnect<T, Fn, Fut>(
+        connect_action: Fn,
+        provided_settings: Option<TkSettings>,
+        type_name: TkConnectionType,
+    ) -> Result<Telekinesis, anyhow::Error>
+    where
+        Fn: FnMut() -> Option<Fut> + Send + 'static,
+        Fut: Future<Output = T> + Send,
+        T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage>
+            + 'static,
+    {
+        let mut telekinesis = Telekinesis::new(provided_settings.unwrap_or_default())?;
+        telekinesis.start_connection(connect_action, type_name);
+        Ok(telekinesis)
+    }
+
+    fn new(settings: TkSettings) -> Result<Telekinesis, anyhow::Error> {
         let (event_sender_client, event_receiver) = crossbeam_channel::unbounded();
         let (event_sender_internal, event_receiver_internal) = crossbeam_channel::unbounded();
-        let (command_sender, command_receiver) = channel(256);
+        let event_sender_client = TkEventSender::new(event_sender_client);
+        let event_sender_internal = TkEventSender::new(event_sender_internal);
+        // replaced with the sender of the connection task in 'start_connection'
+        let (command_sender, _) = channel(1);
         let (scheduler, mut worker) = ButtplugScheduler::create(PlayerSettings {
             scalar_resolution_ms: 100,
+            update_coalesce_ms: settings.update_coalesce_ms,
+            crossfade_ms: settings.crossfade_ms,
+            command_rate_ms: settings.command_rate_ms,
+            fade_out_ms: settings.fade_out_ms,
+            pattern_min_delta: settings.pattern_min_delta,
         });
 
         let telekinesis = Telekinesis {
             command_sender,
+            sent_commands: 0,
+            dropped_commands: 0,
             connection_events: event_receiver,
-            runtime: Runtime::new()?,
+            runtime: start_runtime(Runtime::new)?,
             settings: settings.clone(),
             scheduler,
-            client_event_sender: event_sender_client.clone(),
-            status_event_sender: event_sender_internal.clone(),
+            client_event_sender: event_sender_client,
+            status_event_sender: event_sender_internal,
             status: Status::new(event_receiver_internal, &settings),
+            patterns: PatternCache::default(),
+            task_events: HashMap::new(),
+            task_deadlines: HashMap::new(),
+            paused_at: None,
+            task_groups: HashMap::new(),
+            task_stop_events: HashMap::new(),
+            battery_levels: HashMap::new(),
+            sensor_subscriptions: HashMap::new(),
+            last_result: TkResult::Ok,
+            connection_task: None,
+            connected_at: Instant::now(),
+            activity: watch::Sender::new(Instant::now()),
+            scan_timeout: None,
+            test_calls: None,
         };
-        info!(?telekinesis, "connecting...");    
+        let mut actuator_errors = worker.report_errors();
+        let client_sender_clone = telekinesis.client_event_sender.clone();
+        let status_sender_clone = telekinesis.status_event_sender.clone();
         telekinesis.runtime.spawn(async move {
-            let client = with_connector(connect_action().await).await;
-            handle_connection(
-                event_sender_client,
-                event_sender_internal,
-                command_receiver,
-                client,
-                type_name,
-            )
-            .await;
-            debug!("connection handling stopped");
+            while let Some((actuator, err)) = actuator_errors.recv().await {
+                error!(%actuator, err, "actuator command failed");
+                let event = TkConnectionEvent::ActuatorError(actuator, err);
+                try_send_event(&client_sender_clone, event.clone());
+                try_send_event(&status_sender_clone, event);
+            }
         });
         telekinesis.runtime.spawn(async move {
             debug!("starting worker thread");
@@ -103,51 +226,384 @@ impl Telekinesis {
         });
         Ok(telekinesis)
     }
+
+    /// Spawns the task handling the buttplug client, commands sent before
+    /// this are not received by the new connection
+    fn start_connection<T, Fn, Fut>(&mut self, mut connect_action: Fn, type_name: TkConnectionType)
+    where
+        Fn: FnMut() -> Option<Fut> + Send + 'static,
+        Fut: Future<Output = T> + Send,
+        T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage>
+            + 'static,
+    {
+        let (command_sender, mut command_receiver) =
+            channel(self.settings.command_queue_size.max(1));
+        self.command_sender = command_sender;
+        self.connected_at = Instant::now();
+        info!(?self, "connecting...");
+        let event_sender_client = self.client_event_sender.clone();
+        let event_sender_internal = self.status_event_sender.clone();
+        let reconnect = self.settings.reconnect.clone();
+        let stop_all_on_disconnect = self
+            .settings
+            .stop_all_on_disconnect
+            .then(|| self.scheduler.stop_all_handle());
+        let idle_disconnect = self.settings.idle_disconnect_secs.map(Duration::from_secs);
+        let mut activity = self.activity.subscribe();
+        let heartbeat = self.settings.heartbeat_ms.map(|ms| Duration::from_millis(ms.max(1)));
+        let heartbeat_sender = self.client_event_sender.clone();
+        let connection = async move {
+            let mut attempt = 0;
+            let mut resume = false;
+            let mut woken_by = None;
+            while let Some(connector) = connect_action() {
+                let client = match attempt {
+                    0 => with_connector(connector.await).await,
+                    _ => {
+                        let reconnect = async { with_connector(connector.await).await };
+                        match unless_disconnected(&mut command_receiver, reconnect).await {
+                            Some(client) => client,
+                            None => break,
+                        }
+                    }
+                };
+                let end = if attempt > 0 && !client.connected() {
+                    TkConnectionEnd::Lost
+                } else {
+                    if attempt > 0 || resume {
+                        info!(attempt, resume, "reconnected");
+                        attempt = 0;
+                        resume = false;
+                        handle_command(
+                            TkCommand::Scan,
+                            &client,
+                            &event_sender_client,
+                            &event_sender_internal,
+                            &type_name,
+                        )
+                        .await;
+                        if let Some(cmd) = woken_by.take() {
+                            handle_command(cmd, &client, &event_sender_client, &event_sender_internal, &type_name)
+                                .await;
+                        }
+                    }
+                    handle_connection(
+                        event_sender_client.clone(),
+                        event_sender_internal.clone(),
+                        &mut command_receiver,
+                        client,
+                        type_name.clone(),
+                        stop_all_on_disconnect.as_ref(),
+                        idle_disconnect,
+                        &activity,
+                    )
+                    .await
+                };
+                match (end, idle_disconnect) {
+                    (TkConnectionEnd::Idle, Some(idle_disconnect)) => {
+                        match wait_for_activity(&mut command_receiver, &mut activity, idle_disconnect).await {
+                            Some(cmd) => woken_by = cmd,
+                            None => break,
+                        }
+                        resume = true;
+                        continue;
+                    }
+                    (TkConnectionEnd::Lost, _) if attempt < reconnect.max_retries => {}
+                    _ => break,
+                }
+                attempt += 1;
+                info!(attempt, "reconnecting...");
+                let reconnecting = TkConnectionEvent::Reconnecting(attempt);
+                try_send_event(&event_sender_client, reconnecting.clone());
+                try_send_event(&event_sender_internal, reconnecting);
+                let backoff = sleep(Duration::from_millis(reconnect.backoff_ms));
+                if unless_disconnected(&mut command_receiver, backoff).await.is_none() {
+                    break;
+                }
+            }
+            debug!("connection handling stopped");
+        };
+        self.connection_task = Some(self.runtime.spawn(async move {
+            match heartbeat {
+                Some(interval) => tokio::select! {
+                    _ = connection => {}
+                    _ = send_heartbeats(interval, heartbeat_sender) => {}
+                },
+                None => connection.await,
+            }
+        }));
+    }
+
+    /// Brings up the connector for 'connection' inside the existing runtime
+    fn connect_to(&mut self, connection: TkConnectionType) {
+        self.test_calls = None;
+        match connection {
+            TkConnectionType::WebSocket(endpoint) => {
+                let uri = get_websocket_uri(&endpoint);
+                self.start_connection(
+                    {
+                        let uri = uri.clone();
+                        move || {
+                            let uri = uri.clone()?;
+                            Some(async move { new_websocket_connector(&uri) })
+                        }
+                    },
+                    TkConnectionType::WebSocket(endpoint.clone()),
+                );
+                if uri.is_none() {
+                    error!(endpoint, "invalid websocket endpoint");
+                    let failure = TkConnectionEvent::ConnectionFailure(format!(
+                        "Invalid endpoint '{}'",
+                        endpoint
+                    ));
+                    try_send_event(&self.client_event_sender, failure.clone());
+                    try_send_event(&self.status_event_sender, failure);
+                }
+            }
+            TkConnectionType::InProcess { managers } => {
+                self.start_connection(
+                    {
+                        let managers = managers.clone();
+                        move || {
+                            let managers = managers.clone();
+                            Some(async move { in_process_connector(&managers) })
+                        }
+                    },
+                    TkConnectionType::InProcess { managers },
+                );
+            }
+            TkConnectionType::Test => {
+                let (connector, call_registry) = match self.settings.test_devices.is_empty() {
+                    true => FakeDeviceConnector::device_demo(),
+                    false => FakeDeviceConnector::new(
+                        self.settings
+                            .test_devices
+                            .iter()
+                            .enumerate()
+                            .map(|(i, name)| vibrator(i as u32 + 1, name))
+                            .collect(),
+                    ),
+                };
+                self.start_connection(
+                    move || {
+                        // records into the same registry when connecting again
+                        let connector = connector.reconnector();
+                        Some(async move { connector })
+                    },
+                    TkConnectionType::Test,
+                );
+                self.test_calls = Some(call_registry);
+            }
+        }
+    }
 }
 
 impl Telekinesis {
     pub fn connect(settings: TkSettings) -> Result<Telekinesis, Error> {
-        let settings_clone = settings.clone();
-        match settings.connection {
-            TkConnectionType::WebSocket(endpoint) => {
-                let uri = format!("ws://{}", endpoint);
-                Telekinesis::connect_with(
-                    || async move { new_json_ws_client_connector(&uri) },
-                    Some(settings_clone),
-                    TkConnectionType::WebSocket(endpoint),
-                )
+        let connection = settings.connection.clone();
+        let mut tk = Telekinesis::new(settings)?;
+        tk.connect_to(connection);
+        Ok(tk)
+    }
+
+    /// Disconnects the current buttplug client and connects to 'connection'
+    /// within the same runtime and starts scanning, all other settings are kept.
+    /// The status goes through 'Disconnected' before the new connection is 'Connected'
+    pub fn switch_connection(&mut self, connection: TkConnectionType) -> Result<(), Error> {
+        info!(%connection, "switching connection");
+        self.close_connection()?;
+        self.settings.connection = connection.clone();
+        self.connect_to(connection);
+        self.scan_for_devices();
+        Ok(())
+    }
+
+    /// Like 'switch_connection' but with a custom connector, see 'connect_with'
+    pub fn switch_connection_with<T, Fn, Fut>(
+        &mut self,
+        connect_action: Fn,
+        type_name: TkConnectionType,
+    ) -> Result<(), Error>
+    where
+        Fn: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send,
+        T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage>
+            + 'static,
+    {
+        info!(%type_name, "switching connection");
+        self.close_connection()?;
+        self.test_calls = None;
+        self.settings.connection = type_name.clone();
+        let mut connect_action = Some(connect_action);
+        self.start_connection(move || connect_action.take().map(|connect| connect()), type_name);
+        self.scan_for_devices();
+        Ok(())
+    }
+
+    /// Connects and starts a scan, blocking until the connection status is
+    /// `Connected`. Fails and disconnects again if this takes longer than `wait`
+    pub fn connect_and_wait(settings: TkSettings, wait: Duration) -> Result<Telekinesis, Error> {
+        let deadline = Instant::now() + wait;
+        let mut tk = Telekinesis::connect(settings)?;
+        tk.scan_for_devices();
+        while tk.status.connection_status() != TkConnectionStatus::Connected {
+            if Instant::now() >= deadline {
+                let status = tk.status.connection_status();
+                tk.disconnect();
+                return Err(anyhow::anyhow!(
+                    "Not connected after {}ms ({:?})",
+                    wait.as_millis(),
+                    status
+                ));
             }
-            TkConnectionType::InProcess => Telekinesis::connect_with(
-                || async move { in_process_connector() },
-                Some(settings),
-                TkConnectionType::InProcess,
-            ),
-            TkConnectionType::Test => Telekinesis::connect_with(
-                || async move { FakeDeviceConnector::device_demo().0 },
-                Some(settings),
-                TkConnectionType::Test,
-            ),
+            std::thread::sleep(Duration::from_millis(10));
         }
+        Ok(tk)
     }
 
-    pub fn scan_for_devices(&self) -> bool {
+    /// Starts scanning, if the command queue is full the scan is retried a
+    /// few times in the background and 'ScanFailed' is sent if it never started
+    pub fn scan_for_devices(&mut self) -> bool {
         info!("start scan");
-        if self.command_sender.try_send(TkCommand::Scan).is_err() {
-            error!("Failed to start scan");
-            return false;
+        self.last_result = match self.command_sender.try_send(TkCommand::Scan) {
+            Ok(_) => {
+                self.sent_commands += 1;
+                TkResult::Ok
+            }
+            Err(TrySendError::Full(_)) => {
+                warn!("command queue full, retrying scan");
+                self.retry_scan();
+                TkResult::Ok
+            }
+            Err(TrySendError::Closed(_)) => {
+                error!("Failed to start scan");
+                let failed = TkConnectionEvent::ScanFailed(String::from("Not connected"));
+                try_send_event(&self.client_event_sender, failed.clone());
+                try_send_event(&self.status_event_sender, failed);
+                self.last_result = TkResult::NotConnected;
+                return false;
+            }
+        };
+        if let Some(scan_duration_ms) = self.settings.scan_duration_ms {
+            let command_sender = self.command_sender.clone();
+            let previous = self.scan_timeout.replace(self.runtime.spawn(async move {
+                sleep(Duration::from_millis(scan_duration_ms)).await;
+                info!("scan timeout");
+                if command_sender.send(TkCommand::StopScan).await.is_err() {
+                    error!("Failed to stop scan");
+                }
+            }));
+            if let Some(previous) = previous {
+                previous.abort();
+            }
         }
         true
     }
 
-    pub fn stop_scan(&self) -> bool {
+    fn retry_scan(&mut self) {
+        let command_sender = self.command_sender.clone();
+        let client_sender_clone = self.client_event_sender.clone();
+        let status_sender_clone = self.status_event_sender.clone();
+        self.runtime.spawn(async move {
+            let mut reason = "Command queue full";
+            for attempt in 1..=SCAN_RETRIES {
+                sleep(SCAN_RETRY_BACKOFF * attempt).await;
+                match command_sender.try_send(TkCommand::Scan) {
+                    Ok(_) => {
+                        info!(attempt, "scan started after retry");
+                        return;
+                    }
+                    Err(TrySendError::Full(_)) => debug!(attempt, "command queue still full"),
+                    Err(TrySendError::Closed(_)) => {
+                        reason = "Not connected";
+                        break;
+                    }
+                }
+            }
+            error!(reason, "Failed to start scan");
+            let failed = TkConnectionEvent::ScanFailed(String::from(reason));
+            try_send_event(&client_sender_clone, failed.clone());
+            try_send_event(&status_sender_clone, failed);
+        });
+    }
+
+    pub fn stop_scan(&mut self) -> bool {
         info!("stop scan");
-        if self.command_sender.try_send(TkCommand::StopScan).is_err() {
+        if let Some(scan_timeout) = self.scan_timeout.take() {
+            scan_timeout.abort();
+        }
+        if !self.send_command(TkCommand::StopScan) {
             error!("Failed to stop scan");
             return false;
         }
         true
     }
 
+    /// Delays the idle disconnect, or connects again after it
+    pub fn record_activity(&self) {
+        self.activity.send_replace(Instant::now());
+    }
+
+    fn send_command(&mut self, command: TkCommand) -> bool {
+        self.last_result = match self.command_sender.try_send(command) {
+            Ok(_) => {
+                self.sent_commands += 1;
+                TkResult::Ok
+            }
+            Err(TrySendError::Full(_)) => TkResult::QueueFull,
+            Err(TrySendError::Closed(_)) => TkResult::NotConnected,
+        };
+        if self.last_result == TkResult::QueueFull {
+            self.dropped_commands += 1;
+            error!("command queue full, {} commands dropped", self.dropped_commands);
+            let event = TkConnectionEvent::CommandDropped(self.dropped_commands);
+            try_send_event(&self.client_event_sender, event.clone());
+            try_send_event(&self.status_event_sender, event);
+        }
+        self.last_result == TkResult::Ok
+    }
+
+    /// Reads patterns from 'pattern_path' from now on, returns false and keeps
+    /// the current path if it is not a directory
+    pub fn set_pattern_path(&mut self, pattern_path: &str) -> bool {
+        if !std::path::Path::new(pattern_path).is_dir() {
+            error!("pattern path {:?} is not a directory", pattern_path);
+            return false;
+        }
+        info!(pattern_path, "set pattern path");
+        self.settings.pattern_path = pattern_path.to_owned();
+        self.patterns.clear();
+        true
+    }
+
+    pub fn get_pattern_path(&self) -> &str {
+        &self.settings.pattern_path
+    }
+
+    /// Handles 'event' as if it was emitted by the buttplug client
+    #[cfg(test)]
+    pub fn inject_client_event(&mut self, event: buttplug::client::ButtplugClientEvent) -> bool {
+        self.send_command(TkCommand::InjectEvent(event))
+    }
+
+    /// Number of commands dropped because the command queue was full,
+    /// see 'TkSettings::command_queue_size'
+    pub fn dropped_commands(&self) -> usize {
+        self.dropped_commands
+    }
+
+    /// Counters of sent commands and events emitted to the client
+    pub fn get_metrics(&mut self) -> TkMetrics {
+        self.scheduler.clean_finished_tasks();
+        TkMetrics {
+            commands_sent: self.sent_commands,
+            commands_dropped: self.dropped_commands,
+            events_emitted: self.client_event_sender.emitted(),
+            events_dropped: self.client_event_sender.dropped(),
+            active_handles: self.scheduler.task_count(),
+        }
+    }
+
     pub fn scalar(
         &mut self,
         task: Task,
@@ -158,9 +614,12 @@ impl Telekinesis {
     ) -> i32 {
         info!("scalar");
         self.scheduler.clean_finished_tasks();
+        if !self.check_task_limit() {
+            return ERROR_HANDLE;
+        }
 
         let task_clone = task.clone();
-        let actuators = self.status.connected_actuators();
+        let mut actuators = self.status.connected_actuators();
 
         let devices = TkParams::filter_devices(
             &actuators,
@@ -168,13 +627,53 @@ impl Telekinesis {
             actuator_types,
             &self.settings.devices,
         );
-        let settings = devices.iter().map(|x| self.settings.get_or_create(x.identifier()).actuator_settings ).collect();
-        let player = self.scheduler.create_player_with_settings(devices, settings);
+        let devices = self.with_followers(devices);
+        let settings = devices
+            .iter()
+            .map(|x| self.settings.get_event_actuator_settings(x.identifier(), &body_parts))
+            .collect();
+        let mut player = self.scheduler.create_player_with_settings(devices, settings);
+        let devices_ready = match player.actuators.is_empty() && self.awaiting_devices() {
+            true => Some(self.await_devices(body_parts.clone(), actuator_types.to_vec())),
+            false => None,
+        };
+        self.last_result = match player.actuators.is_empty() && devices_ready.is_none() {
+            true => TkResult::NoDevices,
+            false => TkResult::Ok,
+        };
 
         let handle = player.handle;
+        self.task_events.insert(handle, sanitize_name_list(&body_parts));
+        self.set_deadline(handle, duration);
+        if let Task::Rotate(_, clockwise) = task {
+            // queued before the task starts, so the first command already has the direction
+            self.scheduler.set_direction(handle, clockwise);
+        }
         let client_sender_clone = self.client_event_sender.clone();
         let status_sender_clone = self.status_event_sender.clone();
         self.runtime.spawn(async move {
+            if let Some(devices_ready) = devices_ready {
+                let cancellation_token = player.cancellation_token.clone();
+                tokio::select! {
+                    ready = devices_ready => match ready {
+                        Some((ready_actuators, settings)) => {
+                            player.actuators = ready_actuators.clone();
+                            player.settings = settings;
+                            actuators = ready_actuators;
+                        }
+                        None => {
+                            error!(handle, "no devices connected within the grace period");
+                            cancellation_token.cancel();
+                            let rejected = TkConnectionEvent::CommandRejected(handle);
+                            try_send_event(&client_sender_clone, rejected.clone());
+                            try_send_event(&status_sender_clone, rejected);
+                            try_send_event(&client_sender_clone, TkConnectionEvent::HandleFinished(handle));
+                            return;
+                        }
+                    },
+                    _ = cancellation_token.cancelled() => {}
+                }
+            }
             let now = Instant::now();
             client_sender_clone
                 .send(TkConnectionEvent::ActionStarted(
@@ -185,325 +684,3908 @@ impl Telekinesis {
                 ))
                 .expect("never full");
             let result = match task {
-                Task::Scalar(speed) => player.play_scalar(duration, speed).await,
+                Task::Scalar(speed) | Task::Rotate(speed, _) => player.play_scalar(duration, speed).await,
+                Task::ScalarRamped(speed, fade_in, fade_out) => {
+                    player
+                        .play_scalar_ramped(duration, speed, fade_in, fade_out)
+                        .await
+                }
+                Task::ScalarRandom(min, max, change) => {
+                    player
+                        .play_scalar_random(duration, min, max, change, None)
+                        .await
+                }
                 Task::Pattern(speed, _, _) => {
                     player
                         .play_scalar_pattern(duration, fscript.unwrap(), speed)
                         .await
                 }
+                Task::MultiPattern(_, _) => panic!(),
                 Task::Linear(_, _) => panic!(),
                 Task::LinearOscillate(_, _) => todo!(),
+                Task::LinearStroke(_, _) => panic!(),
             };
-            let event = match result {
-                Ok(_) => TkConnectionEvent::ActionDone(task_clone, now.elapsed(), handle),
-                Err(err) => TkConnectionEvent::ActionError(actuators[0].clone(), err.to_string()),
-            };
-            client_sender_clone.send(event.clone()).expect("never full");
-            status_sender_clone.send(event.clone()).expect("never full");
+            send_task_result(
+                &client_sender_clone,
+                &status_sender_clone,
+                task_clone,
+                actuators.first(),
+                handle,
+                now,
+                result,
+            );
         });
         handle
     }
 
-    pub fn linear_pattern(
+    /// Vibrates all enabled vibrators matching 'body_parts', rejects the
+    /// command without creating a task when nothing would happen
+    pub fn vibrate(&mut self, speed: Speed, duration: Duration, body_parts: Vec<String>) -> i32 {
+        if !self.validate_vibrate(duration, &body_parts) {
+            return ERROR_HANDLE;
+        }
+        self.scalar(
+            Task::Scalar(speed),
+            duration,
+            body_parts,
+            None,
+            &[ActuatorType::Vibrate],
+        )
+    }
+
+    /// Like 'vibrate' but tags the handle with 'group' so that all tasks
+    /// of the group can be stopped together with 'stop_group'
+    pub fn vibrate_grouped(
         &mut self,
-        task: Task,
+        group: &str,
+        speed: Speed,
         duration: Duration,
         body_parts: Vec<String>,
-        fscript: FScript,
     ) -> i32 {
-        info!("linear pattern");
+        let handle = self.vibrate(speed, duration, body_parts);
+        if handle != ERROR_HANDLE {
+            let group = sanitize_name_list(&[group.to_owned()]).remove(0);
+            self.task_groups.insert(handle, group);
+        }
+        handle
+    }
 
-        self.scheduler.clean_finished_tasks();
-        let task_clone = task.clone();
+    /// Vibrates without a duration until 'trigger_stop_event' is called
+    /// with 'stop_event'
+    pub fn vibrate_until(&mut self, stop_event: &str, speed: Speed, body_parts: Vec<String>) -> i32 {
+        let handle = self.vibrate(speed, Duration::MAX, body_parts);
+        if handle != ERROR_HANDLE {
+            let stop_event = sanitize_name_list(&[stop_event.to_owned()]).remove(0);
+            self.task_stop_events.insert(handle, stop_event);
+        }
+        handle
+    }
 
-        let actuators = self.status.connected_actuators();
+    /// Mirrors every command to 'primary' onto 'follower', the link is stored
+    /// by device name and is kept when the devices reconnect
+    pub fn link_devices(&mut self, primary: &str, follower: &str) -> bool {
+        info!("link devices");
+        self.settings.link_devices(primary, follower)
+    }
+
+    /// Actuator ids that 'settings_prune_disconnected' would remove, nothing is changed
+    pub fn settings_prune_disconnected_dry_run(&mut self) -> Vec<String> {
+        let connected: Vec<String> = self
+            .status
+            .connected_actuators()
+            .iter()
+            .map(|x| String::from(x.identifier()))
+            .collect();
+        self.settings.get_devices_not_in(&connected)
+    }
+
+    /// Removes the settings of all actuators that are not connected right now,
+    /// i.e. devices that were configured but never showed up. Returns the removed
+    /// actuator ids
+    pub fn settings_prune_disconnected(&mut self) -> Vec<String> {
+        info!("pruning settings of disconnected devices");
+        let pruned = self.settings_prune_disconnected_dry_run();
+        self.settings.remove_devices(&pruned);
+        self.status.forget_actuators(&pruned);
+        pruned
+    }
+
+    /// Adds the actuators of all devices that follow a device of 'actuators'
+    /// and have the same actuator type, see 'link_devices'
+    fn with_followers(&mut self, actuators: Vec<Arc<Actuator>>) -> Vec<Arc<Actuator>> {
+        add_followers(&self.settings, &self.status.connected_actuators(), actuators)
+    }
+
+    /// Controls a single actuator, ignores its enabled and event settings
+    pub fn vibrate_actuator(&mut self, actuator_id: &str, speed: Speed, duration: Duration) -> i32 {
+        info!("vibrate actuator");
+        let devices: Vec<_> = self
+            .status
+            .connected_actuators()
+            .into_iter()
+            .filter(|x| x.identifier() == actuator_id)
+            .collect();
+        self.vibrate_on(devices, speed, duration, Duration::ZERO)
+    }
 
+    /// Starts vibrating after 'start_delay', stopping the handle
+    /// before that prevents the devices from starting at all
+    pub fn vibrate_delayed(
+        &mut self,
+        speed: Speed,
+        duration: Duration,
+        start_delay: Duration,
+        body_parts: Vec<String>,
+    ) -> i32 {
+        info!("vibrate delayed");
         let devices = TkParams::filter_devices(
-            &actuators,
+            &self.status.connected_actuators(),
             &body_parts,
-            &[ActuatorType::Position],
+            &[ActuatorType::Vibrate],
             &self.settings.devices,
         );
-        let settings = devices.iter().map(|x| self.settings.get_or_create(x.identifier()).actuator_settings ).collect();
+        let handle = self.vibrate_on(devices, speed, duration, start_delay);
+        if handle != ERROR_HANDLE {
+            self.task_events.insert(handle, sanitize_name_list(&body_parts));
+        }
+        handle
+    }
+
+    /// Vibrates all vibrators of the named devices regardless of their events,
+    /// unknown names are ignored
+    pub fn vibrate_devices(&mut self, device_names: Vec<String>, speed: Speed, duration: Duration) -> i32 {
+        info!("vibrate devices");
+        self.vibrate_selected(TkDeviceSelector::Names(device_names), speed, duration)
+    }
+
+    /// Vibrates all connected devices whose name matches the glob 'name_pattern',
+    /// i.e. 'Lovense*'. Invalid patterns send a selector error event
+    pub fn vibrate_matching(&mut self, name_pattern: &str, speed: Speed, duration: Duration) -> i32 {
+        info!("vibrate matching");
+        self.vibrate_selected(TkDeviceSelector::NamePattern(name_pattern.into()), speed, duration)
+    }
+
+    fn vibrate_selected(&mut self, selector: TkDeviceSelector, speed: Speed, duration: Duration) -> i32 {
+        let devices = match self.status.get_devices_matching(&selector) {
+            Ok(devices) => devices,
+            Err(err) => {
+                error!("invalid device selector '{}': {}", selector, err);
+                self.last_result = TkResult::InvalidSelector;
+                let event = TkConnectionEvent::SelectorError(selector.to_string(), err.to_string());
+                try_send_event(&self.client_event_sender, event.clone());
+                try_send_event(&self.status_event_sender, event);
+                return ERROR_HANDLE;
+            }
+        };
+        let actuators = self
+            .status
+            .connected_actuators()
+            .into_iter()
+            .filter(|x| x.actuator == ActuatorType::Vibrate)
+            .filter(|x| devices.iter().any(|device| device.index() == x.device.index()))
+            .collect();
+        self.vibrate_on(actuators, speed, duration, Duration::ZERO)
+    }
+
+    fn vibrate_on(
+        &mut self,
+        devices: Vec<Arc<Actuator>>,
+        speed: Speed,
+        duration: Duration,
+        start_delay: Duration,
+    ) -> i32 {
+        self.scheduler.clean_finished_tasks();
+        if !self.check_task_limit() {
+            return ERROR_HANDLE;
+        }
+
+        let task = Task::Scalar(speed);
+        let devices = self.with_followers(devices);
+        let settings = devices.iter().map(|x| self.settings.get_actuator_settings(x.identifier())).collect();
         let player = self.scheduler.create_player_with_settings(devices, settings);
-        let handle = player.handle;
+        self.last_result = match player.actuators.is_empty() {
+            true => TkResult::NoDevices,
+            false => TkResult::Ok,
+        };
 
+        let handle = player.handle;
+        self.set_deadline(handle, start_delay.saturating_add(duration));
         let client_sender_clone = self.client_event_sender.clone();
         let status_sender_clone = self.status_event_sender.clone();
         self.runtime.spawn(async move {
+            if !player.wait_for_start(start_delay).await {
+                return;
+            }
             let now = Instant::now();
+            let actuators = player.actuators.clone();
             client_sender_clone
                 .send(TkConnectionEvent::ActionStarted(
-                    task_clone.clone(),
+                    task.clone(),
                     player.actuators.clone(),
-                    body_parts,
+                    vec![],
                     player.handle,
                 ))
                 .expect("never full");
-            let result = match task {
-                Task::Linear(speed, _) => player.play_linear(duration, fscript).await,
-                _ => panic!(),
-            };
-            let event = match result {
-                Ok(_) => TkConnectionEvent::ActionDone(task_clone, now.elapsed(), handle),
-                Err(err) => TkConnectionEvent::ActionError(actuators[0].clone(), err.to_string()),
-            };
-            client_sender_clone.send(event.clone()).expect("never full");
-            status_sender_clone.send(event.clone()).expect("never full");
+            let result = player.play_scalar(duration, speed).await;
+            send_task_result(
+                &client_sender_clone,
+                &status_sender_clone,
+                task,
+                actuators.first(),
+                handle,
+                now,
+                result,
+            );
         });
         handle
     }
 
-    pub fn linear_oscillate(
+    /// 'speed_scale' changes the playback speed of the pattern, 1.0 plays it as is
+    pub fn vibrate_pattern(
         &mut self,
-        task: Task,
+        speed: Speed,
         duration: Duration,
-        range: &str,
+        pattern_dir: Option<&str>,
+        pattern_name: &str,
+        speed_scale: f64,
         body_parts: Vec<String>,
     ) -> i32 {
-        info!("linear oscillate");
+        if !self.validate_vibrate(duration, &body_parts) {
+            return ERROR_HANDLE;
+        }
+        match self.load_pattern(pattern_dir, pattern_name, true) {
+            Some(fscript) => self.scalar(
+                Task::Pattern(speed, ActuatorType::Vibrate, pattern_name.into()),
+                duration,
+                body_parts,
+                Some(scale_pattern_speed(fscript, speed_scale)),
+                &[ActuatorType::Vibrate],
+            ),
+            None => ERROR_HANDLE,
+        }
+    }
 
+    /// Plays a funscript given as json instead of a pattern file, invalid json
+    /// is reported as PatternError and no task is started
+    pub fn vibrate_pattern_inline(
+        &mut self,
+        speed: Speed,
+        duration: Duration,
+        json: &str,
+        body_parts: Vec<String>,
+    ) -> i32 {
+        if !self.validate_vibrate(duration, &body_parts) {
+            return ERROR_HANDLE;
+        }
+        match parse_pattern(json, INLINE_PATTERN_NAME) {
+            Ok(fscript) => self.scalar(
+                Task::Pattern(speed, ActuatorType::Vibrate, INLINE_PATTERN_NAME.into()),
+                duration,
+                body_parts,
+                Some(fscript),
+                &[ActuatorType::Vibrate],
+            ),
+            Err(err) => {
+                error!("invalid inline pattern {}", err);
+                self.last_result = TkResult::PatternError;
+                let event = TkConnectionEvent::PatternError(INLINE_PATTERN_NAME.into(), err.to_string());
+                try_send_event(&self.client_event_sender, event.clone());
+                try_send_event(&self.status_event_sender, event);
+                ERROR_HANDLE
+            }
+        }
+    }
+
+    /// Plays each pattern on the actuators of its type at the same time, all
+    /// channels share one handle. Position actuators play stroker patterns
+    pub fn multi_pattern(
+        &mut self,
+        speed: Speed,
+        duration: Duration,
+        channels: Vec<(ActuatorType, String)>,
+        body_parts: Vec<String>,
+    ) -> i32 {
+        info!("multi pattern");
         self.scheduler.clean_finished_tasks();
-        let task_clone = task.clone();
+        if !self.check_task_limit() {
+            return ERROR_HANDLE;
+        }
 
         let actuators = self.status.connected_actuators();
+        let mut players = vec![];
+        let mut handle = ERROR_HANDLE;
+        for (actuator_type, pattern_name) in channels.iter() {
+            let is_linear = *actuator_type == ActuatorType::Position;
+            let fscript = match self.load_pattern(None, pattern_name, !is_linear) {
+                Some(fscript) => fscript,
+                None => continue,
+            };
+            let devices = TkParams::filter_devices(
+                &actuators,
+                &body_parts,
+                &[*actuator_type],
+                &self.settings.devices,
+            );
+            if devices.is_empty() {
+                continue;
+            }
+            let devices = self.with_followers(devices);
+            let settings = devices
+                .iter()
+                .map(|x| self.settings.get_event_actuator_settings(x.identifier(), &body_parts))
+                .collect();
+            let player = if handle == ERROR_HANDLE {
+                self.scheduler
+                    .create_player_with_settings(devices, settings)
+            } else {
+                self.scheduler
+                    .create_linked_player(handle, devices, settings)
+                    .expect("handle was just created")
+            };
+            handle = player.handle;
+            players.push((player, fscript, is_linear));
+        }
+        if handle == ERROR_HANDLE {
+            if self.last_result != TkResult::PatternError {
+                self.last_result = TkResult::NoDevices;
+            }
+            return ERROR_HANDLE;
+        }
+        self.last_result = TkResult::Ok;
+        self.task_events.insert(handle, sanitize_name_list(&body_parts));
+        self.set_deadline(handle, duration);
 
-        let devices = TkParams::filter_devices(
-            &actuators,
-            &body_parts,
-            &[ActuatorType::Position],
-            &self.settings.devices,
-        );
-        let settings = devices.iter().map(|x| self.settings.get_or_create(x.identifier()).actuator_settings ).collect();
-        let player = self.scheduler.create_player_with_settings(devices, settings);
-        let handle = player.handle;
-
+        let task = Task::MultiPattern(speed, channels);
+        let actuators: Vec<Arc<Actuator>> =
+            players.iter().flat_map(|x| x.0.actuators.clone()).collect();
         let client_sender_clone = self.client_event_sender.clone();
         let status_sender_clone = self.status_event_sender.clone();
         self.runtime.spawn(async move {
             let now = Instant::now();
             client_sender_clone
                 .send(TkConnectionEvent::ActionStarted(
-                    task_clone.clone(),
-                    player.actuators.clone(),
+                    task.clone(),
+                    actuators.clone(),
                     body_parts,
-                    player.handle,
+                    handle,
                 ))
                 .expect("never full");
-            let result = match task {
-                Task::LinearOscillate(speed, _) => player.play_oscillate_linear(duration, speed, LinearRange::max()).await,
-                _ => panic!(),
-            };
-            let event = match result {
-                Ok(_) => TkConnectionEvent::ActionDone(task_clone, now.elapsed(), handle),
-                Err(err) => TkConnectionEvent::ActionError(actuators[0].clone(), err.to_string()),
-            };
-            client_sender_clone.send(event.clone()).expect("never full");
-            status_sender_clone.send(event.clone()).expect("never full");
+            let results = join_all(players.into_iter().map(
+                |(player, fscript, is_linear)| async move {
+                    match is_linear {
+                        true => player.play_linear(duration, fscript).await,
+                        false => player.play_scalar_pattern(duration, fscript, speed).await,
+                    }
+                },
+            ))
+            .await;
+            let result = results.into_iter().find(|x| x.is_err()).unwrap_or(Ok(()));
+            send_task_result(
+                &client_sender_clone,
+                &status_sender_clone,
+                task,
+                actuators.first(),
+                handle,
+                now,
+                result,
+            );
         });
         handle
     }
 
-    #[instrument(skip(self))]
-    pub fn update(&mut self, handle: i32, speed: Speed) -> bool {
-        info!("update");
-        self.scheduler.clean_finished_tasks();
-        self.scheduler.update_task(handle, speed)
+    /// Reads the pattern from 'pattern_dir' or the pattern path, a missing or
+    /// invalid funscript is reported as PatternError and no task is started
+    pub fn load_pattern(
+        &mut self,
+        pattern_dir: Option<&str>,
+        pattern_name: &str,
+        vibration_pattern: bool,
+    ) -> Option<FScript> {
+        let path = &self.settings.pattern_path;
+        let result = match pattern_dir {
+            Some(dir) => self.patterns.get_from(path, dir, pattern_name, vibration_pattern),
+            None => self.patterns.try_get(path, pattern_name, vibration_pattern),
+        };
+        match result {
+            Ok(fscript) => Some(fscript),
+            Err(err) => {
+                self.last_result = TkResult::PatternError;
+                let event = TkConnectionEvent::PatternError(pattern_name.into(), err.to_string());
+                try_send_event(&self.client_event_sender, event.clone());
+                try_send_event(&self.status_event_sender, event);
+                None
+            }
+        }
     }
 
-    #[instrument(skip(self))]
-    pub fn stop(&mut self, handle: i32) -> bool {
-        info!("stop");
-        self.scheduler.stop_task(handle);
-        true
+    /// Plays the patterns configured for 'event' on all enabled actuators, returns one
+    /// handle per distinct pattern. Linear actuators play the stroker pattern of that name
+    pub fn trigger_event(&mut self, event: &str) -> Vec<i32> {
+        info!("trigger event");
+        let mut patterns: HashMap<(String, bool), Vec<Arc<Actuator>>> = HashMap::new();
+        for actuator in self.status.connected_actuators() {
+            let settings = self.settings.get_or_create(actuator.identifier());
+            if !settings.enabled {
+                continue;
+            }
+            if let Some(pattern) = self.settings.get_event_pattern(actuator.identifier(), event) {
+                let is_linear = actuator.actuator == ActuatorType::Position;
+                patterns.entry((pattern, is_linear)).or_default().push(actuator);
+            }
+        }
+        self.last_result = TkResult::NoDevices;
+        let mut handles = vec![];
+        for ((pattern_name, is_linear), actuators) in
+            patterns.into_iter().sorted_by(|a, b| a.0.cmp(&b.0))
+        {
+            let path = self.settings.pattern_path.clone();
+            let (fscript, duration) = match (
+                self.load_pattern(None, &pattern_name, !is_linear),
+                self.patterns.get_duration(&path, &pattern_name, !is_linear),
+            ) {
+                (Some(fscript), Some(duration)) => (fscript, duration),
+                _ => continue,
+            };
+            let handle =
+                self.pattern_on(actuators, &pattern_name, is_linear, duration, fscript, event);
+            if handle != ERROR_HANDLE {
+                handles.push(handle);
+            }
+        }
+        handles
     }
 
-    #[instrument(skip(self))]
-    pub fn stop_all(&mut self) -> bool {
-        info!("stop all");
-        self.scheduler.stop_all();
-        if self.command_sender.try_send(TkCommand::StopAll).is_err() {
-            error!("Failed to queue stop_all");
-            return false;
+    fn pattern_on(
+        &mut self,
+        devices: Vec<Arc<Actuator>>,
+        pattern_name: &str,
+        is_linear: bool,
+        duration: Duration,
+        fscript: FScript,
+        event: &str,
+    ) -> i32 {
+        self.scheduler.clean_finished_tasks();
+        if !self.check_task_limit() {
+            return ERROR_HANDLE;
         }
-        true
+
+        let task = match is_linear {
+            true => Task::Linear(Speed::max(), pattern_name.into()),
+            false => Task::Pattern(Speed::max(), ActuatorType::Vibrate, pattern_name.into()),
+        };
+        let events = [event.to_owned()];
+        let devices = self.with_followers(devices);
+        let settings = devices
+            .iter()
+            .map(|x| self.settings.get_event_actuator_settings(x.identifier(), &events))
+            .collect();
+        let player = self.scheduler.create_player_with_settings(devices, settings);
+        self.last_result = TkResult::Ok;
+
+        let handle = player.handle;
+        let events = sanitize_name_list(&[event.to_owned()]);
+        self.task_events.insert(handle, events.clone());
+        self.set_deadline(handle, duration);
+        let client_sender_clone = self.client_event_sender.clone();
+        let status_sender_clone = self.status_event_sender.clone();
+        self.runtime.spawn(async move {
+            let now = Instant::now();
+            let actuators = player.actuators.clone();
+            client_sender_clone
+                .send(TkConnectionEvent::ActionStarted(
+                    task.clone(),
+                    player.actuators.clone(),
+                    events,
+                    player.handle,
+                ))
+                .expect("never full");
+            let result = match is_linear {
+                true => player.play_linear(duration, fscript).await,
+                false => player.play_scalar_pattern(duration, fscript, Speed::max()).await,
+            };
+            send_task_result(
+                &client_sender_clone,
+                &status_sender_clone,
+                task,
+                actuators.first(),
+                handle,
+                now,
+                result,
+            );
+        });
+        handle
     }
 
-    #[instrument(skip(self))]
-    pub fn disconnect(&mut self) {
-        info!("disconnect");
-        if self.command_sender.try_send(TkCommand::Disconect).is_err() {
-            error!("Failed to send disconnect");
+    pub fn linear_pattern(
+        &mut self,
+        task: Task,
+        duration: Duration,
+        body_parts: Vec<String>,
+        fscript: FScript,
+    ) -> i32 {
+        info!("linear pattern");
+
+        self.scheduler.clean_finished_tasks();
+        if !self.check_task_limit() {
+            return ERROR_HANDLE;
         }
-    }
+        let task_clone = task.clone();
 
-}
+        let actuators = self.status.connected_actuators();
 
-pub fn in_process_connector(
-) -> impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage> {
-    ButtplugInProcessClientConnectorBuilder::default()
-        .server(
-            ButtplugServerBuilder::default()
-                .comm_manager(BtlePlugCommunicationManagerBuilder::default())
-                .finish()
-                .expect("Could not create in-process-server."),
-        )
-        .finish()
-}
+        let devices = TkParams::filter_devices(
+            &actuators,
+            &body_parts,
+            &[ActuatorType::Position],
+            &self.settings.devices,
+        );
+        let devices = self.with_followers(devices);
+        let settings = devices.iter().map(|x| self.settings.get_actuator_settings(x.identifier())).collect();
+        let player = self.scheduler.create_player_with_settings(devices, settings);
+        self.last_result = match player.actuators.is_empty() {
+            true => TkResult::NoDevices,
+            false => TkResult::Ok,
+        };
+        let handle = player.handle;
+        self.task_events.insert(handle, sanitize_name_list(&body_parts));
+        self.set_deadline(handle, duration);
 
-async fn with_connector<T>(connector: T) -> ButtplugClient
-where
-    T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage>
-        + 'static,
-{
-    let buttplug = ButtplugClient::new("Telekinesis");
-    if let Err(err) = buttplug.connect(connector).await {
-        error!("Could not connect client. Error: {}.", err);
+        let client_sender_clone = self.client_event_sender.clone();
+        let status_sender_clone = self.status_event_sender.clone();
+        self.runtime.spawn(async move {
+            let now = Instant::now();
+            client_sender_clone
+                .send(TkConnectionEvent::ActionStarted(
+                    task_clone.clone(),
+                    player.actuators.clone(),
+                    body_parts,
+                    player.handle,
+                ))
+                .expect("never full");
+            let result = match task {
+                Task::Linear(_, _) => player.play_linear(duration, fscript).await,
+                _ => panic!(),
+            };
+            send_task_result(
+                &client_sender_clone,
+                &status_sender_clone,
+                task_clone,
+                actuators.first(),
+                handle,
+                now,
+                result,
+            );
+        });
+        handle
     }
-    buttplug
-}
 
-impl fmt::Debug for Telekinesis {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Telekinesis")
-            .field("settings", &self.settings)
-            .finish()
+    pub fn linear_oscillate(
+        &mut self,
+        task: Task,
+        duration: Duration,
+        _range: &str,
+        body_parts: Vec<String>,
+    ) -> i32 {
+        info!("linear oscillate");
+
+        self.scheduler.clean_finished_tasks();
+        if !self.check_task_limit() {
+            return ERROR_HANDLE;
+        }
+        let task_clone = task.clone();
+
+        let actuators = self.status.connected_actuators();
+
+        let devices = TkParams::filter_devices(
+            &actuators,
+            &body_parts,
+            &[ActuatorType::Position],
+            &self.settings.devices,
+        );
+        let devices = self.with_followers(devices);
+        let settings = devices.iter().map(|x| self.settings.get_actuator_settings(x.identifier())).collect();
+        let player = self.scheduler.create_player_with_settings(devices, settings);
+        self.last_result = match player.actuators.is_empty() {
+            true => TkResult::NoDevices,
+            false => TkResult::Ok,
+        };
+        let handle = player.handle;
+        self.task_events.insert(handle, sanitize_name_list(&body_parts));
+        self.set_deadline(handle, duration);
+
+        let client_sender_clone = self.client_event_sender.clone();
+        let status_sender_clone = self.status_event_sender.clone();
+        self.runtime.spawn(async move {
+            let now = Instant::now();
+            client_sender_clone
+                .send(TkConnectionEvent::ActionStarted(
+                    task_clone.clone(),
+                    player.actuators.clone(),
+                    body_parts,
+                    player.handle,
+                ))
+                .expect("never full");
+            let result = match task {
+                Task::LinearOscillate(speed, _) => player.play_oscillate_linear(duration, speed, LinearRange::max()).await,
+                _ => panic!(),
+            };
+            send_task_result(
+                &client_sender_clone,
+                &status_sender_clone,
+                task_clone,
+                actuators.first(),
+                handle,
+                now,
+                result,
+            );
+        });
+        handle
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::pattern::read_pattern;
-    use crate::status::TkConnectionStatus;
-    use crate::telekinesis::in_process_connector;
-    use crate::*;
-    use bp_fakes::{scalar, FakeConnectorCallRegistry, FakeDeviceConnector};
-    use bp_scheduler::speed::Speed;
-    use buttplug::core::message::{ActuatorType, DeviceAdded};
-    use std::time::Instant;
-    use std::{thread, time::Duration, vec};
+    /// Moves the linear devices to 'position' within 'duration_ms'
+    pub fn linear_stroke(&mut self, position: f64, duration_ms: u32, body_parts: Vec<String>) -> i32 {
+        info!("linear stroke");
 
-    use super::Telekinesis;
+        self.scheduler.clean_finished_tasks();
+        if !self.check_task_limit() {
+            return ERROR_HANDLE;
+        }
+        let task = Task::LinearStroke(position, duration_ms);
 
-    macro_rules! assert_timeout {
-        ($cond:expr, $arg:tt) => {
-            // starting time
-            let start: Instant = Instant::now();
-            while !$cond {
-                thread::sleep(Duration::from_millis(10));
-                if start.elapsed().as_secs() > 5 {
-                    panic!($arg);
+        let actuators = self.status.connected_actuators();
+
+        let devices = TkParams::filter_devices(
+            &actuators,
+            &body_parts,
+            &[ActuatorType::Position],
+            &self.settings.devices,
+        );
+        let devices = self.with_followers(devices);
+        let settings = devices.iter().map(|x| self.settings.get_actuator_settings(x.identifier())).collect();
+        let player = self.scheduler.create_player_with_settings(devices, settings);
+        self.last_result = match player.actuators.is_empty() {
+            true => TkResult::NoDevices,
+            false => TkResult::Ok,
+        };
+        let handle = player.handle;
+        self.task_events.insert(handle, sanitize_name_list(&body_parts));
+        self.set_deadline(handle, Duration::from_millis(duration_ms.into()));
+
+        let client_sender_clone = self.client_event_sender.clone();
+        let status_sender_clone = self.status_event_sender.clone();
+        self.runtime.spawn(async move {
+            let now = Instant::now();
+            client_sender_clone
+                .send(TkConnectionEvent::ActionStarted(
+                    task.clone(),
+                    player.actuators.clone(),
+                    body_parts,
+                    player.handle,
+                ))
+                .expect("never full");
+            let result = player.play_linear_stroke(position, duration_ms).await;
+            send_task_result(
+                &client_sender_clone,
+                &status_sender_clone,
+                task,
+                actuators.first(),
+                handle,
+                now,
+                result,
+            );
+        });
+        handle
+    }
+
+    /// True within 'device_ready_grace_ms' after connecting
+    fn awaiting_devices(&self) -> bool {
+        self.settings.device_ready_grace_ms > 0
+            && self.connected_at.elapsed() < Duration::from_millis(self.settings.device_ready_grace_ms)
+    }
+
+    /// Resolves to the matching actuators once a device appears, or None when the
+    /// grace period ends first
+    fn await_devices(
+        &mut self,
+        body_parts: Vec<String>,
+        actuator_types: Vec<ActuatorType>,
+    ) -> impl Future<Output = Option<(Vec<Arc<Actuator>>, Vec<ActuatorSettings>)>> {
+        // subscribe before reading the status, so no device is missed in between
+        let mut events = self.status_event_sender.subscribe();
+        let mut actuators = self.status.connected_actuators();
+        let mut settings = self.settings.clone();
+        let deadline = self.connected_at + Duration::from_millis(settings.device_ready_grace_ms);
+        info!(?body_parts, "no devices yet, waiting for devices");
+        let wait = async move {
+            loop {
+                let devices =
+                    TkParams::filter_devices(&actuators, &body_parts, &actuator_types, &settings.devices);
+                if !devices.is_empty() {
+                    let devices = add_followers(&settings, &actuators, devices);
+                    let device_settings = devices
+                        .iter()
+                        .map(|x| settings.get_event_actuator_settings(x.identifier(), &body_parts))
+                        .collect();
+                    return (devices, device_settings);
+                }
+                match events.recv().await {
+                    Ok(TkConnectionEvent::DeviceAdded(device)) => {
+                        actuators.extend(get_actuators(vec![device]))
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => std::future::pending().await,
                 }
             }
         };
+        async move { timeout_at(deadline.into(), wait).await.ok() }
+    }
+
+    /// False if a vibration would be a no-op because of a zero duration
+    /// or because no enabled vibrator matches 'body_parts'
+    fn validate_vibrate(&mut self, duration: Duration, body_parts: &[String]) -> bool {
+        let devices = TkParams::filter_devices(
+            &self.status.connected_actuators(),
+            body_parts,
+            &[ActuatorType::Vibrate],
+            &self.settings.devices,
+        );
+        let rejected = match (duration.is_zero(), devices.is_empty()) {
+            (true, _) => TkResult::InvalidDuration,
+            (false, true) if self.awaiting_devices() => return true,
+            (false, true) => TkResult::NoDevices,
+            (false, false) => return true,
+        };
+        error!(?duration, ?body_parts, "rejected vibration: {}", rejected);
+        self.last_result = rejected;
+        let event = TkConnectionEvent::CommandRejected(ERROR_HANDLE);
+        try_send_event(&self.client_event_sender, event.clone());
+        try_send_event(&self.status_event_sender, event);
+        false
+    }
+
+    /// Makes room for a new task according to the overflow policy,
+    /// returns false if the new task must be rejected
+    fn check_task_limit(&mut self) -> bool {
+        let limit = &self.settings.task_limit;
+        if self.scheduler.task_count() < limit.max_active {
+            return true;
+        }
+        let rejected = match limit.overflow {
+            TkOverflowPolicy::RejectNew => None,
+            TkOverflowPolicy::DropOldest => self.scheduler.oldest_task(),
+        };
+        error!(?rejected, "task limit of {} reached", limit.max_active);
+        let event = TkConnectionEvent::CommandRejected(rejected.unwrap_or(ERROR_HANDLE));
+        try_send_event(&self.client_event_sender, event.clone());
+        try_send_event(&self.status_event_sender, event);
+        match rejected {
+            Some(handle) => {
+                self.scheduler.stop_task(handle);
+                self.task_events.remove(&handle);
+                true
+            }
+            None => {
+                self.last_result = TkResult::TaskLimit;
+                false
+            }
+        }
+    }
+
+    /// Rotates all enabled rotators matching 'body_parts' in one direction,
+    /// only rotators controlled with RotateCmd support counterclockwise
+    pub fn rotate_directional(
+        &mut self,
+        speed: Speed,
+        clockwise: bool,
+        duration: Duration,
+        body_parts: Vec<String>,
+    ) -> i32 {
+        self.scalar(
+            Task::Rotate(speed, clockwise),
+            duration,
+            body_parts,
+            None,
+            &[ActuatorType::Rotate],
+        )
+    }
+
+    /// Reverses the rotators of a running task, they keep their speed
+    pub fn set_direction(&mut self, handle: i32, clockwise: bool) -> bool {
+        info!("set direction");
+        self.scheduler.clean_finished_tasks();
+        let updated = self.scheduler.set_direction(handle, clockwise);
+        self.last_result = match updated {
+            true => TkResult::Ok,
+            false => TkResult::InvalidHandle,
+        };
+        updated
+    }
+
+    #[instrument(skip(self))]
+    pub fn update(&mut self, handle: i32, speed: Speed) -> bool {
+        info!("update");
+        self.scheduler.clean_finished_tasks();
+        let updated = self.scheduler.update_task(handle, speed);
+        self.last_result = match updated {
+            true => TkResult::Ok,
+            false => TkResult::InvalidHandle,
+        };
+        updated
+    }
+
+    #[instrument(skip(self))]
+    pub fn stop(&mut self, handle: i32) -> bool {
+        info!("stop");
+        self.scheduler.clean_finished_tasks();
+        self.last_result = match self.scheduler.has_task(handle) {
+            true => TkResult::Ok,
+            false => TkResult::InvalidHandle,
+        };
+        self.scheduler.stop_task(handle);
+        true
+    }
+
+    /// Stops every task that was started for 'event'
+    #[instrument(skip(self))]
+    pub fn stop_event(&mut self, event: &str) -> bool {
+        info!("stop event");
+        self.scheduler.clean_finished_tasks();
+        self.task_events
+            .retain(|handle, _| self.scheduler.has_task(*handle));
+
+        let event = sanitize_name_list(&[event.to_owned()]).remove(0);
+        let handles: Vec<i32> = self
+            .task_events
+            .iter()
+            .filter(|(_, events)| events.contains(&event))
+            .map(|(handle, _)| *handle)
+            .collect();
+        for handle in handles.iter() {
+            self.task_events.remove(handle);
+            self.scheduler.stop_task(*handle);
+        }
+        !handles.is_empty()
+    }
+
+    /// Stops every task that was started with 'group', returns
+    /// false if no task of the group was running
+    #[instrument(skip(self))]
+    pub fn stop_group(&mut self, group: &str) -> bool {
+        info!("stop group");
+        self.scheduler.clean_finished_tasks();
+        self.task_groups
+            .retain(|handle, _| self.scheduler.has_task(*handle));
+
+        let group = sanitize_name_list(&[group.to_owned()]).remove(0);
+        let handles: Vec<i32> = self
+            .task_groups
+            .iter()
+            .filter(|(_, task_group)| **task_group == group)
+            .map(|(handle, _)| *handle)
+            .collect();
+        for handle in handles.iter() {
+            self.task_groups.remove(handle);
+            self.scheduler.stop_task(*handle);
+        }
+        !handles.is_empty()
+    }
+
+    /// Stops every task that was started to run until 'event', returns
+    /// false if no such task was running
+    #[instrument(skip(self))]
+    pub fn trigger_stop_event(&mut self, event: &str) -> bool {
+        info!("trigger stop event");
+        self.scheduler.clean_finished_tasks();
+        self.task_stop_events
+            .retain(|handle, _| self.scheduler.has_task(*handle));
+
+        let event = sanitize_name_list(&[event.to_owned()]).remove(0);
+        let handles: Vec<i32> = self
+            .task_stop_events
+            .iter()
+            .filter(|(_, stop_event)| **stop_event == event)
+            .map(|(handle, _)| *handle)
+            .collect();
+        for handle in handles.iter() {
+            self.task_stop_events.remove(handle);
+            self.scheduler.stop_task(*handle);
+        }
+        !handles.is_empty()
+    }
+
+    /// Stops every task that controls an actuator of 'actuator_type', tasks that
+    /// control several actuator types at once are stopped entirely
+    #[instrument(skip(self))]
+    pub fn stop_actuator_type(&mut self, actuator_type: ActuatorType) -> bool {
+        info!("stop actuator type");
+        self.scheduler.clean_finished_tasks();
+        let handles = self.scheduler.tasks_using(actuator_type);
+        for handle in handles.iter() {
+            self.task_events.remove(handle);
+            self.task_groups.remove(handle);
+            self.task_stop_events.remove(handle);
+            self.scheduler.stop_task(*handle);
+        }
+        !handles.is_empty()
     }
 
-    impl Telekinesis {
-        pub fn await_connect(&mut self, devices: usize) {
-            assert_timeout!(self.status.actuators().len() >= devices, "Awaiting connect");
-        }
+    /// True if there are events that have not been read from 'connection_events'
+    pub fn events_pending(&self) -> bool {
+        !self.connection_events.is_empty()
+    }
+
+    /// Time until a timed task ends, Duration::MAX for tasks without a duration
+    /// and None if the task is unknown or has already ended
+    pub fn get_handle_remaining(&mut self, handle: i32) -> Option<Duration> {
+        self.scheduler.clean_finished_tasks();
+        self.task_deadlines
+            .retain(|handle, _| self.scheduler.has_task(*handle));
+        if !self.scheduler.has_task(handle) {
+            return None;
+        }
+        let now = self.paused_at.unwrap_or_else(Instant::now);
+        match self.task_deadlines.get(&handle) {
+            Some(deadline) => Some(deadline.saturating_duration_since(now)),
+            None => Some(Duration::MAX),
+        }
+    }
+
+    fn set_deadline(&mut self, handle: i32, duration: Duration) {
+        if let Some(deadline) = Instant::now().checked_add(duration) {
+            self.task_deadlines.insert(handle, deadline);
+        }
+    }
+
+    /// Stops all devices without ending the running tasks, returns false if
+    /// already paused
+    #[instrument(skip(self))]
+    pub fn pause_all(&mut self) -> bool {
+        info!("pause all");
+        if !self.scheduler.pause_all() {
+            return false;
+        }
+        self.paused_at = Some(Instant::now());
+        true
+    }
+
+    /// Continues all paused tasks with their last speed and remaining duration
+    #[instrument(skip(self))]
+    pub fn resume_all(&mut self) -> bool {
+        info!("resume all");
+        if !self.scheduler.resume_all() {
+            return false;
+        }
+        if let Some(paused_at) = self.paused_at.take() {
+            let paused = paused_at.elapsed();
+            for deadline in self.task_deadlines.values_mut() {
+                *deadline = deadline.checked_add(paused).unwrap_or(*deadline);
+            }
+        }
+        true
+    }
+
+    #[instrument(skip(self))]
+    pub fn stop_all(&mut self) -> bool {
+        info!("stop all");
+        self.scheduler.stop_all();
+        self.paused_at = None;
+        self.task_events.clear();
+        self.task_deadlines.clear();
+        self.task_groups.clear();
+        self.task_stop_events.clear();
+        if self.settings.fade_out_ms > 0 {
+            // the devices are stopped once the running actuators have faded out,
+            // unless a new task was started during the fade
+            let fade_out = Duration::from_millis(self.settings.fade_out_ms);
+            let tasks_created = self.scheduler.subscribe_tasks_created();
+            let command_sender = self.command_sender.clone();
+            self.runtime.spawn(async move {
+                sleep(fade_out).await;
+                if tasks_created.has_changed().unwrap_or(true) {
+                    debug!("task started during fade out, skipping stop all");
+                    return;
+                }
+                if command_sender.send(TkCommand::StopAll).await.is_err() {
+                    error!("Failed to queue stop_all");
+                }
+            });
+            return true;
+        }
+        if !self.send_command(TkCommand::StopAll) {
+            error!("Failed to queue stop_all");
+            return false;
+        }
+        true
+    }
+
+    /// Actuators of all connected devices, devices that are only known
+    /// from the settings are not included
+    pub fn get_actuators(&mut self) -> Vec<Arc<Actuator>> {
+        self.status.connected_actuators()
+    }
+
+    /// Battery level between 0.0 and 1.0, None if the device has no battery sensor
+    /// or does not answer within 'SENSOR_READ_TIMEOUT'
+    #[instrument(skip(self))]
+    pub fn get_device_battery(&mut self, device_name: &str) -> Option<f64> {
+        let device = self.status.get_device(device_name)?;
+        self.read_device_battery(&device)
+    }
+
+    /// Like 'get_device_battery' for a device that is already resolved, e.g. the
+    /// device of an actuator when several devices share the same name
+    pub fn read_device_battery(&mut self, device: &Arc<ButtplugClientDevice>) -> Option<f64> {
+        if !device.has_battery_level() {
+            return None;
+        }
+        match self
+            .runtime
+            .block_on(async { timeout(SENSOR_READ_TIMEOUT, device.battery_level()).await })
+        {
+            Ok(Ok(level)) => {
+                self.battery_levels.insert(device.index(), level);
+                Some(level)
+            }
+            Ok(Err(err)) => {
+                error!("failed reading battery level {:?}", err);
+                None
+            }
+            Err(_) => {
+                error!("reading battery level timed out");
+                None
+            }
+        }
+    }
+
+    /// All connected devices, the battery level is the last one read with
+    /// 'get_device_battery' so this never blocks on the device
+    pub fn get_devices_snapshot(&mut self) -> Vec<TkDeviceSnapshot> {
+        let actuators = self.status.connected_actuators();
+        let mut devices = vec![];
+        for name in self.status.get_device_names() {
+            let device = match self.status.get_device(&name) {
+                Some(device) => device,
+                None => continue,
+            };
+            let enabled = actuators
+                .iter()
+                .filter(|x| x.device.index() == device.index())
+                .any(|x| self.settings.get_device(x.identifier()).map(|x| x.enabled).unwrap_or(false));
+            devices.push(TkDeviceSnapshot {
+                capabilities: self.status.get_device_capabilities(&name),
+                name,
+                index: device.index(),
+                enabled,
+                battery: self.battery_levels.get(&device.index()).copied(),
+            });
+        }
+        devices
+    }
+
+    /// Signal strength in dBm, None if the device does not report its RSSI
+    /// or does not answer within 'SENSOR_READ_TIMEOUT'
+    #[instrument(skip(self))]
+    pub fn get_device_rssi(&mut self, device_name: &str) -> Option<i32> {
+        let device = self.status.get_device(device_name)?;
+        if !device.has_rssi_level() {
+            return None;
+        }
+        match self
+            .runtime
+            .block_on(async { timeout(SENSOR_READ_TIMEOUT, device.rssi_level()).await })
+        {
+            Ok(Ok(level)) => Some(level),
+            Ok(Err(err)) => {
+                error!("failed reading rssi level {:?}", err);
+                None
+            }
+            Err(_) => {
+                error!("reading rssi level timed out");
+                None
+            }
+        }
+    }
+
+    /// Subscribes all sensors of the device, each reading is sent as a
+    /// SensorReading event until 'unsubscribe_sensor' is called or the device
+    /// disconnects. Devices without subscribable sensors send a SensorError
+    #[instrument(skip(self))]
+    pub fn subscribe_sensor(&mut self, device_name: &str) -> bool {
+        // ended subscriptions are cancelled by their task, i.e. when the device disconnected
+        if self
+            .sensor_subscriptions
+            .get(device_name)
+            .is_some_and(|x| !x.is_cancelled())
+        {
+            return true;
+        }
+        let sensors = self
+            .status
+            .get_device(device_name)
+            .map(|device| {
+                let sensors = device.message_attributes().sensor_subscribe_cmd().clone();
+                (device, sensors.unwrap_or_default())
+            })
+            .filter(|(_, sensors)| !sensors.is_empty());
+        let Some((device, sensors)) = sensors else {
+            error!("device has no sensors");
+            let event = TkConnectionEvent::SensorError(device_name.into(), String::from("No sensors"));
+            try_send_event(&self.client_event_sender, event.clone());
+            try_send_event(&self.status_event_sender, event);
+            return false;
+        };
+
+        let token = CancellationToken::new();
+        self.sensor_subscriptions.insert(device_name.into(), token.clone());
+        let name = String::from(device_name);
+        let client_sender_clone = self.client_event_sender.clone();
+        let status_sender_clone = self.status_event_sender.clone();
+        self.runtime.spawn(async move {
+            // listen before subscribing to not miss the first readings
+            let mut device_events = device.event_stream();
+            for (index, sensor) in sensors.iter().enumerate() {
+                if let Err(err) = device.subscribe_sensor(index as u32, *sensor.sensor_type()).await {
+                    error!("failed subscribing sensor {:?}", err);
+                    let event = TkConnectionEvent::SensorError(name, err.to_string());
+                    try_send_event(&client_sender_clone, event.clone());
+                    try_send_event(&status_sender_clone, event);
+                    token.cancel();
+                    return;
+                }
+            }
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    event = device_events.next() => match event {
+                        Some(ButtplugClientDeviceEvent::Message(ButtplugCurrentSpecServerMessage::SensorReading(reading))) => {
+                            if let Some(value) = reading.data().first() {
+                                try_send_event(&client_sender_clone, TkConnectionEvent::SensorReading(name.clone(), *value));
+                            }
+                        }
+                        Some(ButtplugClientDeviceEvent::Message(_)) => {}
+                        _ => {
+                            token.cancel();
+                            return;
+                        }
+                    }
+                }
+            }
+            for (index, sensor) in sensors.iter().enumerate() {
+                if let Err(err) = device.unsubscribe_sensor(index as u32, *sensor.sensor_type()).await {
+                    error!("failed unsubscribing sensor {:?}", err);
+                }
+            }
+        });
+        true
+    }
+
+    /// Ends the subscription started with 'subscribe_sensor', false if the
+    /// device was not subscribed
+    #[instrument(skip(self))]
+    pub fn unsubscribe_sensor(&mut self, device_name: &str) -> bool {
+        match self.sensor_subscriptions.remove(device_name) {
+            Some(subscription) => {
+                subscription.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops all devices and blocks until the connection is closed (at most 'DISCONNECT_TIMEOUT')
+    #[instrument(skip(self))]
+    pub fn disconnect(&mut self) {
+        info!("disconnect");
+        if let Err(err) = self.close_connection() {
+            error!("{}", err);
+        }
+    }
+
+    fn close_connection(&mut self) -> Result<(), Error> {
+        self.scheduler.stop_all();
+        self.paused_at = None;
+        self.task_events.clear();
+        self.task_groups.clear();
+        self.task_stop_events.clear();
+        for (_, subscription) in self.sensor_subscriptions.drain() {
+            subscription.cancel();
+        }
+        // a closed queue means the connection task has already ended
+        if !self.send_command(TkCommand::Disconect) && self.last_result != TkResult::NotConnected {
+            return Err(anyhow::anyhow!("Failed to send disconnect"));
+        }
+        if let Some(connection_task) = self.connection_task.take() {
+            let abort_handle = connection_task.abort_handle();
+            if self
+                .runtime
+                .block_on(async { timeout(DISCONNECT_TIMEOUT, connection_task).await })
+                .is_err()
+            {
+                abort_handle.abort();
+                return Err(anyhow::anyhow!("timeout waiting for disconnect"));
+            }
+        }
+        try_send_event(&self.client_event_sender, TkConnectionEvent::Disconnected);
+        try_send_event(&self.status_event_sender, TkConnectionEvent::Disconnected);
+        Ok(())
+    }
+
+}
+
+pub fn in_process_connector(
+    managers: &[CommManagerKind],
+) -> impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage> {
+    let mut server = ButtplugServerBuilder::default();
+    for manager in managers.iter().unique() {
+        match manager {
+            CommManagerKind::Btle => {
+                server.comm_manager(BtlePlugCommunicationManagerBuilder::default());
+            }
+            CommManagerKind::Serial => {
+                server.comm_manager(SerialPortCommunicationManagerBuilder::default());
+            }
+            CommManagerKind::LovenseDongle => {
+                server.comm_manager(LovenseSerialDongleCommunicationManagerBuilder::default());
+            }
+            CommManagerKind::LovenseConnect => {
+                server.comm_manager(LovenseConnectServiceCommunicationManagerBuilder::default());
+            }
+            #[cfg(target_os = "windows")]
+            CommManagerKind::XInput => {
+                server.comm_manager(
+                    buttplug::server::device::hardware::communication::xinput::XInputDeviceCommunicationManagerBuilder::default(),
+                );
+            }
+            #[cfg(not(target_os = "windows"))]
+            CommManagerKind::XInput => error!("XInput is only supported on windows"),
+        }
+    }
+    ButtplugInProcessClientConnectorBuilder::default()
+        .server(server.finish().expect("Could not create in-process-server."))
+        .finish()
+}
+
+fn new_websocket_connector(
+    uri: &str,
+) -> impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage> {
+    let transport = if uri.starts_with("wss://") {
+        ButtplugWebsocketClientTransport::new_secure_connector(uri, false)
+    } else {
+        ButtplugWebsocketClientTransport::new_insecure_connector(uri)
+    };
+    ButtplugRemoteClientConnector::<_, ButtplugClientJSONSerializer>::new(transport)
+}
+
+/// Adds the 'connected' actuators of all devices that follow a device of 'actuators'
+fn add_followers(
+    settings: &TkSettings,
+    connected: &[Arc<Actuator>],
+    mut actuators: Vec<Arc<Actuator>>,
+) -> Vec<Arc<Actuator>> {
+    if settings.device_links.is_empty() {
+        return actuators;
+    }
+    for actuator in actuators.clone() {
+        for follower in settings.get_followers(actuator.device.name()) {
+            for linked in connected.iter().filter(|x| {
+                x.device.name().to_lowercase() == follower && x.actuator == actuator.actuator
+            }) {
+                if !actuators.iter().any(|x| x.identifier() == linked.identifier()) {
+                    actuators.push(linked.clone());
+                }
+            }
+        }
+    }
+    actuators
+}
+
+/// Reports that the task 'handle' which was started at 'started' ended with 'result',
+/// errors of tasks without actuators are only logged
+fn send_task_result(
+    client_sender: &TkEventSender,
+    status_sender: &TkEventSender,
+    task: Task,
+    actuator: Option<&Arc<Actuator>>,
+    handle: i32,
+    started: Instant,
+    result: Result<(), ButtplugClientError>,
+) {
+    let event = match (result, actuator) {
+        (Ok(_), _) => Some(TkConnectionEvent::ActionDone(task, started.elapsed(), handle)),
+        (Err(err), Some(actuator)) => Some(TkConnectionEvent::ActionError(actuator.clone(), err.to_string())),
+        (Err(err), None) => {
+            error!(handle, "task without actuators failed: {}", err);
+            None
+        }
+    };
+    if let Some(event) = event {
+        client_sender.send(event.clone()).expect("never full");
+        status_sender.send(event).expect("never full");
+    }
+    client_sender
+        .send(TkConnectionEvent::HandleFinished(handle))
+        .expect("never full");
+}
+
+async fn with_connector<T>(connector: T) -> ButtplugClient
+where
+    T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage>
+        + 'static,
+{
+    let buttplug = ButtplugClient::new("Telekinesis");
+    if let Err(err) = buttplug.connect(connector).await {
+        error!("Could not connect client. Error: {}.", err);
+    }
+    buttplug
+}
+
+impl fmt::Display for TkResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TkResult::Ok => write!(f, "Ok"),
+            TkResult::NotConnected => write!(f, "NotConnected"),
+            TkResult::QueueFull => write!(f, "QueueFull"),
+            TkResult::NoDevices => write!(f, "NoDevices"),
+            TkResult::InvalidHandle => write!(f, "InvalidHandle"),
+            TkResult::TaskLimit => write!(f, "TaskLimit"),
+            TkResult::PatternError => write!(f, "PatternError"),
+            TkResult::InvalidDuration => write!(f, "InvalidDuration"),
+            TkResult::InvalidSelector => write!(f, "InvalidSelector"),
+            TkResult::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+impl fmt::Display for TkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TkError::RuntimeInit(_) => write!(f, "failed to start the async runtime"),
+        }
+    }
+}
+
+impl std::error::Error for TkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TkError::RuntimeInit(err) => Some(err),
+        }
+    }
+}
+
+fn start_runtime<F>(build: F) -> Result<Runtime, TkError>
+where
+    F: FnOnce() -> std::io::Result<Runtime>,
+{
+    build().map_err(|err| {
+        error!("failed to start the async runtime: {}", err);
+        TkError::RuntimeInit(err)
+    })
+}
+
+impl fmt::Debug for Telekinesis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Telekinesis")
+            .field("settings", &self.settings)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pattern::read_pattern;
+    use crate::status::TkConnectionStatus;
+    use crate::telekinesis::in_process_connector;
+    use crate::*;
+    use bp_fakes::{
+        linear, rotate, scalar, scalars, sensor_vibrator, vibrator_rotator, FakeConnectorCallRegistry,
+        FakeDeviceConnector,
+    };
+    use bp_scheduler::speed::Speed;
+    use buttplug::core::message::{ActuatorType, ButtplugSpecV3ClientMessage, DeviceAdded, SensorType};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+    use std::{thread, time::Duration, vec};
+
+    use super::{start_runtime, try_send_event, Telekinesis, TkError, TkResult};
+    use crate::settings::{CommManagerKind, TkOverflowPolicy};
+
+    macro_rules! assert_timeout {
+        ($cond:expr, $arg:tt) => {
+            // starting time
+            let start: Instant = Instant::now();
+            while !$cond {
+                thread::sleep(Duration::from_millis(10));
+                if start.elapsed().as_secs() > 5 {
+                    panic!($arg);
+                }
+            }
+        };
+    }
+
+    impl Telekinesis {
+        pub fn await_connect(&mut self, devices: usize) {
+            assert_timeout!(self.status.actuators().len() >= devices, "Awaiting connect");
+        }
+    }
+
+    /// Vibrate
+
+    #[test]
+    fn vibrate_infinitely_and_then_stop() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let handle = tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+
+        thread::sleep(Duration::from_secs(1));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+
+        tk.stop(handle);
+        thread::sleep(Duration::from_secs(1));
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+    }
+
+    #[test]
+    fn vibrate_timed_starts_and_stops_once() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(300),
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        assert_eq!(calls.len(), 2);
+        calls[0].assert_strenth(1.0);
+        calls[1].assert_strenth(0.0);
+    }
+
+    #[test]
+    fn remaining_duration_of_timed_and_infinite_tasks() {
+        // arrange
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let timed = tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_secs(5),
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        let infinite = tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(100));
+
+        // assert
+        let remaining = tk.get_handle_remaining(timed).unwrap();
+        assert!(remaining <= Duration::from_millis(4900) && remaining > Duration::from_millis(4700));
+        assert_eq!(tk.get_handle_remaining(infinite), Some(Duration::MAX));
+        tk.stop(infinite);
+        assert_eq!(tk.get_handle_remaining(infinite), None);
+        assert_eq!(tk.get_handle_remaining(-5), None);
+    }
+
+    #[test]
+    fn pause_all_freezes_timed_vibration() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let handle = tk.vibrate(Speed::new(50), Duration::from_millis(500), vec![]);
+        thread::sleep(Duration::from_millis(200));
+
+        // act
+        assert!(tk.pause_all());
+        assert!(!tk.pause_all());
+        thread::sleep(Duration::from_secs(1));
+        let remaining = tk.get_handle_remaining(handle).unwrap();
+        assert!(tk.resume_all());
+        thread::sleep(Duration::from_millis(600));
+
+        // assert
+        assert!(remaining > Duration::from_millis(250) && remaining <= Duration::from_millis(300));
+        let calls = call_registry.get_device(1);
+        assert_eq!(calls.len(), 4);
+        calls[0].assert_strenth(0.5);
+        calls[1].assert_strenth(0.0);
+        calls[2].assert_strenth(0.5);
+        calls[3].assert_strenth(0.0);
+        let active = (calls[1].time - calls[0].time) + (calls[3].time - calls[2].time);
+        assert!(
+            active > Duration::from_millis(450) && active < Duration::from_millis(600),
+            "active {:?}",
+            active
+        );
+        assert!(calls[2].time - calls[1].time > Duration::from_millis(900));
+    }
+
+    #[test]
+    fn stop_all_ends_pause() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.vibrate(Speed::new(50), Duration::from_secs(10), vec![]);
+        assert!(tk.pause_all());
+
+        // act
+        tk.stop_all();
+        thread::sleep(Duration::from_millis(100));
+        tk.vibrate(Speed::new(70), Duration::from_millis(200), vec![]);
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        assert!(tk.pause_all());
+        let calls = call_registry.get_device(1);
+        assert!(calls.iter().any(|x| x.get_strength() == 0.7), "{:?}", calls);
+    }
+
+    #[test]
+    fn vibrate_delayed_starts_after_delay() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let start = Instant::now();
+        tk.vibrate_delayed(
+            Speed::max(),
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(200));
+        let calls_during_delay = call_registry.get_device(1).len();
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        assert_eq!(calls_during_delay, 0);
+        call_registry.get_device(1)[0].assert_strenth(1.0).assert_time(300, start);
+        call_registry.get_device(1)[1].assert_strenth(0.0).assert_time(400, start);
+    }
+
+    #[test]
+    fn vibrate_delayed_stopped_before_start_does_nothing() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let handle = tk.vibrate_delayed(
+            Speed::max(),
+            Duration::from_millis(100),
+            Duration::from_millis(200),
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(50));
+        tk.stop(handle);
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        call_registry.assert_unused(1);
+    }
+
+    #[test]
+    fn multi_pattern_plays_each_channel_on_its_actuator() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![vibrator_rotator(1, "vibrot1")], None);
+        let tmp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Vib.vibrator.funscript"),
+            r#"{"actions":[{"at":0,"pos":80},{"at":1000,"pos":80}]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Rot.vibrator.funscript"),
+            r#"{"actions":[{"at":0,"pos":30},{"at":1000,"pos":30}]}"#,
+        )
+        .unwrap();
+        tk.settings.pattern_path = String::from(tmp_dir.path().to_str().unwrap());
+
+        // act
+        let handle = tk.multi_pattern(
+            Speed::max(),
+            Duration::MAX,
+            vec![
+                (ActuatorType::Vibrate, String::from("Vib")),
+                (ActuatorType::Rotate, String::from("Rot")),
+            ],
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(200));
+        tk.stop(handle);
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        let vibrate = calls
+            .iter()
+            .find(|x| matches!(x.message, ButtplugSpecV3ClientMessage::ScalarCmd(_)))
+            .unwrap();
+        let rotate = calls
+            .iter()
+            .find(|x| matches!(x.message, ButtplugSpecV3ClientMessage::RotateCmd(_)))
+            .unwrap();
+        vibrate.assert_strenth(0.8);
+        rotate.assert_rotation(0.3);
+        assert!(!tk.scheduler.has_task(handle));
+    }
+
+    #[test]
+    fn vibrate_linear_then_cancel() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        thread::sleep(Duration::from_secs(1));
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_secs(1),
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_secs(2));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        tk.stop_all();
+
+        thread::sleep(Duration::from_secs(1));
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+    }
+
+    #[test]
+    fn test_connection_simulates_configured_devices() {
+        // arrange
+        let settings = TkSettings {
+            connection: TkConnectionType::Test,
+            test_devices: vec![String::from("vib1"), String::from("vib2")],
+            ..Default::default()
+        };
+        let mut tk = Telekinesis::connect(settings).unwrap();
+        tk.await_connect(2);
+        for actuator_id in tk.status.get_known_actuator_ids() {
+            tk.settings.set_enabled(&actuator_id, true);
+        }
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(50),
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        let test_calls = tk.test_calls.as_ref().unwrap();
+        for device in [1, 2] {
+            test_calls.get_device(device)[0].assert_strenth(1.0);
+            test_calls.get_device(device)[1].assert_strenth(0.0);
+        }
+    }
+
+    #[test]
+    fn vibrate_all_demo_vibrators() {
+        // arrange
+        let (connector, call_registry) = FakeDeviceConnector::device_demo();
+        let count = connector.devices.len();
+
+        // act
+        let mut tk =
+            Telekinesis::connect_with(|| async move { connector }, None, TkConnectionType::Test)
+                .unwrap();
+        tk.await_connect(count);
+        for actuator_id in tk.status.get_known_actuator_ids() {
+            tk.settings.set_enabled(&actuator_id, true);
+        }
+        tk.scalar(
+            Task::Scalar(Speed::new(100)),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+
+        // assert
+        thread::sleep(Duration::from_millis(500));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+        call_registry.assert_unused(4); // linear
+        call_registry.assert_unused(7); // rotator
+    }
+
+    #[test]
+    fn vibrate_non_existing_device() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(1),
+            vec![String::from("does not exist")],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(50));
+
+        // assert
+        call_registry.assert_unused(1);
+    }
+
+    #[test]
+    fn settings_only_vibrate_enabled_devices() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+                scalar(3, "vib3", ActuatorType::Vibrate),
+            ],
+            None,
+        );
+        tk.settings.set_enabled("vib2 (Vibrate)", false);
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+        call_registry.get_device(3)[0].assert_strenth(1.0);
+        call_registry.get_device(3)[1].assert_strenth(0.0);
+        call_registry.assert_unused(2);
+    }
+
+    #[test]
+    fn last_result_reports_failure_reason() {
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        let handle = tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        assert_eq!(tk.last_result, TkResult::Ok);
+
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ActuatorType::Inflate],
+        );
+        assert_eq!(tk.last_result, TkResult::NoDevices);
+
+        tk.update(handle + 100, Speed::min());
+        assert_eq!(tk.last_result, TkResult::InvalidHandle);
+        tk.stop(handle);
+        assert_eq!(tk.last_result, TkResult::Ok);
+        tk.stop(handle);
+        assert_eq!(tk.last_result, TkResult::InvalidHandle);
+
+        tk.disconnect();
+        thread::sleep(Duration::from_millis(200));
+        assert!(!tk.stop_all());
+        assert_eq!(tk.last_result, TkResult::NotConnected);
+    }
+
+    #[test]
+    fn vibrate_only_uses_enabled_motor_of_device() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalars(1, "vib1", ActuatorType::Vibrate, 2)], None);
+        tk.settings.set_enabled("vib1 (Vibrate #1)", false);
+
+        // act
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![]);
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strengths(vec![(0, 1.0)]);
+        calls[1].assert_strengths(vec![(0, 0.0)]);
+        assert_eq!(calls.len(), 2);
+    }
+
+    #[test]
+    fn heartbeat_is_sent_periodically() {
+        // arrange
+        let settings = TkSettings {
+            heartbeat_ms: Some(50),
+            ..Default::default()
+        };
+
+        // act
+        let (tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        let heartbeats: Vec<f64> = tk
+            .connection_events
+            .try_iter()
+            .filter_map(|x| match x {
+                TkConnectionEvent::Heartbeat(uptime) => Some(uptime.as_secs_f64()),
+                _ => None,
+            })
+            .collect();
+        assert!(heartbeats.len() >= 2, "got {} heartbeats", heartbeats.len());
+        assert!(heartbeats[1] > heartbeats[0]);
+    }
+
+    #[test]
+    fn heartbeat_stops_on_disconnect() {
+        // arrange
+        let settings = TkSettings {
+            heartbeat_ms: Some(50),
+            ..Default::default()
+        };
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+
+        // act
+        tk.disconnect();
+        while tk.connection_events.try_recv().is_ok() {}
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert!(!tk
+            .connection_events
+            .try_iter()
+            .any(|x| matches!(x, TkConnectionEvent::Heartbeat(_))));
+    }
+
+    #[test]
+    fn stop_actuator_type_only_stops_that_type() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![vibrator_rotator(1, "vibrot1")], None);
+        let vibrate = tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        let rotate = tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ActuatorType::Rotate],
+        );
+        thread::sleep(Duration::from_millis(200));
+
+        // act
+        assert!(tk.stop_actuator_type(ActuatorType::Rotate));
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert!(tk.scheduler.has_task(vibrate));
+        assert!(!tk.scheduler.has_task(rotate));
+        let calls = call_registry.get_device(1);
+        let last_rotate = calls
+            .iter()
+            .rfind(|x| matches!(x.message, ButtplugSpecV3ClientMessage::RotateCmd(_)))
+            .unwrap();
+        last_rotate.assert_rotation(0.0);
+        let scalar_calls: Vec<_> = calls
+            .iter()
+            .filter(|x| matches!(x.message, ButtplugSpecV3ClientMessage::ScalarCmd(_)))
+            .collect();
+        assert_eq!(scalar_calls.len(), 1);
+        scalar_calls[0].assert_strenth(1.0);
+        assert!(!tk.stop_actuator_type(ActuatorType::Rotate));
+    }
+
+    #[test]
+    fn fade_out_when_task_ends() {
+        // arrange
+        let settings = TkSettings {
+            fade_out_ms: 200,
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+
+        // act
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![]);
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        let strengths: Vec<f64> = call_registry
+            .get_device(1)
+            .iter()
+            .map(|x| x.get_strength())
+            .collect();
+        assert!(strengths.len() > 3);
+        assert_eq!(strengths[0], 1.0);
+        assert!(strengths.windows(2).all(|x| x[1] < x[0]));
+        assert_eq!(*strengths.last().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn fade_out_before_stop_all() {
+        // arrange
+        let settings = TkSettings {
+            fade_out_ms: 200,
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+        tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
+
+        // act
+        tk.stop_all();
+        thread::sleep(Duration::from_millis(100));
+        let stop_calls_during_fade = call_registry.get_stop_all_calls();
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        let strengths: Vec<f64> = call_registry
+            .get_device(1)
+            .iter()
+            .map(|x| x.get_strength())
+            .collect();
+        assert!(strengths.len() > 3);
+        assert!(strengths.windows(2).all(|x| x[1] < x[0]));
+        assert_eq!(*strengths.last().unwrap(), 0.0);
+        assert_eq!(stop_calls_during_fade, 0);
+        assert_eq!(call_registry.get_stop_all_calls(), 1);
+    }
+
+    #[test]
+    fn vibrate_during_stop_all_fade_out_keeps_running() {
+        // arrange
+        let settings = TkSettings {
+            fade_out_ms: 200,
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+        tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
+
+        // act
+        tk.stop_all();
+        thread::sleep(Duration::from_millis(50));
+        tk.vibrate(Speed::new(50), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        call_registry.get_device(1).last().unwrap().assert_strenth(0.5);
+        assert_eq!(call_registry.get_stop_all_calls(), 0);
+    }
+
+    #[test]
+    fn crossfade_when_new_task_takes_over() {
+        // arrange
+        let settings = TkSettings {
+            crossfade_ms: 200,
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+        tk.vibrate(Speed::new(20), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
+
+        // act
+        tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        let strengths: Vec<f64> = call_registry
+            .get_device(1)
+            .iter()
+            .map(|x| x.get_strength())
+            .collect();
+        assert_eq!(strengths[0], 0.2);
+        assert!(strengths.iter().any(|x| *x > 0.2 && *x < 1.0), "{:?}", strengths);
+        assert_eq!(*strengths.last().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn stop_group_only_stops_tasks_of_that_group() {
+        // arrange
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let scene_1 = tk.vibrate_grouped("Scene", Speed::new(10), Duration::MAX, vec![]);
+        let scene_2 = tk.vibrate_grouped("scene", Speed::new(20), Duration::MAX, vec![]);
+        let ungrouped = tk.vibrate(Speed::new(30), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(200));
+
+        // act
+        assert!(tk.stop_group(" SCENE "));
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert!(!tk.scheduler.has_task(scene_1));
+        assert!(!tk.scheduler.has_task(scene_2));
+        assert!(tk.scheduler.has_task(ungrouped));
+        assert!(!tk.stop_group("scene"));
+    }
+
+    #[test]
+    fn vibrate_until_runs_until_stop_event() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let handle = tk.vibrate_until("Combat_End", Speed::max(), vec![]);
+        thread::sleep(Duration::from_millis(200));
+        assert!(!tk.trigger_stop_event("other"));
+        thread::sleep(Duration::from_millis(200));
+        assert!(tk.scheduler.has_task(handle));
+        assert_eq!(call_registry.get_device(1).len(), 1);
+
+        // act
+        assert!(tk.trigger_stop_event("combat_end"));
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert!(!tk.scheduler.has_task(handle));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+        assert!(!tk.trigger_stop_event("combat_end"));
+    }
+
+    #[test]
+    fn stop_event_only_stops_tasks_of_that_event() {
+        // arrange
+        let mut settings = TkSettings::default();
+        settings.set_events("vib1 (Vibrate)", &[String::from("combat")]);
+        settings.set_events("vib2 (Vibrate)", &[String::from("magic")]);
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            Some(settings),
+        );
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![String::from("combat")],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![String::from("magic")],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(200));
+
+        // act
+        assert!(tk.stop_event(" Combat "));
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+        assert_eq!(call_registry.get_device(2).len(), 1);
+        assert!(!tk.stop_event("combat"));
+    }
+
+    #[test]
+    fn vibrate_actuator_only_moves_selected_motor() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalars(1, "vib1", ActuatorType::Vibrate, 2)], None);
+
+        // act
+        tk.vibrate_actuator("vib1 (Vibrate #1)", Speed::max(), Duration::from_millis(100));
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        call_registry.get_device(1)[0].assert_strengths(vec![(1, 1.0)]);
+        call_registry.get_device(1)[1].assert_strengths(vec![(1, 0.0)]);
+    }
+
+    #[test]
+    fn vibrate_devices_only_moves_named_devices() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+        );
+
+        // act
+        tk.vibrate_devices(
+            vec![String::from(" VIB2 "), String::from("does not exist")],
+            Speed::max(),
+            Duration::from_millis(100),
+        );
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        call_registry.assert_unused(1);
+        call_registry.get_device(2)[0].assert_strenth(1.0);
+        call_registry.get_device(2)[1].assert_strenth(0.0);
+    }
+
+    #[test]
+    fn vibrate_only_vibrates_vibrators() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                linear(2, "lin1"),
+            ],
+            None,
+        );
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.assert_unused(2);
+    }
+
+    #[test]
+    fn task_limit_rejects_new_tasks() {
+        // arrange
+        let mut settings = TkSettings::default();
+        settings.task_limit.max_active = 2;
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+
+        // act
+        let handles: Vec<i32> = [10, 20, 30]
+            .iter()
+            .map(|speed| {
+                tk.scalar(
+                    Task::Scalar(Speed::new(*speed)),
+                    Duration::MAX,
+                    vec![],
+                    None,
+                    &[ActuatorType::Vibrate],
+                )
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert_eq!(handles[2], ERROR_HANDLE);
+        assert_eq!(tk.last_result, TkResult::TaskLimit);
+        assert!(tk.scheduler.has_task(handles[0]));
+        assert!(tk.scheduler.has_task(handles[1]));
+        call_registry.get_device(1).last().unwrap().assert_strenth(0.2);
+    }
+
+    #[test]
+    fn task_limit_drops_oldest_task() {
+        // arrange
+        let mut settings = TkSettings::default();
+        settings.task_limit.max_active = 2;
+        settings.task_limit.overflow = TkOverflowPolicy::DropOldest;
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+
+        // act
+        let handles: Vec<i32> = [10, 20, 30]
+            .iter()
+            .map(|speed| {
+                tk.scalar(
+                    Task::Scalar(Speed::new(*speed)),
+                    Duration::MAX,
+                    vec![],
+                    None,
+                    &[ActuatorType::Vibrate],
+                )
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert!(!tk.scheduler.has_task(handles[0]));
+        assert!(tk.scheduler.has_task(handles[1]));
+        assert!(tk.scheduler.has_task(handles[2]));
+        call_registry.get_device(1).last().unwrap().assert_strenth(0.3);
+        let rejected = (0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_CommandRejected")
+            .unwrap();
+        assert_eq!(rejected.num_arg, f64::from(handles[0]));
+    }
+
+    #[test]
+    fn vibrate_rejects_zero_duration() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let handle = tk.vibrate(Speed::max(), Duration::ZERO, vec![]);
+        let pattern_handle = tk.vibrate_pattern(
+            Speed::max(),
+            Duration::ZERO,
+            None,
+            "02_Cruel-Tease",
+            1.0,
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(100));
+
+        // assert
+        assert_eq!(handle, ERROR_HANDLE);
+        assert_eq!(pattern_handle, ERROR_HANDLE);
+        assert_eq!(tk.last_result, TkResult::InvalidDuration);
+        assert_eq!(tk.scheduler.task_count(), 0);
+        call_registry.assert_unused(1);
+        let rejected = (0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_CommandRejected")
+            .unwrap();
+        assert_eq!(rejected.num_arg, f64::from(ERROR_HANDLE));
+    }
+
+    #[test]
+    fn vibrate_rejects_body_parts_without_devices() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.settings.set_events("vib1 (Vibrate)", &[String::from("nipple")]);
+
+        // act
+        let handle = tk.vibrate(Speed::max(), Duration::MAX, vec![String::from("vaginal")]);
+        let pattern_handle = tk.vibrate_pattern(
+            Speed::max(),
+            Duration::MAX,
+            None,
+            "02_Cruel-Tease",
+            1.0,
+            vec![String::from("vaginal")],
+        );
+        thread::sleep(Duration::from_millis(100));
+
+        // assert
+        assert_eq!(handle, ERROR_HANDLE);
+        assert_eq!(pattern_handle, ERROR_HANDLE);
+        assert_eq!(tk.last_result, TkResult::NoDevices);
+        assert_eq!(tk.scheduler.task_count(), 0);
+        call_registry.assert_unused(1);
+    }
+
+    #[test]
+    fn full_command_queue_reports_dropped_commands() {
+        // arrange
+        let settings = TkSettings {
+            command_queue_size: 1,
+            ..Default::default()
+        };
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+
+        // act
+        let queued = (0..100).filter(|_| tk.stop_all()).count();
+
+        // assert
+        assert!(queued < 100);
+        assert_eq!(tk.dropped_commands(), 100 - queued);
+        let dropped = (0..200)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_CommandDropped")
+            .unwrap();
+        assert_eq!(dropped.num_arg, 1.0);
+    }
+
+    #[test]
+    fn ffi_ex_functions_report_full_command_queue() {
+        // arrange
+        let (connector, _) = FakeDeviceConnector::new(vec![]);
+        let tk = Telekinesis::connect_with(
+            || async move {
+                // commands are not read until the connector is ready
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                connector
+            },
+            None,
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        let mut api = crate::tk_new();
+        api.state.lock().unwrap().replace(tk);
+        let stop_all = std::ffi::CString::new("stop_all").unwrap();
+        let unknown = std::ffi::CString::new("does_not_exist").unwrap();
+
+        // act
+        let results: Vec<TkResult> = (0..TkSettings::default().command_queue_size + 1)
+            .map(|_| unsafe { crate::tk_cmd_ex(&mut api, stop_all.as_ptr()) })
+            .collect();
+
+        // assert
+        assert!(results[..results.len() - 1].iter().all(|x| *x == TkResult::Ok));
+        assert_eq!(results.last(), Some(&TkResult::QueueFull));
+        assert_eq!(crate::tk_last_result(&mut api), TkResult::QueueFull);
+        assert_eq!(unsafe { crate::tk_cmd_ex(&mut api, unknown.as_ptr()) }, TkResult::Failed);
+        assert_eq!(crate::tk_stop_ex(&mut api, 42), TkResult::InvalidHandle);
+    }
+
+    #[test]
+    fn ffi_ex_functions_report_not_connected() {
+        let mut api = crate::tk_new();
+        let stop_all = std::ffi::CString::new("stop_all").unwrap();
+
+        assert_eq!(unsafe { crate::tk_cmd_ex(&mut api, stop_all.as_ptr()) }, TkResult::NotConnected);
+        assert_eq!(crate::tk_last_result(&mut api), TkResult::NotConnected);
+    }
+
+    #[test]
+    fn scan_is_retried_while_command_queue_is_full() {
+        // arrange
+        let settings = TkSettings {
+            command_queue_size: 1,
+            ..Default::default()
+        };
+        let (connector, _) = FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let mut tk = Telekinesis::connect_with(
+            || async move {
+                // commands are not read until the connector is ready
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                connector
+            },
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        assert!(tk.stop_all());
+
+        // act
+        let started = tk.scan_for_devices();
+
+        // assert
+        assert!(started);
+        assert_eq!(tk.dropped_commands(), 0);
+        assert!((0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .any(|x| x.event_name == "Tele_ScanStarted"));
+    }
+
+    #[test]
+    fn scan_failed_when_command_queue_stays_full() {
+        // arrange
+        let settings = TkSettings {
+            command_queue_size: 1,
+            ..Default::default()
+        };
+        let (connector, _) = FakeDeviceConnector::new(vec![]);
+        let mut tk = Telekinesis::connect_with(
+            || async move {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                connector
+            },
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        assert!(tk.stop_all());
+
+        // act
+        tk.scan_for_devices();
+
+        // assert
+        let failed = (0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_ScanFailed")
+            .unwrap();
+        assert_eq!(failed.str_arg, "Command queue full");
+        assert!(!tk.status.is_scanning());
+    }
+
+    #[test]
+    fn vibrate_pattern_with_speed_scale_plays_faster() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let tmp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Steps.vibrator.funscript"),
+            r#"{"actions":[{"at":0,"pos":100},{"at":200,"pos":50},{"at":400,"pos":20},{"at":600,"pos":0}]}"#,
+        )
+        .unwrap();
+        tk.settings.pattern_path = String::from(tmp_dir.path().to_str().unwrap());
+
+        // act
+        let start = Instant::now();
+        tk.vibrate_pattern(
+            Speed::max(),
+            Duration::from_millis(250),
+            None,
+            "Steps",
+            2.0,
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.2).assert_time(200, start);
+    }
+
+    #[test]
+    fn vibrate_pattern_plays_malformed_funscript() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let tmp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Malformed.vibrator.funscript"),
+            r#"{"actions":[{"at":200,"pos":-10},{"at":0,"pos":300},{"at":100,"pos":50}]}"#,
+        )
+        .unwrap();
+        tk.settings.pattern_path = String::from(tmp_dir.path().to_str().unwrap());
+
+        // act
+        let start = Instant::now();
+        tk.vibrate_pattern(
+            Speed::max(),
+            Duration::from_millis(250),
+            None,
+            "Malformed",
+            1.0,
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.0).assert_time(200, start);
+    }
+
+    #[test]
+    fn vibrate_pattern_inline_plays_json() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let start = Instant::now();
+        let handle = tk.vibrate_pattern_inline(
+            Speed::max(),
+            Duration::from_millis(250),
+            r#"{"actions":[{"at":0,"pos":100},{"at":100,"pos":50},{"at":200,"pos":0}]}"#,
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        assert_ne!(handle, ERROR_HANDLE);
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.0).assert_time(200, start);
+    }
+
+    #[test]
+    fn vibrate_pattern_inline_rejects_malformed_json() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let handle = tk.vibrate_pattern_inline(
+            Speed::max(),
+            Duration::from_secs(1),
+            r#"{"actions":[{"at":0,"pos""#,
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert_eq!(handle, ERROR_HANDLE);
+        assert_eq!(tk.last_result, TkResult::PatternError);
+        call_registry.assert_unused(1);
+        let error = (0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_PatternError")
+            .unwrap();
+        assert!(error.str_arg.starts_with("inline: "));
+    }
+
+    #[test]
+    fn vibrate_pattern_plays_csv_pattern() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let tmp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Simple.vibrator.csv"),
+            "0,100\n100,50\n200,0\n",
+        )
+        .unwrap();
+        tk.settings.pattern_path = String::from(tmp_dir.path().to_str().unwrap());
+
+        // act
+        let start = Instant::now();
+        tk.vibrate_pattern(
+            Speed::max(),
+            Duration::from_millis(250),
+            None,
+            "Simple",
+            1.0,
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.0).assert_time(200, start);
+    }
+
+    #[test]
+    fn ffi_speed_is_a_clamped_percentage() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let api = crate::build_api();
+        let vibrate = api.control.iter().find(|x| x.name == "vibrate").unwrap();
+        let events = cxx::CxxVector::<cxx::CxxString>::new();
+
+        // act
+        let handle = (vibrate.exec)(&mut tk, 50, 0.0, "", &events);
+        thread::sleep(Duration::from_millis(100));
+        (api.update.exec)(&mut tk, handle, 200);
+        thread::sleep(Duration::from_millis(100));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(0.5);
+        calls[1].assert_strenth(1.0);
+    }
+
+    #[test]
+    fn vibrate_before_devices_connected_waits_for_device() {
+        // arrange
+        let mut settings = TkSettings {
+            device_ready_grace_ms: 2000,
+            ..Default::default()
+        };
+        settings.set_enabled("vib1 (Vibrate)", true);
+        let (connector, call_registry) = FakeDeviceConnector::new(vec![]);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+
+        // act
+        let handle = tk.vibrate(Speed::new(50), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
+        call_registry.add_device(scalar(1, "vib1", ActuatorType::Vibrate));
+
+        // assert
+        assert!(handle > 0);
+        assert_eq!(tk.last_result, TkResult::Ok);
+        assert_timeout!(!call_registry.get_device(1).is_empty(), "Awaiting vibration");
+        call_registry.get_device(1)[0].assert_strenth(0.5);
+        tk.stop(handle);
+        assert_timeout!(
+            call_registry.get_device(1).last().unwrap().get_strength() == 0.0,
+            "Awaiting stop"
+        );
+    }
+
+    #[test]
+    fn vibrate_before_devices_connected_includes_followers() {
+        // arrange
+        let mut settings = TkSettings {
+            device_ready_grace_ms: 2000,
+            ..Default::default()
+        };
+        settings.set_enabled("vib1 (Vibrate)", true);
+        settings.link_devices("vib1", "vib2");
+        let (connector, call_registry) = FakeDeviceConnector::new(vec![]);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+
+        // act
+        let handle = tk.vibrate(Speed::new(50), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
+        call_registry.add_device(scalar(2, "vib2", ActuatorType::Vibrate));
+        call_registry.add_device(scalar(1, "vib1", ActuatorType::Vibrate));
+
+        // assert
+        assert_timeout!(!call_registry.get_device(1).is_empty(), "Awaiting vibration");
+        assert_timeout!(!call_registry.get_device(2).is_empty(), "Awaiting follower");
+        call_registry.get_device(2)[0].assert_strenth(0.5);
+        tk.stop(handle);
+    }
+
+    #[test]
+    fn vibrate_after_grace_period_is_rejected() {
+        // arrange
+        let settings = TkSettings {
+            device_ready_grace_ms: 100,
+            ..Default::default()
+        };
+        let (connector, _) = FakeDeviceConnector::new(vec![]);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+
+        // act
+        thread::sleep(Duration::from_millis(150));
+        let handle = tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+
+        // assert
+        assert_eq!(handle, ERROR_HANDLE);
+        assert_eq!(tk.last_result, TkResult::NoDevices);
+    }
+
+    #[test]
+    fn vibrate_within_grace_period_ends_when_no_device_appears() {
+        // arrange
+        let settings = TkSettings {
+            device_ready_grace_ms: 200,
+            ..Default::default()
+        };
+        let (connector, _) = FakeDeviceConnector::new(vec![]);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+
+        // act
+        let handle = tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(400));
+
+        // assert
+        assert_ne!(handle, ERROR_HANDLE);
+        tk.scheduler.clean_finished_tasks();
+        assert!(!tk.scheduler.has_task(handle));
+        let events: Vec<TkConnectionEvent> = tk.connection_events.try_iter().collect();
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, TkConnectionEvent::CommandRejected(x) if *x == handle)));
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, TkConnectionEvent::HandleFinished(x) if *x == handle)));
+    }
+
+    #[test]
+    fn handle_finished_for_task_without_actuators() {
+        // arrange
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let handle = tk.vibrate_actuator("unknown (Vibrate)", Speed::max(), Duration::from_millis(100));
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        assert_ne!(handle, ERROR_HANDLE);
+        let events: Vec<TkConnectionEvent> = tk.connection_events.try_iter().collect();
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, TkConnectionEvent::HandleFinished(x) if *x == handle)));
+    }
+
+    #[test]
+    fn handle_finished_after_timed_vibration() {
+        // arrange
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let start = Instant::now();
+        let handle = tk.vibrate(Speed::max(), Duration::from_millis(200), vec![]);
+
+        // assert
+        let finished = (0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_HandleFinished")
+            .unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(finished.num_arg, f64::from(handle));
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_millis(400));
+    }
+
+    #[test]
+    fn json_events_have_kind_and_typed_fields() {
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let device = tk.status.get_device("vib1").unwrap();
+        let actuator = tk.status.get_actuator("vib1 (Vibrate)").unwrap();
+        let task = Task::Scalar(Speed::new(50));
+
+        let cases = vec![
+            (
+                TkConnectionEvent::Connected(String::from("InProcess")),
+                serde_json::json!({ "kind": "Connected", "connection": "InProcess" }),
+            ),
+            (
+                TkConnectionEvent::ConnectionFailure(String::from("err")),
+                serde_json::json!({ "kind": "ConnectionFailure", "reason": "err" }),
+            ),
+            (TkConnectionEvent::Disconnected, serde_json::json!({ "kind": "Disconnected" })),
+            (
+                TkConnectionEvent::DeviceAdded(device.clone()),
+                serde_json::json!({ "kind": "DeviceAdded", "device": "vib1" }),
+            ),
+            (
+                TkConnectionEvent::DeviceRemoved(device),
+                serde_json::json!({ "kind": "DeviceRemoved", "device": "vib1" }),
+            ),
+            (
+                TkConnectionEvent::ActionStarted(task.clone(), vec![actuator.clone()], vec![String::from("a")], 3),
+                serde_json::json!({
+                    "kind": "ActionStarted",
+                    "handle": 3,
+                    "task": "Constant(50%)",
+                    "actuators": ["vib1 (Vibrate)"],
+                    "tags": ["a"]
+                }),
+            ),
+            (
+                TkConnectionEvent::ActionDone(task, Duration::from_millis(1500), 3),
+                serde_json::json!({
+                    "kind": "ActionDone",
+                    "handle": 3,
+                    "task": "Constant(50%)",
+                    "duration_secs": 1.5
+                }),
+            ),
+            (
+                TkConnectionEvent::HandleFinished(3),
+                serde_json::json!({ "kind": "HandleFinished", "handle": 3 }),
+            ),
+            (
+                TkConnectionEvent::ActionError(actuator.clone(), String::from("err")),
+                serde_json::json!({
+                    "kind": "ActionError",
+                    "device": "vib1",
+                    "actuator": "vib1 (Vibrate)",
+                    "reason": "err"
+                }),
+            ),
+            (
+                TkConnectionEvent::ActuatorError(actuator, String::from("err")),
+                serde_json::json!({
+                    "kind": "ActuatorError",
+                    "device": "vib1",
+                    "actuator": "vib1 (Vibrate)",
+                    "reason": "err"
+                }),
+            ),
+            (
+                TkConnectionEvent::Reconnecting(2),
+                serde_json::json!({ "kind": "Reconnecting", "attempt": 2 }),
+            ),
+            (
+                TkConnectionEvent::CommandRejected(4),
+                serde_json::json!({ "kind": "CommandRejected", "handle": 4 }),
+            ),
+            (
+                TkConnectionEvent::CommandDropped(5),
+                serde_json::json!({ "kind": "CommandDropped", "dropped": 5 }),
+            ),
+            (
+                TkConnectionEvent::PatternError(String::from("p"), String::from("err")),
+                serde_json::json!({ "kind": "PatternError", "pattern": "p", "reason": "err" }),
+            ),
+            (
+                TkConnectionEvent::Heartbeat(Duration::from_secs(2)),
+                serde_json::json!({ "kind": "Heartbeat", "uptime_secs": 2.0 }),
+            ),
+            (TkConnectionEvent::ScanStarted, serde_json::json!({ "kind": "ScanStarted" })),
+            (TkConnectionEvent::ScanStopped, serde_json::json!({ "kind": "ScanStopped" })),
+            (
+                TkConnectionEvent::ScanFailed(String::from("err")),
+                serde_json::json!({ "kind": "ScanFailed", "reason": "err" }),
+            ),
+            (
+                TkConnectionEvent::SelectorError(String::from("s"), String::from("err")),
+                serde_json::json!({ "kind": "SelectorError", "selector": "s", "reason": "err" }),
+            ),
+            (
+                TkConnectionEvent::SensorReading(String::from("d"), 300),
+                serde_json::json!({ "kind": "SensorReading", "device": "d", "value": 300 }),
+            ),
+            (
+                TkConnectionEvent::SensorError(String::from("d"), String::from("err")),
+                serde_json::json!({ "kind": "SensorError", "device": "d", "reason": "err" }),
+            ),
+        ];
+        for (event, expected) in cases {
+            assert_eq!(to_json_event(event), expected);
+        }
+    }
+
+    #[test]
+    fn metrics_count_commands_events_and_handles() {
+        // arrange
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let before = tk.get_metrics();
+
+        // act
+        tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        let running = tk.get_metrics();
+        tk.stop_scan();
+        tk.stop_all();
+        thread::sleep(Duration::from_millis(100));
+        let after = tk.get_metrics();
+
+        // assert
+        assert_eq!(running.active_handles, 2);
+        assert_eq!(after.active_handles, 0);
+        assert!(after.commands_sent >= before.commands_sent + 2);
+        assert!(after.events_emitted > before.events_emitted);
+        assert_eq!(after.commands_dropped, 0);
+        assert_eq!(after.events_dropped, 0);
+    }
+
+    #[test]
+    fn missing_pattern_reports_error_and_starts_nothing() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        // act
+        let handle = tk.vibrate_pattern(
+            Speed::max(),
+            Duration::from_secs(1),
+            None,
+            "does not exist",
+            1.0,
+            vec![],
+        );
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert_eq!(handle, ERROR_HANDLE);
+        assert_eq!(tk.last_result, TkResult::PatternError);
+        assert_eq!(tk.scheduler.task_count(), 0);
+        call_registry.assert_unused(1);
+        let error = (0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_PatternError")
+            .unwrap();
+        assert!(error.str_arg.starts_with("does not exist: "));
+    }
+
+    #[test]
+    fn set_pattern_path_lists_patterns_of_new_dir() {
+        // arrange
+        let (mut tk, _) = wait_for_connection(vec![], None);
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().to_str().unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Custom.vibrator.funscript"),
+            r#"{"actions":[{"at":0,"pos":0},{"at":100,"pos":100}]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Stroke.funscript"),
+            r#"{"actions":[{"at":0,"pos":0},{"at":100,"pos":100}]}"#,
+        )
+        .unwrap();
+
+        // act
+        assert!(!tk.set_pattern_path("does/not/exist"));
+        assert!(tk.set_pattern_path(path));
+
+        // assert
+        assert_eq!(tk.get_pattern_path(), path);
+        assert_eq!(get_pattern_names(tk.get_pattern_path(), true), vec!["Custom"]);
+        assert_eq!(get_pattern_names(tk.get_pattern_path(), false), vec!["Stroke"]);
+    }
+
+    #[test]
+    fn trigger_event_plays_configured_pattern_per_device() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+                scalar(3, "vib3", ActuatorType::Vibrate),
+            ],
+            None,
+        );
+        tk.settings.set_event_pattern("vib1 (Vibrate)", "Orgasm", "02_Cruel-Tease");
+        tk.settings.set_event_pattern("vib2 (Vibrate)", "orgasm", "03_Wub-Wub-Wub");
+        tk.settings.set_event_pattern("vib3 (Vibrate)", "other", "02_Cruel-Tease");
+
+        // act
+        let handles = tk.trigger_event("orgasm");
+        thread::sleep(Duration::from_millis(500));
+        for handle in handles.iter() {
+            tk.stop(*handle);
+        }
+
+        // assert
+        assert_eq!(handles.len(), 2);
+        let cruel_tease = read_pattern(&tk.settings.pattern_path, "02_Cruel-Tease", true).unwrap();
+        let wub_wub = read_pattern(&tk.settings.pattern_path, "03_Wub-Wub-Wub", true).unwrap();
+        call_registry.get_device(1)[0].assert_strenth(cruel_tease.actions[0].pos as f64 / 100.0);
+        call_registry.get_device(2)[0].assert_strenth(wub_wub.actions[0].pos as f64 / 100.0);
+        call_registry.assert_unused(3);
+    }
+
+    #[test]
+    fn event_cap_limits_speed_of_matching_event() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+        );
+        tk.settings.set_events("vib1 (Vibrate)", &[String::from("combat")]);
+        tk.settings.set_events("vib2 (Vibrate)", &[String::from("combat")]);
+        tk.settings.set_event_cap("vib1 (Vibrate)", "Combat", Some(60));
+
+        // act
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![String::from("combat")]);
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(0.6);
+        call_registry.get_device(2)[0].assert_strenth(1.0);
+    }
+
+    #[test]
+    fn event_cap_ignored_for_other_events() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.settings.set_event_cap("vib1 (Vibrate)", "combat", Some(60));
+
+        // act
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![]);
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+    }
+
+    #[test]
+    fn trigger_event_plays_stroker_pattern_on_linear_devices() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![linear(1, "lin1"), scalar(2, "vib1", ActuatorType::Vibrate)],
+            None,
+        );
+        let tmp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Stroke.funscript"),
+            r#"{"actions":[{"at":0,"pos":0},{"at":100,"pos":80},{"at":200,"pos":20},{"at":300,"pos":100}]}"#,
+        )
+        .unwrap();
+        tk.settings.pattern_path = String::from(tmp_dir.path().to_str().unwrap());
+        tk.settings.set_event_pattern("lin1 (Position)", "stroke", "Stroke");
+
+        // act
+        let handles = tk.trigger_event("stroke");
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        assert_eq!(handles.len(), 1);
+        let calls = call_registry.get_device(1);
+        calls[0].assert_pos(0.8);
+        calls[1].assert_pos(0.2);
+        calls[2].assert_pos(1.0);
+        call_registry.assert_unused(2);
+    }
+
+    #[test]
+    fn disconnect_stops_devices_before_returning() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(200));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+
+        // act
+        tk.disconnect();
+
+        // assert
+        assert_eq!(call_registry.get_stop_all_calls(), 1);
+    }
+
+    /// Rotate
+
+    #[test]
+    fn rotate_only_rotates_rotators() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                rotate(2, "rot1"),
+            ],
+            None,
+        );
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ActuatorType::Rotate],
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        call_registry.get_device(2)[0].assert_rotation(1.0);
+        call_registry.get_device(2)[1].assert_rotation(0.0);
+        call_registry.assert_unused(1);
+    }
+
+    #[test]
+    fn rotate_directional_reverses_while_running() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(vec![rotate(1, "rot1")], None);
+
+        // act
+        let handle = tk.rotate_directional(Speed::new(50), false, Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
+        assert!(tk.set_direction(handle, true));
+        thread::sleep(Duration::from_millis(100));
+        tk.stop(handle);
+        thread::sleep(Duration::from_millis(100));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_rotation(0.5).assert_direction(false);
+        calls[1].assert_rotation(0.5).assert_direction(true);
+        calls[2].assert_rotation(0.0);
+        assert!(!tk.set_direction(handle, false));
+    }
+
+    /// Oscillate
+
+    #[test]
+    fn oscillate_only_oscillates_oscillators() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "osc1", ActuatorType::Oscillate),
+            ],
+            None,
+        );
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ActuatorType::Oscillate],
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        call_registry.get_device(2)[0].assert_strenth(1.0);
+        call_registry.get_device(2)[1].assert_strenth(0.0);
+        call_registry.assert_unused(1);
+    }
+
+    /// Inflate
+
+    #[test]
+    fn min_speed_raises_weak_speeds_but_zero_stops() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.settings.access_scalar("vib1 (Vibrate)", |x| x.min_speed = 20);
+
+        // act
+        let handle = tk.scalar(
+            Task::Scalar(Speed::new(5)),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(100));
+        tk.update(handle, Speed::new(0));
+        thread::sleep(Duration::from_millis(100));
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(0.2);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+    }
+
+    #[test]
+    fn global_factor_scales_all_scalar_actuators() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+        );
+        tk.settings.access_scalar("vib2 (Vibrate)", |x| x.factor = 0.5);
+        tk.settings.set_global_factor(0.5);
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_secs(1),
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+
+        // assert
+        thread::sleep(Duration::from_millis(200));
+        call_registry.get_device(1)[0].assert_strenth(0.5);
+        call_registry.get_device(2)[0].assert_strenth(0.25);
+    }
+
+    #[test]
+    fn inflate_only_inflates_inflatables_up_to_max_speed() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "pump1", ActuatorType::Inflate),
+            ],
+            None,
+        );
+        tk.settings.access_scalar("pump1 (Inflate)", |x| x.max_speed = 50);
+
+        // act
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ActuatorType::Inflate],
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        call_registry.get_device(2)[0].assert_strenth(0.5);
+        call_registry.get_device(2)[1].assert_strenth(0.0);
+        call_registry.assert_unused(1);
+    }
+
+    /// Linear
+
+    #[test]
+    fn linear_stroke_moves_only_linear_devices() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                linear(3, "stroker"),
+            ],
+            None,
+        );
+
+        // act
+        let handle = tk.linear_stroke(0.8, 200, vec![]);
+        let remaining = tk.get_handle_remaining(handle).unwrap();
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        call_registry.get_device(3)[0]
+            .assert_pos(0.8)
+            .assert_duration(200);
+        call_registry.assert_unused(1);
+        assert!(remaining <= Duration::from_millis(200));
+    }
+
+    /// Battery
+    #[test]
+    fn battery_level_is_read_from_device() {
+        let (connector, _) = FakeDeviceConnector::new(vec![
+            sensor_vibrator(1, "vib1", SensorType::Battery, 0..=100, false),
+            scalar(2, "vib2", ActuatorType::Vibrate),
+        ]);
+        let connector = connector.with_battery_level(1, 50);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            None,
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(2);
+
+        assert_eq!(tk.get_device_battery("vib1"), Some(0.5));
+        assert_eq!(tk.get_device_battery("vib2"), None);
+        assert_eq!(tk.get_device_battery("unknown"), None);
+    }
+
+    #[test]
+    fn rssi_level_read_times_out() {
+        let (connector, _) = FakeDeviceConnector::new(vec![sensor_vibrator(1, "vib1", SensorType::RSSI, 0..=128, false)]);
+        let connector = connector.with_silent_sensors(1);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            None,
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(1);
+
+        let start = Instant::now();
+        assert_eq!(tk.get_device_rssi("vib1"), None);
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn battery_level_read_times_out() {
+        let (connector, _) = FakeDeviceConnector::new(vec![sensor_vibrator(1, "vib1", SensorType::Battery, 0..=100, false)]);
+        let connector = connector.with_silent_sensors(1);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            None,
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(1);
+
+        let start = Instant::now();
+        assert_eq!(tk.get_device_battery("vib1"), None);
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn failing_actuator_reports_error_and_others_still_run() {
+        // arrange
+        let (connector, call_registry) = FakeDeviceConnector::new(vec![
+            scalar(1, "vib1", ActuatorType::Vibrate),
+            scalar(2, "vib2", ActuatorType::Vibrate),
+        ]);
+        let connector = connector.with_failing_device(1);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            None,
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(2);
+        for actuator in tk.status.actuators() {
+            tk.settings.set_enabled(actuator.identifier(), true);
+        }
+
+        // act
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![]);
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        call_registry.assert_unused(1);
+        call_registry.get_device(2)[0].assert_strenth(1.0);
+        let error = (0..20)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_ActuatorError")
+            .unwrap();
+        assert!(error.str_arg.starts_with("vib1: "));
+        assert!(tk.status.get_snapshot().last_error.unwrap().starts_with("vib1: "));
+    }
+
+    #[test]
+    fn failing_fade_out_is_reported() {
+        // arrange
+        let (connector, _) = FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let connector = connector.with_failing_device(1);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            Some(TkSettings {
+                fade_out_ms: 100,
+                ..Default::default()
+            }),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(1);
+        tk.settings.set_enabled("vib1 (Vibrate)", true);
+        tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
+
+        // act
+        tk.stop_all();
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        let errors = tk
+            .connection_events
+            .try_iter()
+            .filter(|x| matches!(x, TkConnectionEvent::ActuatorError(_, _)))
+            .count();
+        assert_eq!(errors, 2, "start and fade out");
+    }
+
+    #[test]
+    fn failing_end_is_reported_once() {
+        // arrange
+        let (connector, _) = FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let connector = connector.with_failing_device(1);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            None,
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(1);
+        tk.settings.set_enabled("vib1 (Vibrate)", true);
+
+        // act
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![]);
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        let events: Vec<String> = tk
+            .connection_events
+            .try_iter()
+            .map(|x| to_mod_event(x).event_name)
+            .collect();
+        assert_eq!(events.iter().filter(|x| *x == "Tele_ActuatorError").count(), 1);
+        assert_eq!(events.iter().filter(|x| *x == "Tele_DeviceError").count(), 1);
+    }
+
+    #[test]
+    fn devices_snapshot_lists_all_devices() {
+        let (connector, _) = FakeDeviceConnector::new(vec![
+            sensor_vibrator(1, "vib1", SensorType::Battery, 0..=100, false),
+            vibrator_rotator(2, "vibrot2"),
+        ]);
+        let connector = connector.with_battery_level(1, 50);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            None,
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(2);
+        tk.settings.set_enabled("vib1 (Vibrate)", true);
+        tk.get_device_battery("vib1");
+
+        let json = serde_json::to_string(&tk.get_devices_snapshot()).unwrap();
+
+        let devices: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let devices = devices.as_array().unwrap();
+        assert_eq!(devices.len(), 2);
+        let vib1 = devices.iter().find(|x| x["name"] == "vib1").unwrap();
+        assert_eq!(vib1["capabilities"], serde_json::json!(["Vibrate"]));
+        assert_eq!(vib1["enabled"], true);
+        assert_eq!(vib1["battery"], 0.5);
+        let vibrot2 = devices.iter().find(|x| x["name"] == "vibrot2").unwrap();
+        assert_eq!(vibrot2["index"], 2);
+        assert_eq!(vibrot2["capabilities"], serde_json::json!(["Rotate(CW/CCW)", "Vibrate"]));
+        assert_eq!(vibrot2["enabled"], false);
+        assert_eq!(vibrot2["battery"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn sensor_readings_are_sent_as_events() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![sensor_vibrator(1, "vib1", SensorType::Pressure, 0..=1000, true), scalar(2, "vib2", ActuatorType::Vibrate)],
+            None,
+        );
+
+        // act
+        assert!(tk.subscribe_sensor("vib1"));
+        assert_timeout!(call_registry.get_device(1).len() == 1, "Sensor subscribed");
+        call_registry.send_sensor_reading(1, SensorType::Pressure, 300);
+
+        // assert
+        assert_timeout!(
+            tk.connection_events.try_iter().any(|x| matches!(
+                x,
+                TkConnectionEvent::SensorReading(device, 300) if device == "vib1"
+            )),
+            "Reading is sent as event"
+        );
+        assert!(tk.unsubscribe_sensor("vib1"));
+        assert_timeout!(call_registry.get_device(1).len() == 2, "Sensor unsubscribed");
+        assert!(!tk.unsubscribe_sensor("vib1"));
+    }
+
+    #[test]
+    fn subscribe_sensor_without_sensors_sends_error() {
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+
+        assert!(!tk.subscribe_sensor("vib1"));
+        assert!(tk.connection_events.try_iter().any(|x| matches!(
+            x,
+            TkConnectionEvent::SensorError(device, _) if device == "vib1"
+        )));
+        assert!(!tk.unsubscribe_sensor("vib1"));
+    }
+
+    #[test]
+    fn rssi_level_is_read_from_device() {
+        let (connector, _) = FakeDeviceConnector::new(vec![
+            sensor_vibrator(1, "vib1", SensorType::RSSI, 0..=128, false),
+            sensor_vibrator(2, "vib2", SensorType::Battery, 0..=100, false),
+        ]);
+        let connector = connector.with_rssi_level(1, -72).with_battery_level(2, 80);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
+            None,
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(2);
+
+        assert_eq!(tk.get_device_rssi("vib1"), Some(-72));
+        assert_eq!(tk.get_device_rssi("vib2"), None);
+        assert_eq!(tk.get_device_rssi("unknown"), None);
+    }
+
+    /// Vibrate (E2E)
+
+    #[test]
+    #[ignore = "Requires one (1) vibrator to be connected via BTLE (vibrates it)"]
+    fn vibrate_pattern() {
+        let (mut tk, handle) = test_pattern("02_Cruel-Tease", Duration::from_secs(10), true);
+        thread::sleep(Duration::from_secs(2)); // dont disconnect
+        tk.stop(handle);
+        thread::sleep(Duration::from_secs(10));
+    }
+
+    fn test_pattern(
+        pattern_name: &str,
+        duration: Duration,
+        vibration_pattern: bool,
+    ) -> (Telekinesis, i32) {
+        let settings = TkSettings::default();
+        let pattern_path =
+            String::from("../../contrib/Distribution/SKSE/Plugins/Telekinesis/Patterns");
+        let mut tk = Telekinesis::connect_with(
+            || async move { in_process_connector(&[CommManagerKind::Btle]) },
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.scan_for_devices();
+        tk.await_connect(1);
+        thread::sleep(Duration::from_secs(2));
+        let known_actuator_ids = tk.status.get_known_actuator_ids();
+        tk.settings
+            .set_enabled(known_actuator_ids.first().unwrap(), true);
+
+        let fscript = read_pattern(&pattern_path, pattern_name, vibration_pattern).unwrap();
+        let handle = tk.scalar(
+            Task::Pattern(Speed::max(), ActuatorType::Vibrate, pattern_name.into()),
+            duration,
+            vec![],
+            Some(fscript),
+            &[ActuatorType::Vibrate],
+        );
+        (tk, handle)
+    }
+
+    /// Intiface (E2E)
+
+    #[test]
+    #[ignore = "Requires intiface to be connected, with a connected device (vibrates it)"]
+    fn intiface_test_vibration() {
+        let settings = TkSettings {
+            connection: TkConnectionType::WebSocket(String::from("127.0.0.1:12345")),
+            ..Default::default()
+        };
+
+        let mut tk = Telekinesis::connect(settings).unwrap();
+        tk.scan_for_devices();
+
+        thread::sleep(Duration::from_secs(5));
+        assert!(matches!(
+            tk.status.connection_status(),
+            TkConnectionStatus::Connected
+        ));
+
+        for actuator in tk.status.actuators() {
+            tk.settings.set_enabled(actuator.device.name(), true);
+        }
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+        thread::sleep(Duration::from_secs(5));
+    }
+
+    #[test]
+    fn intiface_not_available_connection_status_error() {
+        let settings = TkSettings {
+            connection: TkConnectionType::WebSocket(String::from("bogushost:6572")),
+            ..Default::default()
+        };
+
+        let mut tk = Telekinesis::connect(settings).unwrap();
+        tk.scan_for_devices();
+        thread::sleep(Duration::from_secs(5));
+        match tk.status.connection_status() {
+            TkConnectionStatus::Failed(err) => {
+                assert!(!err.is_empty());
+            }
+            _ => todo!(),
+        };
+    }
+
+    #[test]
+    fn invalid_websocket_endpoint_fails_connection() {
+        let settings = TkSettings {
+            connection: TkConnectionType::WebSocket(String::from("")),
+            ..Default::default()
+        };
+
+        let mut tk = Telekinesis::connect(settings).unwrap();
+        assert_timeout!(
+            matches!(tk.status.connection_status(), TkConnectionStatus::Failed(_)),
+            "Awaiting connection failure"
+        );
+    }
+
+    /// Settings
+
+    #[test]
+    fn settings_are_trimmed_and_lowercased() {
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.settings.set_enabled("vib1 (Vibrate)", true);
+        tk.settings.set_events("vib1 (Vibrate)", &[String::from(" SoMe EvEnT    ")]);
+        tk.scalar(
+            Task::Scalar(Speed::max()),
+            Duration::from_millis(1),
+            vec![String::from("some event")],
+            None,
+            &[ActuatorType::Vibrate],
+        );
+
+        thread::sleep(Duration::from_millis(500));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+    }
+
+    #[test]
+    fn get_devices_contains_connected_devices() {
+        // arrange
+        let (mut tk, _) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Inflate),
+            ],
+            None,
+        );
+
+        // assert
+        assert_timeout!(tk.status.actuators().len() == 2, "Enough devices connected");
+        assert!(
+            tk.status
+                .get_known_actuator_ids()
+                .contains(&String::from("vib1 (Vibrate)")),
+            "Contains name vib1"
+        );
+        assert!(
+            tk.status
+                .get_known_actuator_ids()
+                .contains(&String::from("vib2 (Inflate)")),
+            "Contains name vib2"
+        );
+    }
+
+    #[test]
+    fn get_actuators_lists_each_motor() {
+        // arrange
+        let mut settings = TkSettings::default();
+        settings.set_enabled("foreign", true);
+        let (mut tk, _) = wait_for_connection(
+            vec![scalars(1, "vib1", ActuatorType::Vibrate, 2)],
+            Some(settings),
+        );
+
+        // act
+        let actuators: Vec<String> = tk
+            .get_actuators()
+            .iter()
+            .map(|x| String::from(x.identifier()))
+            .collect();
+
+        // assert
+        assert_eq!(actuators, vec!["vib1 (Vibrate)", "vib1 (Vibrate #1)"]);
+    }
+
+    #[test]
+    fn get_devices_contains_devices_from_settings() {
+        let mut settings = TkSettings::default();
+        settings.set_enabled("foreign", true);
+
+        let (mut tk, _) = wait_for_connection(vec![], Some(settings));
+        assert!(
+            tk.status
+                .get_known_actuator_ids()
+                .contains(&String::from("foreign")),
+            "Contains additional device from settings"
+        );
+    }
+
+    #[test]
+    fn events_get() {
+        let empty: Vec<String> = vec![];
+        let one_event = &[String::from("evt2")];
+        let two_events = &[String::from("evt2"), String::from("evt3")];
+
+        let (mut tk, _) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+                scalar(3, "vib3", ActuatorType::Vibrate),
+            ],
+            None,
+        );
+
+        tk.settings.set_events("vib2", one_event);
+        tk.settings.set_events("vib3", two_events);
+
+        assert_eq!(tk.settings.get_events("vib1"), empty);
+        assert_eq!(tk.settings.get_events("vib2"), one_event);
+        assert_eq!(tk.settings.get_events("vib3"), two_events);
     }
 
-    /// Vibrate
-
     #[test]
-    fn vibrate_infinitely_and_then_stop() {
-        // arrange
-        let (mut tk, call_registry) =
-            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+    fn event_only_vibrate_selected_devices() {
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+        );
+        tk.settings.set_events("vib1 (Vibrate)", &[String::from("selected_event")]);
+        tk.settings.set_events("vib2 (Vibrate)", &[String::from("bogus")]);
 
-        // act
-        let handle = tk.scalar(
+        tk.scalar(
             Task::Scalar(Speed::max()),
-            Duration::MAX,
-            vec![],
+            Duration::from_millis(1),
+            vec![String::from("selected_event")],
             None,
             &[ActuatorType::Vibrate],
         );
-
         thread::sleep(Duration::from_secs(1));
-        call_registry.get_device(1)[0].assert_strenth(1.0);
 
-        tk.stop(handle);
-        thread::sleep(Duration::from_secs(1));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
         call_registry.get_device(1)[1].assert_strenth(0.0);
+        call_registry.assert_unused(2);
     }
 
     #[test]
-    fn vibrate_linear_then_cancel() {
-        // arrange
+    fn event_is_trimmed_and_ignores_casing() {
         let (mut tk, call_registry) =
             wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
-
-        // act
-        thread::sleep(Duration::from_secs(1));
+        tk.settings.set_enabled("vib1 (Vibrate)", true);
+        tk.settings.set_events("vib1 (Vibrate)", &[String::from("some event")]);
         tk.scalar(
             Task::Scalar(Speed::max()),
-            Duration::from_secs(1),
-            vec![],
+            Duration::from_millis(1),
+            vec![String::from(" SoMe EvEnT    ")],
             None,
             &[ActuatorType::Vibrate],
         );
-        thread::sleep(Duration::from_secs(2));
-        call_registry.get_device(1)[0].assert_strenth(1.0);
-        tk.stop_all();
 
-        thread::sleep(Duration::from_secs(1));
+        thread::sleep(Duration::from_millis(500));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
         call_registry.get_device(1)[1].assert_strenth(0.0);
     }
 
+    /// Device Status
     #[test]
-    fn vibrate_all_demo_vibrators() {
+    fn get_device_connected() {
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "existing", ActuatorType::Vibrate)], None);
+        assert_eq!(
+            tk.status.get_actuator_status("existing (Vibrate)"),
+            TkConnectionStatus::Connected,
+            "Existing device returns connected"
+        );
+        assert_eq!(
+            tk.status.get_actuator_status("not existing (Vibrate)"),
+            TkConnectionStatus::NotConnected,
+            "Non-existing device returns not connected"
+        );
+    }
+
+    #[test]
+    fn settings_prune_disconnected_removes_only_ghost_devices() {
+        let mut settings = TkSettings::default();
+        settings.set_enabled("vib1 (Vibrate)", true);
+        settings.set_enabled("ghost", true);
+
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings));
+        assert_eq!(tk.settings_prune_disconnected_dry_run(), vec!["ghost"]);
+        assert!(tk.settings.get_device("ghost").is_some(), "dry run changes nothing");
+
+        assert_eq!(tk.settings_prune_disconnected(), vec!["ghost"]);
+        assert!(tk.settings.get_device("ghost").is_none());
+        assert!(tk.settings.get_enabled("vib1 (Vibrate)"));
+        assert!(!tk.status.get_known_actuator_ids().contains(&String::from("ghost")));
+        assert!(tk.settings_prune_disconnected_dry_run().is_empty());
+    }
+
+    #[test]
+    fn get_device_names_lists_each_device_once() {
+        let (mut tk, _) = wait_for_connection(
+            vec![
+                scalars(1, "vib1", ActuatorType::Vibrate, 2),
+                linear(2, "lin2"),
+            ],
+            None,
+        );
+        let mut names = tk.status.get_device_names();
+        names.sort();
+        assert_eq!(names, vec!["lin2", "vib1"]);
+    }
+
+    #[test]
+    fn injected_server_disconnect_fails_connection() {
         // arrange
-        let (connector, call_registry) = FakeDeviceConnector::device_demo();
-        let count = connector.devices.len();
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.scan_for_devices();
+        assert_timeout!(
+            tk.status.connection_status() == TkConnectionStatus::Connected,
+            "Status is connected"
+        );
 
         // act
-        let mut tk =
-            Telekinesis::connect_with(|| async move { connector }, None, TkConnectionType::Test)
-                .unwrap();
-        tk.await_connect(count);
-        for actuator_id in tk.status.get_known_actuator_ids() {
-            tk.settings.set_enabled(&actuator_id, true);
-        }
-        tk.scalar(
-            Task::Scalar(Speed::new(100)),
-            Duration::from_millis(1),
-            vec![],
-            None,
-            &[ActuatorType::Vibrate],
+        assert!(tk.inject_client_event(buttplug::client::ButtplugClientEvent::ServerDisconnect));
+
+        // assert
+        let failure = (0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_ConnectionError")
+            .unwrap();
+        assert_eq!(failure.str_arg, "Server disconnected");
+        assert_timeout!(
+            tk.status.connection_status() == TkConnectionStatus::Failed(String::from("Server disconnected")),
+            "Status is failed"
         );
+    }
+
+    #[test]
+    fn injected_scanning_finished_clears_scan_state() {
+        // arrange
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.scan_for_devices();
+        assert_timeout!(tk.status.is_scanning(), "Awaiting scan started");
+
+        // act
+        assert!(tk.inject_client_event(buttplug::client::ButtplugClientEvent::ScanningFinished));
 
         // assert
-        thread::sleep(Duration::from_millis(500));
-        call_registry.get_device(1)[0].assert_strenth(1.0);
-        call_registry.get_device(1)[1].assert_strenth(0.0);
-        call_registry.assert_unused(4); // linear
-        call_registry.assert_unused(7); // rotator
+        assert_timeout!(!tk.status.is_scanning(), "Awaiting scan stopped");
+        assert!((0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .any(|x| x.event_name == "Tele_ScanStopped"));
     }
 
     #[test]
-    fn vibrate_non_existing_device() {
+    fn injected_device_removed_disconnects_actuators() {
         // arrange
-        let (mut tk, call_registry) =
-            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let device = tk.status.get_device("vib1").unwrap();
 
         // act
-        tk.scalar(
-            Task::Scalar(Speed::max()),
-            Duration::from_millis(1),
-            vec![String::from("does not exist")],
+        assert!(tk.inject_client_event(buttplug::client::ButtplugClientEvent::DeviceRemoved(device)));
+
+        // assert
+        assert_timeout!(
+            tk.status.get_actuator_status("vib1 (Vibrate)") == TkConnectionStatus::NotConnected,
+            "Actuator is not connected"
+        );
+        assert!(tk.status.get_device("vib1").is_none());
+    }
+
+    #[test]
+    fn vibrate_matching_selects_devices_by_glob() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "Lovense Hush", ActuatorType::Vibrate),
+                scalar(2, "Lovense Lush", ActuatorType::Vibrate),
+                scalar(3, "Kiiroo Pearl", ActuatorType::Vibrate),
+            ],
             None,
-            &[ActuatorType::Vibrate],
         );
-        thread::sleep(Duration::from_millis(50));
+
+        // act
+        let handle = tk.vibrate_matching("Lovense*", Speed::new(70), Duration::from_millis(100));
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        assert_ne!(handle, ERROR_HANDLE);
+        call_registry.get_device(1)[0].assert_strenth(0.7);
+        call_registry.get_device(2)[0].assert_strenth(0.7);
+        call_registry.assert_unused(3);
+    }
+
+    #[test]
+    fn vibrate_matching_rejects_invalid_pattern() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "Lovense Hush", ActuatorType::Vibrate)], None);
+
+        // act
+        let handle = tk.vibrate_matching("Lovense [12", Speed::max(), Duration::from_millis(100));
 
         // assert
+        assert_eq!(handle, ERROR_HANDLE);
+        assert_eq!(tk.last_result, TkResult::InvalidSelector);
+        let error = (0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .find(|x| x.event_name == "Tele_SelectorError")
+            .unwrap();
+        assert!(error.str_arg.starts_with("Lovense [12: "));
         call_registry.assert_unused(1);
     }
 
     #[test]
-    fn settings_only_vibrate_enabled_devices() {
+    fn linked_device_mirrors_primary() {
         // arrange
         let (mut tk, call_registry) = wait_for_connection(
             vec![
@@ -513,202 +4595,361 @@ mod tests {
             ],
             None,
         );
-        tk.settings.set_enabled("vib2 (Vibrate)", false);
+        assert!(tk.link_devices("vib1", "VIB2"));
+        assert!(!tk.link_devices("vib1", "vib1"));
 
         // act
-        tk.scalar(
-            Task::Scalar(Speed::max()),
-            Duration::from_millis(1),
-            vec![],
-            None,
-            &[ActuatorType::Vibrate],
-        );
-        thread::sleep(Duration::from_secs(1));
+        tk.vibrate_devices(vec![String::from("vib1")], Speed::new(40), Duration::from_millis(100));
+        thread::sleep(Duration::from_millis(300));
 
         // assert
-        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(1)[0].assert_strenth(0.4);
+        call_registry.get_device(2)[0].assert_strenth(0.4);
         call_registry.get_device(1)[1].assert_strenth(0.0);
-        call_registry.get_device(3)[0].assert_strenth(1.0);
-        call_registry.get_device(3)[1].assert_strenth(0.0);
+        call_registry.get_device(2)[1].assert_strenth(0.0);
+        call_registry.assert_unused(3);
+    }
+
+    #[test]
+    fn unlinked_device_no_longer_mirrors() {
+        let mut settings = TkSettings::default();
+        settings.link_devices("vib1", "vib2");
+        settings.unlink_device("vib2");
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            Some(settings),
+        );
+
+        tk.vibrate_devices(vec![String::from("vib1")], Speed::new(40), Duration::from_millis(100));
+        thread::sleep(Duration::from_millis(300));
+
+        call_registry.get_device(1)[0].assert_strenth(0.4);
         call_registry.assert_unused(2);
     }
 
-    /// Vibrate (E2E)
+    #[test]
+    fn get_device_index_and_name_round_trip() {
+        let (mut tk, _) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+        );
+        for name in tk.status.get_device_names() {
+            let index = tk.status.get_device_index(&name).unwrap();
+            assert_eq!(tk.status.get_device_name(index), Some(name));
+        }
+        assert_eq!(tk.status.get_device_index("vib2"), Some(2));
+        assert_eq!(tk.status.get_device_name(1), Some(String::from("vib1")));
+        assert_eq!(tk.status.get_device_index("unknown"), None);
+        assert_eq!(tk.status.get_device_name(3), None);
+    }
 
     #[test]
-    #[ignore = "Requires one (1) vibrator to be connected via BTLE (vibrates it)"]
-    fn vibrate_pattern() {
-        let (mut tk, handle) = test_pattern("02_Cruel-Tease", Duration::from_secs(10), true);
-        thread::sleep(Duration::from_secs(2)); // dont disconnect
-        tk.stop(handle);
-        thread::sleep(Duration::from_secs(10));
+    fn get_device_capabilities_lists_all_actuator_types() {
+        let (mut tk, _) = wait_for_connection(
+            vec![
+                scalars(1, "vib1", ActuatorType::Vibrate, 2),
+                vibrator_rotator(2, "rot1"),
+                linear(3, "lin1"),
+            ],
+            None,
+        );
+        assert_eq!(tk.status.get_device_capabilities("vib1"), vec!["Vibrate"]);
+        assert_eq!(
+            tk.status.get_device_capabilities("rot1"),
+            vec!["Rotate(CW/CCW)", "Vibrate"]
+        );
+        assert_eq!(tk.status.get_device_capabilities("lin1"), vec!["Position"]);
+        assert!(tk.status.get_device_capabilities("unknown").is_empty());
     }
 
-    fn test_pattern(
-        pattern_name: &str,
-        duration: Duration,
-        vibration_pattern: bool,
-    ) -> (Telekinesis, i32) {
-        let settings = TkSettings::default();
-        let pattern_path =
-            String::from("../contrib/Distribution/SKSE/Plugins/Telekinesis/Patterns");
+    #[test]
+    fn get_device_capabilities_shows_rotation_direction() {
+        let (mut tk, _) = wait_for_connection(
+            vec![
+                rotate(1, "rot1"),
+                scalar(2, "rot2", ActuatorType::Rotate),
+            ],
+            None,
+        );
+        assert_eq!(tk.status.get_device_capabilities("rot1"), vec!["Rotate(CW/CCW)"]);
+        assert_eq!(tk.status.get_device_capabilities("rot2"), vec!["Rotate"]);
+    }
+
+    #[test]
+    fn get_device_names_distinguishes_devices_with_same_name() {
+        let (connector, _) = FakeDeviceConnector::new(vec![
+            sensor_vibrator(1, "hush", SensorType::Battery, 0..=100, false),
+            scalar(2, "hush", ActuatorType::Vibrate),
+        ]);
+        let connector = connector.with_battery_level(1, 50);
         let mut tk = Telekinesis::connect_with(
-            || async move { in_process_connector() },
-            Some(settings),
+            || async move { connector },
+            None,
             TkConnectionType::Test,
         )
         .unwrap();
-        tk.scan_for_devices();
-        tk.await_connect(1);
-        thread::sleep(Duration::from_secs(2));
-        let known_actuator_ids = tk.status.get_known_actuator_ids();
-        tk.settings
-            .set_enabled(known_actuator_ids.first().unwrap(), true);
+        tk.await_connect(2);
 
-        let fscript = read_pattern(&pattern_path, pattern_name, vibration_pattern).unwrap();
-        let handle = tk.scalar(
-            Task::Pattern(Speed::max(), ActuatorType::Vibrate, pattern_name.into()),
-            duration,
-            vec![],
-            Some(fscript),
-            &[ActuatorType::Vibrate],
-        );
-        (tk, handle)
+        let mut names = tk.status.get_device_names();
+        names.sort();
+        assert_eq!(names, vec!["hush #1", "hush #2"]);
+        assert_eq!(tk.get_device_battery("hush #1"), Some(0.5));
+        assert_eq!(tk.get_device_battery("hush #2"), None);
     }
 
-    /// Intiface (E2E)
-
-    #[test]
-    #[ignore = "Requires intiface to be connected, with a connected device (vibrates it)"]
-    fn intiface_test_vibration() {
-        let mut settings = TkSettings::default();
-        settings.connection = TkConnectionType::WebSocket(String::from("127.0.0.1:12345"));
-
-        let mut tk = Telekinesis::connect(settings).unwrap();
-        tk.scan_for_devices();
-
-        thread::sleep(Duration::from_secs(5));
-        assert!(matches!(
-            tk.status.connection_status(),
-            TkConnectionStatus::Connected
-        ));
-
-        for actuator in tk.status.actuators() {
-            tk.settings.set_enabled(actuator.device.name(), true);
-        }
-        tk.scalar(
-            Task::Scalar(Speed::max()),
-            Duration::MAX,
-            vec![],
+    #[test]
+    fn read_device_battery_of_actuator_with_duplicate_device_name() {
+        let (connector, _) = FakeDeviceConnector::new(vec![
+            scalar(1, "hush", ActuatorType::Vibrate),
+            sensor_vibrator(2, "hush", SensorType::Battery, 0..=100, false),
+        ]);
+        let connector = connector.with_battery_level(2, 50);
+        let mut tk = Telekinesis::connect_with(
+            || async move { connector },
             None,
-            &[ActuatorType::Vibrate],
-        );
-        thread::sleep(Duration::from_secs(5));
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(2);
+
+        let actuators = tk.get_actuators();
+        let second = actuators.iter().find(|x| x.device.index() == 2).unwrap();
+        let first = actuators.iter().find(|x| x.device.index() == 1).unwrap();
+        assert_eq!(tk.read_device_battery(&second.device), Some(0.5));
+        assert_eq!(tk.read_device_battery(&first.device), None);
     }
 
     #[test]
-    fn intiface_not_available_connection_status_error() {
+    fn devices_with_same_name_share_settings() {
         let mut settings = TkSettings::default();
-        settings.connection = TkConnectionType::WebSocket(String::from("bogushost:6572"));
+        settings.set_enabled("hush (Vibrate)", true);
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![scalar(1, "hush", ActuatorType::Vibrate), scalar(2, "hush", ActuatorType::Vibrate)],
+            Some(settings),
+        );
 
-        let mut tk = Telekinesis::connect(settings).unwrap();
-        tk.scan_for_devices();
-        thread::sleep(Duration::from_secs(5));
-        match tk.status.connection_status() {
-            TkConnectionStatus::Failed(err) => {
-                assert!(!err.is_empty());
-            }
-            _ => todo!(),
-        };
+        // one actuator id for both devices, known limitation of name based settings
+        assert_eq!(tk.status.get_known_actuator_ids(), vec!["hush (Vibrate)"]);
+        tk.vibrate(Speed::new(50), Duration::from_millis(100), vec![]);
+        assert_timeout!(
+            !call_registry.get_device(1).is_empty() && !call_registry.get_device(2).is_empty(),
+            "Both devices are enabled"
+        );
     }
 
-    /// Settings
+    /// Events
 
     #[test]
-    fn settings_are_trimmed_and_lowercased() {
+    fn device_added_and_removed_events() {
         let (mut tk, call_registry) =
             wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
-        tk.settings.set_enabled("vib1 (Vibrate)", true);
-        tk.settings.set_events("vib1 (Vibrate)", &[String::from(" SoMe EvEnT    ")]);
-        tk.scalar(
-            Task::Scalar(Speed::max()),
-            Duration::from_millis(1),
-            vec![String::from("some event")],
-            None,
-            &[ActuatorType::Vibrate],
+
+        call_registry.remove_device(1);
+        assert_timeout!(
+            tk.status.connected_actuators().is_empty(),
+            "Awaiting device removed"
+        );
+
+        let added = get_next_events_blocking(&tk.connection_events).unwrap();
+        assert_eq!(added.event_name.to_string(), "Tele_DeviceAdded");
+        assert_eq!(added.str_arg.to_string(), "vib1");
+        let removed = get_next_events_blocking(&tk.connection_events).unwrap();
+        assert_eq!(removed.event_name.to_string(), "Tele_DeviceRemoved");
+        assert_eq!(removed.str_arg.to_string(), "vib1");
+    }
+
+    #[test]
+    fn device_readded_with_new_attributes_updates_capabilities() {
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        assert_eq!(tk.status.get_device_capabilities("vib1"), vec!["Vibrate"]);
+
+        call_registry.remove_device(1);
+        assert_timeout!(
+            tk.status.connected_actuators().is_empty(),
+            "Awaiting device removed"
         );
+        call_registry.add_device(vibrator_rotator(1, "vib1"));
 
+        assert_timeout!(
+            tk.status.get_device_capabilities("vib1") == vec!["Rotate(CW/CCW)", "Vibrate"],
+            "Awaiting capabilities updated"
+        );
+    }
+
+    #[test]
+    fn idle_connection_stops_devices_and_disconnects() {
+        // arrange
+        let settings = TkSettings {
+            idle_disconnect_secs: Some(1),
+            ..Default::default()
+        };
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![scalar(1, "vib1", ActuatorType::Vibrate)],
+            Some(settings),
+        );
+        tk.scan_for_devices();
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![]);
         thread::sleep(Duration::from_millis(500));
-        call_registry.get_device(1)[0].assert_strenth(1.0);
-        call_registry.get_device(1)[1].assert_strenth(0.0);
+        assert_eq!(call_registry.get_stop_all_calls(), 0);
+
+        // act
+        thread::sleep(Duration::from_millis(1000));
+
+        // assert
+        assert_eq!(call_registry.get_stop_all_calls(), 1);
+        assert_eq!(tk.status.connection_status(), TkConnectionStatus::NotConnected);
+        assert!(tk.status.connected_actuators().is_empty());
+        assert!((0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .any(|x| x.event_name == "Tele_Disconnected"));
     }
 
     #[test]
-    fn get_devices_contains_connected_devices() {
+    fn idle_connection_connects_again_on_command() {
         // arrange
-        let (mut tk, _) = wait_for_connection(
-            vec![
-                scalar(1, "vib1", ActuatorType::Vibrate),
-                scalar(2, "vib2", ActuatorType::Inflate),
-            ],
-            None,
+        let settings = TkSettings {
+            connection: TkConnectionType::Test,
+            test_devices: vec![String::from("vib1")],
+            idle_disconnect_secs: Some(1),
+            ..Default::default()
+        };
+        let mut tk = Telekinesis::connect(settings).unwrap();
+        tk.await_connect(1);
+        tk.scan_for_devices();
+        assert_timeout!(
+            tk.status.connection_status() == TkConnectionStatus::NotConnected,
+            "Awaiting idle disconnect"
         );
 
+        // act
+        tk.scan_for_devices();
+
         // assert
-        assert_timeout!(tk.status.actuators().len() == 2, "Enough devices connected");
-        assert!(
-            tk.status
-                .get_known_actuator_ids()
-                .contains(&String::from("vib1 (Vibrate)")),
-            "Contains name vib1"
+        assert_timeout!(
+            tk.status.connection_status() == TkConnectionStatus::Connected,
+            "Awaiting connected again"
         );
-        assert!(
-            tk.status
-                .get_known_actuator_ids()
-                .contains(&String::from("vib2 (Inflate)")),
-            "Contains name vib2"
+        assert_timeout!(tk.status.connected_actuators().len() == 1, "Awaiting device");
+        tk.settings.set_enabled("vib1 (Vibrate)", true);
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![]);
+        let calls = tk.test_calls.clone().unwrap();
+        assert_timeout!(!calls.get_device(1).is_empty(), "Awaiting vibration");
+    }
+
+    #[test]
+    fn runtime_init_failure_is_typed() {
+        let result = start_runtime(|| Err(std::io::Error::other("thread limit reached")));
+        let err = anyhow::Error::from(result.unwrap_err());
+        assert!(matches!(err.downcast_ref::<TkError>(), Some(TkError::RuntimeInit(_))));
+        assert_eq!(err.to_string(), "failed to start the async runtime");
+        assert_eq!(
+            format!("{:#}", err),
+            "failed to start the async runtime: thread limit reached"
         );
     }
 
     #[test]
-    fn get_devices_contains_devices_from_settings() {
-        let mut settings = TkSettings::default();
-        settings.set_enabled("foreign", true);
+    fn switch_connection_enumerates_new_devices() {
+        // arrange
+        let (mut tk, first_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let (connector, second_registry) = FakeDeviceConnector::new(vec![
+            scalar(1, "other1", ActuatorType::Vibrate),
+            scalar(2, "other2", ActuatorType::Vibrate),
+        ]);
 
-        let (mut tk, _) = wait_for_connection(vec![], Some(settings));
-        assert!(
-            tk.status
-                .get_known_actuator_ids()
-                .contains(&String::from("foreign")),
-            "Contains additional device from settings"
+        // act
+        tk.switch_connection_with(|| async move { connector }, TkConnectionType::Test)
+            .unwrap();
+        assert_timeout!(tk.status.connected_actuators().len() == 2, "Awaiting new devices");
+
+        // assert
+        assert!((0..10)
+            .filter_map(|_| get_next_events_blocking(&tk.connection_events))
+            .any(|x| x.event_name == "Tele_Disconnected"));
+        assert_eq!(first_registry.get_stop_all_calls(), 1);
+        assert_eq!(tk.status.connection_status(), TkConnectionStatus::Connected);
+        assert_eq!(tk.status.get_device_names(), vec!["other1", "other2"]);
+        for actuator in tk.status.actuators() {
+            tk.settings.set_enabled(actuator.identifier(), true);
+        }
+        tk.vibrate(Speed::max(), Duration::from_millis(100), vec![]);
+        thread::sleep(Duration::from_millis(200));
+        second_registry.get_device(1)[0].assert_strenth(1.0);
+        second_registry.get_device(2)[0].assert_strenth(1.0);
+    }
+
+    #[test]
+    fn switch_connection_after_connection_was_lost() {
+        // arrange
+        let (mut tk, registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        registry.disconnect_server();
+        assert_timeout!(
+            tk.connection_task.as_ref().unwrap().is_finished(),
+            "Awaiting connection task end"
         );
+        let (connector, _) = FakeDeviceConnector::new(vec![scalar(1, "other1", ActuatorType::Vibrate)]);
+
+        // act
+        let result = tk.switch_connection_with(|| async move { connector }, TkConnectionType::Test);
+
+        // assert
+        assert!(result.is_ok());
+        assert_timeout!(tk.status.get_device_names() == vec!["other1"], "Awaiting new device");
     }
 
     #[test]
-    fn events_get() {
-        let empty: Vec<String> = vec![];
-        let one_event = &[String::from("evt2")];
-        let two_events = &[String::from("evt2"), String::from("evt3")];
+    fn switch_connection_to_test_devices() {
+        // arrange
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        tk.settings.test_devices = vec![String::from("test1")];
 
-        let (mut tk, _) = wait_for_connection(
+        // act
+        tk.switch_connection(TkConnectionType::Test).unwrap();
+
+        // assert
+        assert_timeout!(tk.status.get_device_names() == vec!["test1"], "Awaiting test device");
+        assert_eq!(tk.settings.connection, TkConnectionType::Test);
+        assert!(tk.test_calls.is_some());
+    }
+
+    #[test]
+    fn device_removed_stops_all_devices_if_enabled() {
+        let settings = TkSettings {
+            stop_all_on_disconnect: true,
+            ..Default::default()
+        };
+        let (mut tk, call_registry) = wait_for_connection(
             vec![
                 scalar(1, "vib1", ActuatorType::Vibrate),
                 scalar(2, "vib2", ActuatorType::Vibrate),
-                scalar(3, "vib3", ActuatorType::Vibrate),
             ],
-            None,
+            Some(settings),
         );
+        let handle = tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
 
-        tk.settings.set_events("vib2", one_event);
-        tk.settings.set_events("vib3", two_events);
+        call_registry.remove_device(1);
+        assert_timeout!(call_registry.get_stop_all_calls() == 1, "Awaiting stop all");
+        tk.update(handle, Speed::new(50));
+        thread::sleep(Duration::from_millis(100));
 
-        assert_eq!(tk.settings.get_events("vib1"), empty);
-        assert_eq!(tk.settings.get_events("vib2"), one_event);
-        assert_eq!(tk.settings.get_events("vib3"), two_events);
+        // vib2 was stopped by the server and is not started again
+        assert!(!tk.scheduler.has_task(handle));
+        assert_eq!(call_registry.get_device(2).len(), 1);
     }
 
     #[test]
-    fn event_only_vibrate_selected_devices() {
+    fn device_removed_does_not_stop_devices_by_default() {
         let (mut tk, call_registry) = wait_for_connection(
             vec![
                 scalar(1, "vib1", ActuatorType::Vibrate),
@@ -716,65 +4957,226 @@ mod tests {
             ],
             None,
         );
-        tk.settings.set_events("vib1 (Vibrate)", &[String::from("selected_event")]);
-        tk.settings.set_events("vib2 (Vibrate)", &[String::from("bogus")]);
+        tk.vibrate(Speed::max(), Duration::MAX, vec![]);
+        thread::sleep(Duration::from_millis(100));
 
-        tk.scalar(
-            Task::Scalar(Speed::max()),
-            Duration::from_millis(1),
-            vec![String::from("selected_event")],
-            None,
-            &[ActuatorType::Vibrate],
+        call_registry.remove_device(1);
+        assert_timeout!(
+            tk.status.connected_actuators().len() == 1,
+            "Awaiting device removed"
         );
-        thread::sleep(Duration::from_secs(1));
+        thread::sleep(Duration::from_millis(100));
 
-        call_registry.get_device(1)[0].assert_strenth(1.0);
-        call_registry.get_device(1)[1].assert_strenth(0.0);
-        call_registry.assert_unused(2);
+        assert_eq!(call_registry.get_stop_all_calls(), 0);
+        assert_eq!(call_registry.get_device(2).len(), 1);
     }
 
     #[test]
-    fn event_is_trimmed_and_ignores_casing() {
-        let (mut tk, call_registry) =
+    fn connection_status_does_not_require_polling_events() {
+        let (mut tk, _) =
             wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
-        tk.settings.set_enabled("vib1 (Vibrate)", true);
-        tk.settings.set_events("vib1 (Vibrate)", &[String::from("some event")]);
-        tk.scalar(
-            Task::Scalar(Speed::max()),
-            Duration::from_millis(1),
-            vec![String::from(" SoMe EvEnT    ")],
-            None,
-            &[ActuatorType::Vibrate],
+        assert_eq!(tk.status.connection_status(), TkConnectionStatus::NotConnected);
+
+        tk.scan_for_devices();
+        assert_timeout!(
+            tk.status.connection_status() == TkConnectionStatus::Connected,
+            "Awaiting connected"
         );
+        assert!(!tk.connection_events.is_empty());
+    }
 
-        thread::sleep(Duration::from_millis(500));
-        call_registry.get_device(1)[0].assert_strenth(1.0);
-        call_registry.get_device(1)[1].assert_strenth(0.0);
+    #[test]
+    fn connect_and_wait_returns_connected() {
+        let settings = TkSettings {
+            connection: TkConnectionType::Test,
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let mut tk = Telekinesis::connect_and_wait(settings, Duration::from_secs(5)).unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(tk.status.connection_status(), TkConnectionStatus::Connected);
     }
 
-    /// Device Status
     #[test]
-    fn get_device_connected() {
+    fn in_process_connection_without_managers_connects() {
+        let settings = TkSettings {
+            connection: TkConnectionType::InProcess { managers: vec![] },
+            ..Default::default()
+        };
+
+        let mut tk = Telekinesis::connect_and_wait(settings, Duration::from_secs(5)).unwrap();
+
+        assert_eq!(tk.status.connection_status(), TkConnectionStatus::Connected);
+        assert!(tk.status.actuators().is_empty());
+    }
+
+    #[test]
+    fn connect_and_wait_fails_on_timeout() {
+        let settings = TkSettings {
+            connection: TkConnectionType::WebSocket(String::from("127.0.0.1:1")),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let result = Telekinesis::connect_and_wait(settings, Duration::from_millis(500));
+
+        assert!(result.is_err());
+        assert!(started.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn status_snapshot_reports_scanning() {
         let (mut tk, _) =
-            wait_for_connection(vec![scalar(1, "existing", ActuatorType::Vibrate)], None);
-        assert_eq!(
-            tk.status.get_actuator_status("existing (Vibrate)"),
-            TkConnectionStatus::Connected,
-            "Existing device returns connected"
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let snapshot = tk.status.get_snapshot();
+        assert!(!snapshot.scanning);
+        assert_eq!(snapshot.device_count, 1);
+        assert_eq!(snapshot.last_error, None);
+
+        tk.scan_for_devices();
+        assert_timeout!(tk.status.get_snapshot().scanning, "Awaiting scan started");
+        assert_eq!(tk.status.get_snapshot().status, "Connected");
+
+        tk.stop_scan();
+        assert_timeout!(!tk.status.get_snapshot().scanning, "Awaiting scan stopped");
+    }
+
+    #[test]
+    fn scan_stops_after_scan_duration() {
+        let settings = TkSettings {
+            scan_duration_ms: Some(200),
+            ..Default::default()
+        };
+        let (mut tk, _) = wait_for_connection(
+            vec![scalar(1, "vib1", ActuatorType::Vibrate)],
+            Some(settings),
         );
-        assert_eq!(
-            tk.status.get_actuator_status("not existing (Vibrate)"),
-            TkConnectionStatus::NotConnected,
-            "Non-existing device returns not connected"
+
+        tk.scan_for_devices();
+        assert_timeout!(tk.status.is_scanning(), "Awaiting scan started");
+        assert_timeout!(!tk.status.is_scanning(), "Awaiting scan stopped");
+    }
+
+    #[test]
+    fn event_callback_receives_events() {
+        let (tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let mut api = tk_new();
+        api.state.lock().unwrap().replace(tk);
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_clone = events.clone();
+
+        assert!(api.set_event_callback(move |evt| {
+            events_clone.lock().unwrap().push(evt.event_name.clone())
+        }));
+        assert!(api.tk_cmd("start_scan"));
+        assert_timeout!(
+            events.lock().unwrap().contains(&String::from("Tele_ScanStarted")),
+            "Awaiting scan started"
         );
+
+        api.clear_event_callback();
+        let count = events.lock().unwrap().len();
+        assert!(api.tk_cmd("stop_scan"));
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(events.lock().unwrap().len(), count);
     }
 
-    /// Events
+    #[test]
+    fn event_callback_takes_all_events_and_survives_connect() {
+        let (tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None);
+        let mut api = tk_new();
+        api.state.lock().unwrap().replace(tk);
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_clone = events.clone();
+        assert!(api.set_event_callback(move |evt| {
+            events_clone.lock().unwrap().push(evt.event_name.clone())
+        }));
+
+        // act
+        assert!(api.tk_cmd("start_scan"));
+        assert!(api.tk_qry_nxt_evt().is_empty());
+        assert!(api.tk_qry_nxt_evt_json().is_empty());
+        assert_timeout!(!events.lock().unwrap().is_empty(), "Awaiting first event");
+        events.lock().unwrap().clear();
+        assert!(api.tk_cmd("connect"));
+        api.tk_cmd("start_scan");
+
+        // assert
+        assert_timeout!(!events.lock().unwrap().is_empty(), "Events of new connection");
+        api.tk_cmd("disconnect");
+    }
+
+    #[test]
+    fn reconnects_after_server_disconnect() {
+        let (connector1, registry1) =
+            FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let (connector2, _) = FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let mut connectors = vec![connector2, connector1];
+        let mut settings = TkSettings::default();
+        settings.reconnect.backoff_ms = 10;
+        let mut tk = Telekinesis::connect_with_reconnect(
+            move || connectors.pop().map(|connector| async move { connector }),
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(1);
+        tk.scan_for_devices();
+        assert_timeout!(
+            tk.status.connection_status() == TkConnectionStatus::Connected,
+            "Awaiting connected"
+        );
+
+        registry1.disconnect_server();
+        let mut events = vec![];
+        while let Some(evt) = get_next_events_blocking(&tk.connection_events) {
+            let name = evt.event_name.to_string();
+            events.push(name.clone());
+            if name == "Tele_Reconnecting" {
+                break;
+            }
+        }
+        assert!(events.contains(&String::from("Tele_ConnectionError")));
+        assert_timeout!(
+            tk.status.connection_status() == TkConnectionStatus::Connected,
+            "Awaiting reconnected"
+        );
+    }
+
+    #[test]
+    fn disconnect_interrupts_reconnect_backoff() {
+        let (connector1, registry1) =
+            FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let (connector2, _) = FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let mut connectors = vec![connector2, connector1];
+        let mut settings = TkSettings::default();
+        settings.reconnect.backoff_ms = 10_000;
+        let mut tk = Telekinesis::connect_with_reconnect(
+            move || connectors.pop().map(|connector| async move { connector }),
+            Some(settings),
+            TkConnectionType::Test,
+        )
+        .unwrap();
+        tk.await_connect(1);
+
+        registry1.disconnect_server();
+        while let Some(evt) = get_next_events_blocking(&tk.connection_events) {
+            if evt.event_name == "Tele_Reconnecting" {
+                break;
+            }
+        }
+        let start = Instant::now();
+        tk.disconnect();
+
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
 
     #[test]
     fn process_next_events_after_action_returns_1() {
         let mut tk = Telekinesis::connect_with(
-            || async move { in_process_connector() },
+            || async move { in_process_connector(&[CommManagerKind::Btle]) },
             None,
             TkConnectionType::Test,
         )
@@ -789,10 +5191,29 @@ mod tests {
         get_next_events_blocking(&tk.connection_events);
     }
 
+    #[test]
+    fn events_pending_until_all_events_are_read() {
+        let (tk, _) = wait_for_connection(vec![], None);
+        while tk.events_pending() {
+            get_next_events_blocking(&tk.connection_events);
+        }
+
+        for i in 0..1000 {
+            try_send_event(&tk.client_event_sender, TkConnectionEvent::Reconnecting(i));
+        }
+        assert!(tk.events_pending());
+        for i in 0..1000 {
+            assert!(tk.events_pending());
+            let evt = get_next_events_blocking(&tk.connection_events).unwrap();
+            assert_eq!(evt.num_arg, f64::from(i));
+        }
+        assert!(!tk.events_pending());
+    }
+
     #[test]
     fn process_next_events_works() {
         let mut tk = Telekinesis::connect_with(
-            || async move { in_process_connector() },
+            || async move { in_process_connector(&[CommManagerKind::Btle]) },
             None,
             TkConnectionType::Test,
         )
@@ -823,9 +5244,9 @@ mod tests {
         let count = connector.devices.len();
 
         // act
-        let mut settings = settings.unwrap_or(TkSettings::default());
+        let mut settings = settings.unwrap_or_default();
         settings.pattern_path =
-            String::from("../contrib/Distribution/SKSE/Plugins/Telekinesis/Patterns");
+            String::from("../../contrib/Distribution/SKSE/Plugins/Telekinesis/Patterns");
         let mut tk = Telekinesis::connect_with(
             || async move { connector },
             Some(settings),